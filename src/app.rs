@@ -0,0 +1,210 @@
+use std::path::{Path, PathBuf};
+use anyhow::Context;
+
+use kripton_qr_reader::{AppResult, EncodeOptions, Symbol, encode};
+use kripton_qr_reader::caption::CaptionSource;
+use kripton_qr_reader::encode::{RenderFormat, render_contact_sheet, save_image, save_symbol};
+use kripton_qr_reader::transfer::Transport;
+use kripton_qr_reader::verify::{Verification, VerifiedFrom, verify_saved};
+use batch_input::{BatchItem, plan_paths};
+
+pub mod batch_input;
+pub mod cli;
+pub mod menu;
+pub mod otp;
+pub mod output;
+pub mod settings;
+
+/// Outcome of a batch generation.
+#[derive(Debug, Default)]
+pub struct BatchTally {
+    /// Codes saved and read back successfully.
+    pub success: usize,
+    /// Codes saved that could not be read back, such as Micro QR codes.
+    pub unverified: usize,
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+impl std::fmt::Display for BatchTally {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Success: {}, Unverified: {}, Skipped: {}, Failed: {}", self.success, self.unverified, self.skipped, self.failed)
+    }
+}
+
+// Codes that are saved but do not read back as their content count as failures. With
+// `skip_existing`, codes whose file already exists are left alone.
+pub fn generate_qr_batch(
+    items: &[BatchItem],
+    output_dir: &Path,
+    template: &str,
+    format: RenderFormat,
+    options: &EncodeOptions,
+    robustness: bool,
+    skip_existing: bool,
+) -> AppResult<BatchTally> {
+    if !output_dir.exists() {
+        std::fs::create_dir_all(output_dir)
+            .context(format!("Could not create output directory: {}", output_dir.display()))?;
+    }
+    let paths = plan_paths(items, output_dir, template, format)?;
+
+    let mut tally = BatchTally::default();
+
+    for (item, (path, format)) in items.iter().zip(paths) {
+        let filename = path.strip_prefix(output_dir).unwrap_or(&path).display().to_string();
+        if skip_existing && path.exists() {
+            println!("- {} already exists, skipped.", filename);
+            tally.skipped += 1;
+            continue;
+        }
+        if let Some(parent) = path.parent().filter(|p| !p.exists()) {
+            std::fs::create_dir_all(parent)
+                .context(format!("Could not create output directory: {}", parent.display()))?;
+        }
+        let mut options = EncodeOptions { ec_level: item.ec_level.unwrap_or(options.ec_level), ..options.clone() };
+        // A row's caption column is drawn as its label, whatever caption is configured.
+        if item.caption.is_some() {
+            options.caption.source = CaptionSource::Label;
+        }
+        options.caption.resolve(item.content.as_bytes(), item.caption.as_deref());
+
+        match encode(&item.content, &options) {
+            Ok(code) => {
+                match save_symbol(&code, &path, format, &options) {
+                    Ok(_) => {
+                        let preview: String = item.content.chars().take(50).map(|c| if c.is_control() { ' ' } else { c }).collect();
+                        let paths = std::slice::from_ref(&path);
+                        match verify_saved(paths, format, std::slice::from_ref(&code), item.content.as_bytes(), &options, robustness) {
+                            Ok(verification) if !verification.readable => {
+                                eprintln!("✗ {} was saved but does not read back as code {}.{}", filename, item.number, unreadable_hint(&options));
+                                tally.failed += 1;
+                            }
+                            Ok(verification) => {
+                                let robustness = describe_robustness(&verification)
+                                    .map(|summary| format!(" [robustness: {}]", summary))
+                                    .unwrap_or_default();
+                                println!("✓ {} created: {}{}", filename, preview, robustness);
+                                tally.success += 1;
+                            }
+                            Err(e) => {
+                                println!("? {} created but not verified: {} ({:#})", filename, preview, e);
+                                tally.unverified += 1;
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("✗ Could not save {}: {:?}", filename, e);
+                        tally.failed += 1;
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("✗ Could not generate QR code {}: {:?}", item.number, e);
+                tally.failed += 1;
+            }
+        }
+    }
+
+    Ok(tally)
+}
+
+// Saves a single symbol at `path`. A Structured Append sequence is saved as numbered
+// files next to it (`name_01.png`, ...), or on one PNG contact sheet at `path`.
+pub fn save_symbols(
+    symbols: &[Symbol],
+    path: &Path,
+    contact_sheet: bool,
+    format: RenderFormat,
+    options: &EncodeOptions,
+) -> AppResult<Vec<PathBuf>> {
+    if contact_sheet && symbols.len() > 1 && format.is_vector() {
+        anyhow::bail!("Contact sheets can only be saved as PNG.");
+    }
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty() && !p.exists()) {
+        std::fs::create_dir_all(parent)
+            .context(format!("Could not create output directory: {}", parent.display()))?;
+    }
+
+    if symbols.len() == 1 {
+        save_symbol(&symbols[0], path, format, options)?;
+        return Ok(vec![path.to_path_buf()]);
+    }
+    if contact_sheet {
+        let columns = (symbols.len() as f64).sqrt().ceil() as usize;
+        save_image(&render_contact_sheet(symbols, columns, options)?, path)?;
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "qr_code".to_string());
+    let mut paths = Vec::with_capacity(symbols.len());
+    for (i, symbol) in symbols.iter().enumerate() {
+        let part_path = path.with_file_name(format!("{}_{:02}.{}", stem, i + 1, format.extension()));
+        let caption = options.caption.for_part(i, symbols.len());
+        save_symbol(symbol, &part_path, format, &EncodeOptions { caption, ..options.clone() })?;
+        paths.push(part_path);
+    }
+    Ok(paths)
+}
+
+// Decodes the files `symbols` were saved to and reports how they held up. Returns
+// `Some(false)` when they do not read back as `data`, and `None` when they could not be
+// checked, as for Micro QR codes.
+pub fn verify_generated(
+    paths: &[PathBuf],
+    format: RenderFormat,
+    symbols: &[Symbol],
+    data: &[u8],
+    options: &EncodeOptions,
+    robustness: bool,
+) -> Option<bool> {
+    match verify_saved(paths, format, symbols, data, options, robustness) {
+        Ok(verification) if verification.readable => {
+            match verification.from {
+                VerifiedFrom::SavedFiles => eprintln!("✓ Verified: the saved file reads back as the input."),
+                VerifiedFrom::SymbolMatrix => eprintln!(
+                    "✓ Verified: the code reads back as the input. {} files are not read back; the same symbol was checked as an image.",
+                    format.extension().to_uppercase()
+                ),
+            }
+            if let Some(summary) = describe_robustness(&verification) {
+                eprintln!("  Robustness: {}", summary);
+            }
+            Some(true)
+        }
+        Ok(_) => {
+            eprintln!("⚠ The saved QR code does not read back as the input.{}", unreadable_hint(options));
+            Some(false)
+        }
+        Err(e) => {
+            eprintln!("Note: the saved code was not verified. {:#}", e);
+            None
+        }
+    }
+}
+
+// For example "zlib, base45".
+pub fn describe_packing(transport: Transport, compress: bool) -> String {
+    match (compress, transport) {
+        (true, Transport::Raw) => "zlib".to_string(),
+        (true, transport) => format!("zlib, {}", transport),
+        (false, transport) => transport.to_string(),
+    }
+}
+
+// For example "fair (downscale ✓, blur ✗, JPEG ✓)".
+fn describe_robustness(verification: &Verification) -> Option<String> {
+    let grade = verification.grade()?;
+    let checks: Vec<String> = verification.robustness.iter()
+        .map(|(degradation, ok)| format!("{} {}", degradation, if *ok { "✓" } else { "✗" }))
+        .collect();
+    Some(format!("{} ({})", grade, checks.join(", ")))
+}
+
+fn unreadable_hint(options: &EncodeOptions) -> &'static str {
+    if options.style.is_plain() {
+        " Try a higher ECC level or a larger module size."
+    } else {
+        " Try a smaller logo, square modules or square finders."
+    }
+}
@@ -0,0 +1,387 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use anyhow::Context;
+use clap::ValueEnum;
+use qrcode::EcLevel;
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use kripton_qr_reader::AppResult;
+use kripton_qr_reader::encode::{RenderFormat, parse_ec_level};
+
+// Template used unless another one is given; it keeps the names of earlier versions.
+pub const DEFAULT_TEMPLATE: &str = "qr_code_{n}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum InputFormat {
+    /// One code per non-empty line
+    Lines,
+    /// Comma-separated values with a header row
+    Csv,
+    /// Tab-separated values with a header row
+    Tsv,
+    /// One JSON object per line
+    Jsonl,
+}
+
+impl InputFormat {
+    // Goes by the extension; anything unrecognized is read as lines.
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+        match extension.as_str() {
+            "csv" => InputFormat::Csv,
+            "tsv" | "tab" => InputFormat::Tsv,
+            "jsonl" | "ndjson" => InputFormat::Jsonl,
+            _ => InputFormat::Lines,
+        }
+    }
+}
+
+// Column names and values of one input row.
+type Row = Vec<(String, Zeroizing<String>)>;
+
+// One code to generate. Every column is kept so filename templates can use it.
+pub struct BatchItem {
+    /// Position among the codes, from 1.
+    pub number: usize,
+    pub content: Zeroizing<String>,
+    pub filename: Option<String>,
+    pub ec_level: Option<EcLevel>,
+    pub format: Option<RenderFormat>,
+    pub caption: Option<String>,
+    pub columns: Row,
+}
+
+impl BatchItem {
+    fn column(&self, name: &str) -> Option<&str> {
+        self.columns.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+}
+
+// Reads the codes to generate from `path`. Rows without content are skipped with a note; an
+// invalid ECC level or format stops the batch before anything is generated.
+pub fn read_batch(path: &Path, format: InputFormat) -> AppResult<Vec<BatchItem>> {
+    let rows = match format {
+        InputFormat::Lines => {
+            let content = Zeroizing::new(std::fs::read_to_string(path)
+                .context(format!("Could not read file: {}", path.display()))?);
+            content.lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| vec![("content".to_string(), Zeroizing::new(line.to_string()))])
+                .collect()
+        }
+        InputFormat::Csv => read_table(path, b',')?,
+        InputFormat::Tsv => read_table(path, b'\t')?,
+        InputFormat::Jsonl => read_jsonl(path)?,
+    };
+
+    let mut items = Vec::with_capacity(rows.len());
+    for (i, columns) in rows.into_iter().enumerate() {
+        // Data rows start on line 2 of a table, after the header.
+        let row = if matches!(format, InputFormat::Csv | InputFormat::Tsv) { i + 2 } else { i + 1 };
+        let value = |names: &[&str]| columns.iter()
+            .find(|(n, _)| names.iter().any(|name| n.eq_ignore_ascii_case(name)))
+            .map(|(_, v)| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+        let Some(content) = columns.iter().find(|(n, _)| n.eq_ignore_ascii_case("content")).map(|(_, v)| v.clone())
+            .filter(|v| !v.trim().is_empty()) else {
+            if format != InputFormat::Lines {
+                eprintln!("Note: skipped row {}, which has no content.", row);
+            }
+            continue;
+        };
+        let ec_level = value(&["ecc", "ec_level"]).map(|v| parse_ec_level(&v))
+            .transpose()
+            .context(format!("Row {}", row))?;
+        let format = value(&["format"]).map(|v| v.parse::<RenderFormat>())
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("Row {}: {}", row, e))?;
+        items.push(BatchItem {
+            number: items.len() + 1,
+            content,
+            filename: value(&["filename"]),
+            ec_level,
+            format,
+            caption: value(&["caption"]),
+            columns,
+        });
+    }
+    Ok(items)
+}
+
+fn read_table(path: &Path, delimiter: u8) -> AppResult<Vec<Row>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_path(path)
+        .context(format!("Could not read file: {}", path.display()))?;
+    let headers: Vec<String> = reader.headers()
+        .context(format!("Could not read the header row of {}", path.display()))?
+        .iter()
+        .map(|header| header.trim().to_string())
+        .collect();
+    if !headers.iter().any(|header| header.eq_ignore_ascii_case("content")) {
+        anyhow::bail!("{} has no 'content' column; found: {}", path.display(), headers.join(", "));
+    }
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.context(format!("Could not read {}", path.display()))?;
+        rows.push(headers.iter().cloned()
+            .zip(record.iter().map(|value| Zeroizing::new(value.to_string())))
+            .collect());
+    }
+    Ok(rows)
+}
+
+// Strings are taken as they are and other values as their JSON text.
+fn read_jsonl(path: &Path) -> AppResult<Vec<Row>> {
+    let content = Zeroizing::new(std::fs::read_to_string(path)
+        .context(format!("Could not read file: {}", path.display()))?);
+    let mut rows = Vec::new();
+    for (i, line) in content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let object: serde_json::Map<String, Value> = serde_json::from_str(line)
+            .context(format!("Line {} of {} is not a JSON object", i + 1, path.display()))?;
+        rows.push(object.into_iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    Value::String(value) => value,
+                    Value::Null => String::new(),
+                    value => value.to_string(),
+                };
+                (name, Zeroizing::new(value))
+            })
+            .collect());
+    }
+    Ok(rows)
+}
+
+// Chooses the output path and format of every item. A filename column wins over `template`;
+// placeholders such as `{id}` take the row's column values, with `{n}` for the position and
+// `{content}` for the content. Values are made safe as file names, and names used twice in
+// the batch get a `_2`, `_3`, ... suffix.
+pub fn plan_paths(
+    items: &[BatchItem],
+    output_dir: &Path,
+    template: &str,
+    default_format: RenderFormat,
+) -> AppResult<Vec<(PathBuf, RenderFormat)>> {
+    // A placeholder no row has a column for is a typo rather than a missing value.
+    for placeholder in placeholders(template)? {
+        if !matches!(placeholder, "n" | "content") && !items.iter().any(|item| item.column(placeholder).is_some()) {
+            anyhow::bail!("Unknown placeholder {{{}}} in filename template; use {{n}}, {{content}} or a column name.", placeholder);
+        }
+    }
+
+    let mut used = HashSet::new();
+    let mut paths = Vec::with_capacity(items.len());
+    for item in items {
+        let name = match &item.filename {
+            Some(filename) => path_component(filename),
+            None => render_template(template, item)?,
+        };
+        let name = name.unwrap_or_else(|| {
+            let fallback = format!("qr_code_{:03}", item.number);
+            eprintln!("Note: code {} has an empty file name; it is saved as {}.", item.number, fallback);
+            fallback
+        });
+        let (name, format) = with_extension(&name, item.format, default_format);
+
+        let mut relative = name.clone();
+        let mut suffix = 2;
+        while !used.insert(relative.to_lowercase()) {
+            let (stem, extension) = relative_parts(&name);
+            relative = format!("{}_{}.{}", stem, suffix, extension);
+            suffix += 1;
+        }
+        if relative != name {
+            eprintln!("Note: {} is used by an earlier row; code {} is saved as {}.", name, item.number, relative);
+        }
+        paths.push((output_dir.join(relative), format));
+    }
+    Ok(paths)
+}
+
+fn placeholders(template: &str) -> AppResult<Vec<&str>> {
+    let mut placeholders = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..].find('}')
+            .context(format!("Unclosed placeholder in filename template: {}", template))?;
+        placeholders.push(&rest[start + 1..start + end]);
+        rest = &rest[start + end + 1..];
+    }
+    Ok(placeholders)
+}
+
+// `None` when the name comes out empty, as when every value it uses is empty.
+fn render_template(template: &str, item: &BatchItem) -> AppResult<Option<String>> {
+    let mut name = String::new();
+    let mut rest = template;
+    for placeholder in placeholders(template)? {
+        let start = rest.find('{').unwrap_or(0);
+        name.push_str(&rest[..start]);
+        let value = match placeholder {
+            "n" => format!("{:03}", item.number),
+            "content" => item.content.chars().take(50).collect(),
+            _ => item.column(placeholder).unwrap_or("").to_string(),
+        };
+        name.push_str(&sanitize(&value));
+        rest = &rest[start + placeholder.len() + 2..];
+    }
+    name.push_str(rest);
+
+    // Slashes in the template itself create subdirectories; empty ones are dropped.
+    let components: Vec<String> = name.split(['/', '\\']).filter_map(path_component).collect();
+    Ok((!components.is_empty()).then(|| components.join("/")))
+}
+
+// Keeps a known image extension, replaced by the row's format when it has one; otherwise
+// the extension of the row's format, or of the batch format, is appended.
+fn with_extension(name: &str, row_format: Option<RenderFormat>, default_format: RenderFormat) -> (String, RenderFormat) {
+    let named_format = RenderFormat::from_path(Path::new(name));
+    let stem = match named_format {
+        Some(_) => relative_parts(name).0,
+        None => name,
+    };
+    let format = row_format.or(named_format).unwrap_or(default_format);
+    (format!("{}.{}", stem, format.extension()), format)
+}
+
+fn relative_parts(name: &str) -> (&str, &str) {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && !extension.contains('/') => (stem, extension),
+        _ => (name, ""),
+    }
+}
+
+// Replaces path separators, characters Windows reserves and control characters with
+// underscores, so a column value cannot add directories or break the name.
+fn sanitize(value: &str) -> String {
+    value.chars()
+        .map(|c| if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') { '_' } else { c })
+        .collect()
+}
+
+// Makes `name` usable as one path component on Linux, macOS and Windows: leading and
+// trailing dots and spaces are dropped, reserved Windows device names get an underscore and
+// long names are cut. `None` when nothing is left.
+fn path_component(name: &str) -> Option<String> {
+    let mut name: String = sanitize(name).trim_matches(['.', ' ']).chars().take(100).collect();
+    if name.is_empty() {
+        return None;
+    }
+    let device = name.split('.').next().unwrap_or("").to_ascii_uppercase();
+    let reserved = matches!(device.as_str(), "CON" | "PRN" | "AUX" | "NUL")
+        || ((device.starts_with("COM") || device.starts_with("LPT"))
+            && device.len() == 4 && device.as_bytes()[3].is_ascii_digit());
+    if reserved {
+        name.insert(0, '_');
+    }
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str, content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-batch-input-{}-{}", env!("CARGO_PKG_NAME"), std::process::id(), name));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn item(number: usize, columns: &[(&str, &str)]) -> BatchItem {
+        let columns: Row = columns.iter().map(|(n, v)| (n.to_string(), Zeroizing::new(v.to_string()))).collect();
+        let value = |name: &str| columns.iter().find(|(n, _)| n == name).map(|(_, v)| v.to_string());
+        BatchItem {
+            number,
+            content: Zeroizing::new(value("content").unwrap_or_else(|| "data".to_string())),
+            filename: value("filename"),
+            ec_level: None,
+            format: None,
+            caption: None,
+            columns,
+        }
+    }
+
+    fn names(items: &[BatchItem], template: &str) -> Vec<String> {
+        let dir = Path::new("out");
+        plan_paths(items, dir, template, RenderFormat::Png).unwrap().into_iter()
+            .map(|(path, _)| path.strip_prefix(dir).unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn keeps_the_default_names() {
+        let items = [item(1, &[]), item(2, &[])];
+        assert_eq!(names(&items, DEFAULT_TEMPLATE), ["qr_code_001.png", "qr_code_002.png"]);
+    }
+
+    #[test]
+    fn keeps_values_inside_the_output_directory() {
+        let items = [
+            item(1, &[("id", "../../etc/passwd")]),
+            item(2, &[("id", r"..\..\windows\system32")]),
+            item(3, &[("filename", "../escape.png")]),
+            item(4, &[("filename", r"..\escape.svg")]),
+            item(5, &[("filename", "/abs/path")]),
+        ];
+        let names = names(&items, "{id}");
+        for name in &names {
+            assert!(!name.contains('/') && !name.contains('\\'), "{}", name);
+            assert!(!name.starts_with('.'), "{}", name);
+        }
+        assert_eq!(names[3], "_escape.svg");
+    }
+
+    #[test]
+    fn renames_windows_device_names() {
+        assert_eq!(path_component("CON.png").as_deref(), Some("_CON.png"));
+        assert_eq!(path_component("com1").as_deref(), Some("_com1"));
+        assert_eq!(path_component("lpt9.svg").as_deref(), Some("_lpt9.svg"));
+        assert_eq!(path_component("console").as_deref(), Some("console"));
+        assert_eq!(path_component("com10").as_deref(), Some("com10"));
+        assert_eq!(path_component(" .. "), None);
+        assert_eq!(sanitize("a:b*c?\"<>|\n"), "a_b_c______");
+    }
+
+    #[test]
+    fn numbers_names_used_twice() {
+        let items = [
+            item(1, &[("filename", "Ticket.png")]),
+            item(2, &[("filename", "ticket.png")]),
+            item(3, &[("filename", "TICKET")]),
+        ];
+        assert_eq!(names(&items, DEFAULT_TEMPLATE), ["Ticket.png", "ticket_2.png", "TICKET_3.png"]);
+    }
+
+    #[test]
+    fn rejects_bad_templates() {
+        let items = [item(1, &[("id", "7")])];
+        assert!(plan_paths(&items, Path::new("out"), "{nope}", RenderFormat::Png).is_err());
+        assert!(plan_paths(&items, Path::new("out"), "code_{id", RenderFormat::Png).is_err());
+        assert_eq!(names(&items, "tickets/{id}_{n}"), ["tickets/7_001.png"]);
+    }
+
+    #[test]
+    fn requires_a_content_column() {
+        let path = temp_file("no-content.csv", "id,text\n1,hello\n");
+        let result = read_batch(&path, InputFormat::Csv);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.map(|_| ()).unwrap_err().to_string().contains("no 'content' column"));
+    }
+
+    #[test]
+    fn reads_jsonl_values_as_text() {
+        let path = temp_file("values.jsonl", "{\"content\": 42, \"id\": true, \"caption\": null}\n\n{\"content\": \"x\", \"id\": [1, 2]}\n{\"id\": 3}\n");
+        let items = read_batch(&path, InputFormat::Jsonl).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].content.as_str(), "42");
+        assert_eq!(items[0].column("id"), Some("true"));
+        assert_eq!(items[0].caption, None);
+        assert_eq!(items[1].column("id"), Some("[1,2]"));
+        assert_eq!(items[1].number, 2);
+    }
+}
@@ -0,0 +1,732 @@
+use std::path::PathBuf;
+use anyhow::Context;
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use image::Rgba;
+use qrcode::EcLevel;
+use zeroize::Zeroizing;
+
+use kripton_qr_reader::{AppResult, DecodeMode, DecodeOptions, EncodeOptions, encode_structured_append};
+use kripton_qr_reader::batch::{decode_paths, resolve_threads};
+use kripton_qr_reader::caption::{CaptionPosition, CaptionSource};
+use kripton_qr_reader::encode::{RenderFormat, ec_level_name, encode_bytes, parse_color, parse_ec_level, render_terminal};
+use kripton_qr_reader::input::{ScanOptions, collect_image_files};
+use kripton_qr_reader::payload::{Contact, Email, Event, EventTime, Geo, Payload, Sms, Wifi, WifiSecurity};
+use kripton_qr_reader::preprocess::Pipeline;
+use kripton_qr_reader::style::{FinderShape, ModuleShape};
+use kripton_qr_reader::transfer::{Transport, encode_fitted, pack};
+use super::batch_input::{DEFAULT_TEMPLATE, InputFormat, read_batch};
+use super::{describe_packing, generate_qr_batch, save_symbols, verify_generated};
+use super::otp::{OtpExportFormat, collect_accounts, export_accounts, print_accounts};
+use super::output::{OutputFormat, copy_single_result, print_qr_results, print_report_warnings, save_payloads, write_qr_content};
+use super::settings::{APP_NAME, AppSettings, parse_frame_stride, save_settings};
+
+#[derive(Parser)]
+#[command(name = APP_NAME, version, about = "Read and generate QR codes. Starts the interactive menu when no command is given.")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Decode QR codes from one or more image files
+    Decode {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Copy the content to the clipboard when exactly one code is found
+        #[arg(long)]
+        copy: bool,
+        /// Write the raw payload bytes to this file (numbered files when several codes are found)
+        #[arg(long)]
+        save_bytes: Option<PathBuf>,
+        /// Read saved payloads as raw bytes, base45 or base64 text, as written by `generate --transport`
+        #[arg(long, default_value_t, requires = "save_bytes")]
+        transport: Transport,
+        /// Inflate saved payloads written by `generate --compress`
+        #[arg(long, requires = "save_bytes")]
+        decompress: bool,
+        #[command(flatten)]
+        decode: DecodeArgs,
+    },
+    /// Decode QR codes from every supported image in a directory
+    ScanDir {
+        /// Directory to scan (defaults to the configured scan directory)
+        dir: Option<PathBuf>,
+        #[command(flatten)]
+        scan: ScanArgs,
+        /// Save decoded contents to this file
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        decode: DecodeArgs,
+    },
+    /// Generate a QR code from text, a file or a structured payload such as WiFi credentials
+    #[command(subcommand_negates_reqs = true, group(ArgGroup::new("several").args(["split", "fit"])))]
+    Generate {
+        #[arg(required_unless_present = "file", conflicts_with = "file")]
+        text: Option<String>,
+        /// Encode the raw bytes of this file instead of text
+        #[arg(long)]
+        file: Option<PathBuf>,
+        /// Save to this file instead of printing to the terminal
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// File format: png, svg, eps or pdf (defaults to the output file's extension, then png)
+        #[arg(long)]
+        format: Option<RenderFormat>,
+        /// Split data too long for one code into up to 16 Structured Append codes
+        #[arg(short, long)]
+        split: bool,
+        /// Use the fewest codes, splitting as needed, with the highest ECC level that allows it
+        #[arg(long, conflicts_with_all = ["split", "ecc"])]
+        fit: bool,
+        /// How to write the data: raw bytes, base45 or base64 text
+        #[arg(long, default_value_t)]
+        transport: Transport,
+        /// Compress the data with zlib first
+        #[arg(long)]
+        compress: bool,
+        /// Save split or fitted codes on one contact sheet instead of numbered PNGs
+        #[arg(long, requires_all = ["several", "output"])]
+        contact_sheet: bool,
+        /// Also check the saved code still reads after downscaling, blur and JPEG compression
+        #[arg(long, requires = "output")]
+        robustness: bool,
+        #[command(flatten)]
+        encode: Box<EncodeArgs>,
+        #[command(subcommand)]
+        payload: Option<PayloadCommand>,
+    },
+    /// Generate one QR code per non-empty line of a text file, or per row of a CSV, TSV or JSONL file
+    BatchGenerate {
+        file: PathBuf,
+        /// Input format (defaults to the file's extension: .csv, .tsv, .jsonl, else lines)
+        #[arg(long, value_enum)]
+        input_format: Option<InputFormat>,
+        /// Output directory (defaults to the configured output or scan directory)
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+        /// File name template, e.g. "{id}_{name}" or "{site}/{id}.svg"; a filename column takes precedence
+        #[arg(long, default_value = DEFAULT_TEMPLATE)]
+        name_template: String,
+        /// Leave codes whose file already exists untouched, to resume an interrupted batch
+        #[arg(long)]
+        skip_existing: bool,
+        /// File format: png, svg, eps or pdf (unless the name or a format column gives one)
+        #[arg(long, default_value_t)]
+        format: RenderFormat,
+        /// Also check every code still reads after downscaling, blur and JPEG compression
+        #[arg(long)]
+        robustness: bool,
+        #[command(flatten)]
+        encode: EncodeArgs,
+    },
+    /// List the accounts in otpauth:// and authenticator export QR codes, show their codes or export them
+    Otp {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Show the current code of each account
+        #[arg(long)]
+        codes: bool,
+        /// Show each account's secret in base32
+        #[arg(long)]
+        show_secrets: bool,
+        /// Write the accounts to this file, or to this directory for QR codes
+        #[arg(short, long)]
+        export: Option<PathBuf>,
+        /// Export format: uri, json (andOTP) or qr
+        #[arg(long, value_enum, default_value_t, requires = "export")]
+        export_format: OtpExportFormat,
+        #[command(flatten)]
+        decode: DecodeArgs,
+    },
+    /// Show or change persisted settings
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+/// Structured content for `generate`; values are escaped for their format.
+#[derive(Subcommand)]
+pub enum PayloadCommand {
+    /// WiFi network credentials that phones can join directly
+    Wifi {
+        /// Network name
+        #[arg(long)]
+        ssid: String,
+        /// Network password (not needed with --security none)
+        #[arg(long)]
+        password: Option<String>,
+        /// Encryption: wpa (also WPA2/WPA3), wep or none
+        #[arg(long, default_value = "wpa")]
+        security: WifiSecurity,
+        /// The network does not broadcast its name
+        #[arg(long)]
+        hidden: bool,
+    },
+    /// Contact card in vCard format
+    Vcard(ContactArgs),
+    /// Contact card in the shorter MeCard format
+    Mecard(ContactArgs),
+    /// Geographic location
+    Geo {
+        #[arg(allow_negative_numbers = true)]
+        latitude: f64,
+        #[arg(allow_negative_numbers = true)]
+        longitude: f64,
+        /// Place name shown by map apps
+        #[arg(long)]
+        label: Option<String>,
+    },
+    /// Text message to a phone number
+    Sms {
+        number: String,
+        message: Option<String>,
+    },
+    /// Email to an address, with optional subject and body
+    Email {
+        to: String,
+        #[arg(long)]
+        subject: Option<String>,
+        #[arg(long)]
+        body: Option<String>,
+    },
+    /// Calendar event
+    Event {
+        summary: String,
+        /// Start, e.g. 2026-10-16 or "2026-10-16 14:30" (append Z for UTC)
+        #[arg(long)]
+        start: EventTime,
+        /// End, in the same form as --start
+        #[arg(long)]
+        end: Option<EventTime>,
+        #[arg(long)]
+        location: Option<String>,
+        #[arg(long)]
+        description: Option<String>,
+    },
+}
+
+#[derive(Args)]
+pub struct ContactArgs {
+    #[arg(long)]
+    first_name: Option<String>,
+    #[arg(long)]
+    last_name: Option<String>,
+    #[arg(long)]
+    organization: Option<String>,
+    /// Job title (vCard only)
+    #[arg(long)]
+    title: Option<String>,
+    #[arg(long)]
+    phone: Option<String>,
+    #[arg(long)]
+    email: Option<String>,
+    #[arg(long)]
+    address: Option<String>,
+    #[arg(long)]
+    url: Option<String>,
+    #[arg(long)]
+    note: Option<String>,
+}
+
+impl ContactArgs {
+    fn contact(self) -> Contact {
+        Contact {
+            first_name: self.first_name.unwrap_or_default(),
+            last_name: self.last_name.unwrap_or_default(),
+            organization: self.organization,
+            title: self.title,
+            phone: self.phone,
+            email: self.email,
+            address: self.address,
+            url: self.url,
+            note: self.note,
+        }
+    }
+}
+
+impl PayloadCommand {
+    fn payload(self) -> Payload {
+        match self {
+            PayloadCommand::Wifi { ssid, password, security, hidden } => Payload::Wifi(Wifi {
+                ssid,
+                password: Zeroizing::new(password.unwrap_or_default()),
+                security,
+                hidden,
+            }),
+            PayloadCommand::Vcard(contact) => Payload::VCard(contact.contact()),
+            PayloadCommand::Mecard(contact) => Payload::MeCard(contact.contact()),
+            PayloadCommand::Geo { latitude, longitude, label } => Payload::Geo(Geo { latitude, longitude, label }),
+            PayloadCommand::Sms { number, message } => Payload::Sms(Sms { number, message }),
+            PayloadCommand::Email { to, subject, body } => Payload::Email(Email { to, subject, body }),
+            PayloadCommand::Event { summary, start, end, location, description } => {
+                Payload::Event(Event { summary, start, end, location, description })
+            }
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct DecodeArgs {
+    /// Output format (defaults to the configured output format)
+    #[arg(short, long, value_enum)]
+    format: Option<OutputFormat>,
+    /// Show version, ECC level, mask, corners and technique in text output
+    #[arg(short, long)]
+    verbose: bool,
+    /// Preprocessing pipeline profile (JSON) to use instead of the configured one
+    #[arg(long)]
+    pipeline: Option<PathBuf>,
+    /// When to stop trying variants: exhaustive (default), first-hit, or a number of expected codes
+    #[arg(long)]
+    mode: Option<DecodeMode>,
+    /// Number of worker threads (0 for one per CPU; defaults to the configured value)
+    #[arg(short, long)]
+    threads: Option<usize>,
+    /// Only decode every N-th frame of animated GIF and WebP files
+    #[arg(long, value_parser = parse_frame_stride)]
+    frame_stride: Option<usize>,
+}
+
+impl DecodeArgs {
+    fn threads(&self, settings: &AppSettings) -> usize {
+        self.threads.unwrap_or(settings.worker_threads)
+    }
+
+    fn format(&self, settings: &AppSettings) -> OutputFormat {
+        self.format.unwrap_or(settings.output_format)
+    }
+
+    fn verbose(&self, settings: &AppSettings) -> bool {
+        self.verbose || settings.verbose_output
+    }
+
+    fn options(&self, settings: &AppSettings) -> AppResult<DecodeOptions> {
+        let mut options = settings.decode_options()?;
+        if let Some(path) = &self.pipeline {
+            options.pipeline = Pipeline::load(path)?;
+        }
+        if let Some(mode) = self.mode {
+            options.mode = mode;
+        }
+        if settings.parallel_variants {
+            options.variant_threads = resolve_threads(self.threads(settings));
+        }
+        if let Some(stride) = self.frame_stride {
+            options.frame_stride = stride;
+        }
+        Ok(options)
+    }
+}
+
+#[derive(Args)]
+pub struct ScanArgs {
+    /// Also scan subdirectories
+    #[arg(short, long)]
+    recursive: bool,
+    /// Deepest subdirectory level to scan (implies --recursive)
+    #[arg(long)]
+    max_depth: Option<usize>,
+    /// Follow symbolic links while scanning
+    #[arg(long)]
+    follow_links: bool,
+    /// Only scan files whose relative path matches this glob (repeatable)
+    #[arg(long)]
+    include: Vec<String>,
+    /// Skip files and directories whose relative path matches this glob (repeatable)
+    #[arg(long)]
+    exclude: Vec<String>,
+    /// Also scan hidden files and directories
+    #[arg(long)]
+    hidden: bool,
+}
+
+impl ScanArgs {
+    fn options(&self, settings: &AppSettings) -> ScanOptions {
+        let mut options = settings.scan.clone();
+        options.recursive |= self.recursive || self.max_depth.is_some();
+        options.max_depth = self.max_depth.or(options.max_depth);
+        options.follow_links |= self.follow_links;
+        options.include_hidden |= self.hidden;
+        if !self.include.is_empty() {
+            options.include = self.include.clone();
+        }
+        options.exclude.extend(self.exclude.iter().cloned());
+        options
+    }
+}
+
+#[derive(Args)]
+pub struct EncodeArgs {
+    /// Error correction level: L, M, Q or H (defaults to the configured level)
+    #[arg(short, long, value_parser = parse_ec_level)]
+    ecc: Option<EcLevel>,
+    /// Smallest symbol version to use
+    #[arg(long, conflicts_with = "fixed_version")]
+    min_version: Option<i16>,
+    /// Use exactly this symbol version
+    #[arg(long)]
+    fixed_version: Option<i16>,
+    /// Generate Micro QR codes (versions M1 to M4)
+    #[arg(long)]
+    micro: bool,
+    /// Mask pattern, 0-7 (0-3 for Micro QR)
+    #[arg(long)]
+    mask: Option<u8>,
+    /// Width of the quiet zone, in modules
+    #[arg(long)]
+    quiet_zone: Option<u32>,
+    /// Size of one module, in pixels
+    #[arg(long)]
+    module_size: Option<u32>,
+    /// Color of dark modules, e.g. #1a2b3c, with optional alpha as in #1a2b3c80
+    #[arg(long, value_parser = parse_color)]
+    dark: Option<Rgba<u8>>,
+    /// Color of light modules and the quiet zone; `transparent` for no background
+    #[arg(long, value_parser = parse_color)]
+    light: Option<Rgba<u8>>,
+    /// Shape of data modules: square, rounded or dots (PNG only)
+    #[arg(long)]
+    modules: Option<ModuleShape>,
+    /// Shape of the corner finder patterns: square, rounded or circle (PNG only)
+    #[arg(long)]
+    finders: Option<FinderShape>,
+    /// Image to place in the center; raises the ECC level to H (PNG only)
+    #[arg(long)]
+    logo: Option<PathBuf>,
+    /// Logo width as a fraction of the code width, 0.05 to 0.35
+    #[arg(long)]
+    logo_size: Option<f32>,
+    /// Text to print with the code: off, content, truncated or label (PNG only)
+    #[arg(long)]
+    caption: Option<CaptionSource>,
+    /// Label to print with the code, such as an asset ID; implies --caption label
+    #[arg(long)]
+    caption_text: Option<String>,
+    /// Where the caption goes: below or above the code
+    #[arg(long)]
+    caption_position: Option<CaptionPosition>,
+    /// Longest truncated caption, in characters
+    #[arg(long)]
+    caption_length: Option<usize>,
+    /// Space around the caption, in modules
+    #[arg(long)]
+    caption_margin: Option<u32>,
+    /// Size of one font dot, in pixels (defaults to the largest that fits)
+    #[arg(long)]
+    caption_scale: Option<u32>,
+}
+
+impl EncodeArgs {
+    fn options(&self, settings: &AppSettings) -> AppResult<EncodeOptions> {
+        let mut options = settings.encode_options();
+        if let Some(ec_level) = self.ecc {
+            options.ec_level = ec_level;
+        }
+        if let Some(version) = self.min_version {
+            options.version = Some(version);
+            options.fixed_version = false;
+        }
+        if let Some(version) = self.fixed_version {
+            options.version = Some(version);
+            options.fixed_version = true;
+        }
+        options.micro |= self.micro;
+        options.mask = self.mask.or(options.mask);
+        options.quiet_zone = self.quiet_zone.or(options.quiet_zone);
+        options.module_size = self.module_size.or(options.module_size);
+        options.dark_color = self.dark.unwrap_or(options.dark_color);
+        options.light_color = self.light.unwrap_or(options.light_color);
+        options.style.modules = self.modules.unwrap_or(options.style.modules);
+        options.style.finders = self.finders.unwrap_or(options.style.finders);
+        options.style.logo = self.logo.clone().or(options.style.logo);
+        options.style.logo_size = self.logo_size.unwrap_or(options.style.logo_size);
+        let caption = &mut options.caption;
+        if let Some(label) = &self.caption_text {
+            caption.label = Some(label.clone());
+            caption.source = CaptionSource::Label;
+        }
+        caption.source = self.caption.unwrap_or(caption.source);
+        caption.position = self.caption_position.unwrap_or(caption.position);
+        caption.max_length = self.caption_length.unwrap_or(caption.max_length);
+        caption.margin = self.caption_margin.unwrap_or(caption.margin);
+        caption.scale = self.caption_scale.or(caption.scale);
+        options.validate()?;
+        Ok(options)
+    }
+}
+
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    /// Print one setting, or all settings as JSON when no key is given
+    Get {
+        key: Option<String>,
+    },
+    /// Change a setting and save it
+    Set {
+        key: String,
+        value: String,
+    },
+    /// Print the effective preprocessing pipeline as JSON, usable as a profile file
+    Pipeline,
+}
+
+pub fn run_command(command: Command, settings: &mut AppSettings) -> AppResult<()> {
+    match command {
+        Command::Decode { files, copy, save_bytes, transport, decompress, decode } => {
+            let decode_options = decode.options(settings)?;
+            let reports = decode_paths(&files, &decode_options, decode.threads(settings), |_, _, _| {});
+            let mut all_results = Vec::new();
+            let mut error_count = 0;
+            for (path, report) in files.iter().zip(reports) {
+                match report {
+                    Ok(report) => {
+                        print_report_warnings(path, &report.warnings);
+                        if report.results.is_empty() {
+                            eprintln!("No QR code found in {} ({} processing techniques tried).", path.display(), report.variants_tried);
+                        }
+                        all_results.extend(report.results);
+                    }
+                    Err(e) => {
+                        eprintln!("Error processing {}: {:?}", path.display(), e);
+                        error_count += 1;
+                    }
+                }
+            }
+
+            if copy {
+                copy_single_result(&all_results, settings.block_flagged_copy);
+            }
+            print_qr_results(&all_results, decode.format(settings), decode.verbose(settings))?;
+
+            if all_results.is_empty() {
+                anyhow::bail!("No QR codes could be decoded from the images.");
+            }
+            if let Some(path) = save_bytes {
+                for path in save_payloads(&all_results, &path, transport, decompress)? {
+                    eprintln!("Payload saved to: {}", path.display());
+                }
+            }
+            if error_count > 0 {
+                anyhow::bail!("{} file(s) could not be processed.", error_count);
+            }
+        }
+        Command::ScanDir { dir, scan, output, decode } => {
+            let scan_dir = match dir.or_else(|| settings.scan_directory.clone()) {
+                Some(p) => p,
+                None => anyhow::bail!("Scan directory is not set. Pass a directory or run `config set scan_directory <dir>`."),
+            };
+            if !scan_dir.is_dir() {
+                anyhow::bail!("The provided path is not a valid directory: {}", scan_dir.display());
+            }
+
+            let decode_options = decode.options(settings)?;
+            let listing = collect_image_files(&scan_dir, &scan.options(settings))?;
+            print_report_warnings(&scan_dir, &listing.warnings);
+            let files = listing.files;
+            let reports = decode_paths(&files, &decode_options, decode.threads(settings), |_, _, _| {});
+            let mut all_results = Vec::new();
+            for (path, report) in files.iter().zip(reports) {
+                match report {
+                    Ok(report) => {
+                        print_report_warnings(path, &report.warnings);
+                        all_results.extend(report.results);
+                    }
+                    Err(e) => eprintln!("Error processing {}: {:?}", path.display(), e),
+                }
+            }
+
+            let format = decode.format(settings);
+            print_qr_results(&all_results, format, decode.verbose(settings))?;
+            if let Some(path) = output {
+                write_qr_content(&all_results, &path, format, decode.verbose(settings))?;
+                eprintln!("QR contents saved to: {}", path.display());
+            }
+
+            if all_results.is_empty() {
+                anyhow::bail!("No QR codes could be decoded from {} image(s) in {}.", files.len(), scan_dir.display());
+            }
+        }
+        Command::Generate { text, file, output, format, split, fit, transport, compress, contact_sheet, robustness, encode, payload } => {
+            let mut encode_options = encode.options(settings)?;
+            // Captions of files show the file name rather than their bytes.
+            let file_name = file.as_ref().and_then(|file| file.file_name()).map(|name| name.to_string_lossy().into_owned());
+            // Payloads may hold passwords, so the bytes are wiped once the codes are built.
+            let data = Zeroizing::new(match (text, file, payload) {
+                (None, None, Some(payload)) => payload.payload().build()?.as_bytes().to_vec(),
+                (_, _, Some(_)) => anyhow::bail!("Pass either text, --file or a payload, not several."),
+                (Some(text), _, None) => text.into_bytes(),
+                (None, Some(file), None) => std::fs::read(&file)
+                    .context(format!("Could not read file: {}", file.display()))?,
+                (None, None, None) => unreachable!("clap requires text, --file or a payload"),
+            });
+            match &file_name {
+                Some(name) => encode_options.caption.resolve(name.as_bytes(), None),
+                None => encode_options.caption.resolve(&data, None),
+            }
+            let data = if compress || transport != Transport::Raw {
+                let packed = pack(&data, transport, compress)?;
+                eprintln!("Packed {} bytes into {} bytes ({}).", data.len(), packed.len(), describe_packing(transport, compress));
+                packed
+            } else {
+                data
+            };
+            let symbols = if fit {
+                let symbols = encode_fitted(&data, &encode_options)?;
+                eprintln!("Fitted into {} code(s) at ECC level {}.", symbols.len(), ec_level_name(symbols[0].ec_level()));
+                symbols
+            } else if split {
+                encode_structured_append(&data, &encode_options)?
+            } else {
+                vec![encode_bytes(&data, &encode_options)
+                    .context("Data does not fit in one QR code. Pass --split to spread it over several codes.")?]
+            };
+
+            match output {
+                Some(path) => {
+                    let format = format.or_else(|| RenderFormat::from_path(&path)).unwrap_or_default();
+                    let paths = save_symbols(&symbols, &path, contact_sheet, format, &encode_options)?;
+                    for path in &paths {
+                        eprintln!("QR code saved successfully: {}", path.display());
+                    }
+                    let robustness = robustness || settings.verify_robustness;
+                    if verify_generated(&paths, format, &symbols, &data, &encode_options, robustness) == Some(false) {
+                        anyhow::bail!("The saved QR code could not be verified.");
+                    }
+                }
+                None => {
+                    for (i, symbol) in symbols.iter().enumerate() {
+                        if symbols.len() > 1 {
+                            println!("Part {} of {}:", i + 1, symbols.len());
+                        }
+                        println!("{}", render_terminal(symbol));
+                    }
+                }
+            }
+        }
+        Command::BatchGenerate { file, input_format, output_dir, name_template, skip_existing, format, robustness, encode } => {
+            let encode_options = encode.options(settings)?;
+            let items = read_batch(&file, input_format.unwrap_or_else(|| InputFormat::from_path(&file)))?;
+            let output_dir = output_dir.unwrap_or_else(|| settings.default_output_directory());
+
+            let tally = generate_qr_batch(
+                &items, &output_dir, &name_template, format, &encode_options,
+                robustness || settings.verify_robustness, skip_existing,
+            )?;
+            println!("\nCompleted! {}", tally);
+            if tally.failed > 0 {
+                anyhow::bail!("{} QR code(s) could not be generated.", tally.failed);
+            }
+        }
+        Command::Otp { files, codes, show_secrets, export, export_format, decode } => {
+            let decode_options = decode.options(settings)?;
+            let reports = decode_paths(&files, &decode_options, decode.threads(settings), |_, _, _| {});
+            let mut all_results = Vec::new();
+            for (path, report) in files.iter().zip(reports) {
+                match report {
+                    Ok(report) => {
+                        print_report_warnings(path, &report.warnings);
+                        all_results.extend(report.results);
+                    }
+                    Err(e) => eprintln!("Error processing {}: {:?}", path.display(), e),
+                }
+            }
+
+            let accounts = collect_accounts(&all_results);
+            if accounts.is_empty() {
+                anyhow::bail!("No otpauth:// or authenticator export QR codes were found.");
+            }
+            print_accounts(&accounts, codes, show_secrets);
+            if let Some(path) = export {
+                for path in export_accounts(&accounts, &path, export_format, &settings.encode_options())? {
+                    eprintln!("Exported: {}", path.display());
+                }
+                eprintln!("The export holds your secrets unencrypted; delete it once the accounts are imported.");
+            }
+        }
+        Command::Config { action } => match action {
+            ConfigAction::Get { key: Some(key) } => println!("{}", settings.get(&key)?),
+            ConfigAction::Get { key: None } => {
+                let content = serde_json::to_string_pretty(settings)
+                    .context("Could not convert settings to JSON format.")?;
+                println!("{}", content);
+            }
+            ConfigAction::Set { key, value } => {
+                settings.set(&key, &value)?;
+                save_settings(settings)?;
+                println!("{} = {}", key, settings.get(&key)?);
+            }
+            ConfigAction::Pipeline => {
+                let pipeline = settings.decode_options()?.pipeline;
+                let content = serde_json::to_string_pretty(&pipeline)
+                    .context("Could not convert pipeline to JSON format.")?;
+                println!("{}", content);
+            }
+        },
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+    use clap::error::ErrorKind;
+
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, ErrorKind> {
+        Cli::try_parse_from(std::iter::once(APP_NAME).chain(args.iter().copied())).map_err(|e| e.kind())
+    }
+
+    #[test]
+    fn arguments_are_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn saved_payload_options_need_save_bytes() {
+        assert_eq!(parse(&["decode", "a.png", "--transport", "base45"]).err(), Some(ErrorKind::MissingRequiredArgument));
+        assert_eq!(parse(&["decode", "a.png", "--decompress"]).err(), Some(ErrorKind::MissingRequiredArgument));
+        assert!(parse(&["decode", "a.png", "--save-bytes", "out.bin", "--transport", "base45", "--decompress"]).is_ok());
+    }
+
+    #[test]
+    fn generate_takes_text_or_a_file() {
+        assert_eq!(parse(&["generate"]).err(), Some(ErrorKind::MissingRequiredArgument));
+        assert_eq!(parse(&["generate", "hello", "--file", "data.bin"]).err(), Some(ErrorKind::ArgumentConflict));
+        assert!(parse(&["generate", "hello"]).is_ok());
+        assert!(parse(&["generate", "--file", "data.bin"]).is_ok());
+        // A payload subcommand replaces the text.
+        assert!(parse(&["generate", "wifi", "--ssid", "Guest"]).is_ok());
+    }
+
+    #[test]
+    fn fit_chooses_the_split_and_ecc_itself() {
+        assert_eq!(parse(&["generate", "hello", "--fit", "--split"]).err(), Some(ErrorKind::ArgumentConflict));
+        assert_eq!(parse(&["generate", "hello", "--fit", "--ecc", "H"]).err(), Some(ErrorKind::ArgumentConflict));
+        assert!(parse(&["generate", "hello", "--fit"]).is_ok());
+    }
+
+    #[test]
+    fn contact_sheet_needs_several_codes_and_an_output() {
+        assert_eq!(parse(&["generate", "hello", "--contact-sheet", "-o", "sheet.png"]).err(), Some(ErrorKind::MissingRequiredArgument));
+        assert_eq!(parse(&["generate", "hello", "--contact-sheet", "--split"]).err(), Some(ErrorKind::MissingRequiredArgument));
+        assert!(parse(&["generate", "hello", "--contact-sheet", "--split", "-o", "sheet.png"]).is_ok());
+        assert!(parse(&["generate", "hello", "--contact-sheet", "--fit", "-o", "sheet.png"]).is_ok());
+    }
+
+    #[test]
+    fn robustness_needs_an_output() {
+        assert_eq!(parse(&["generate", "hello", "--robustness"]).err(), Some(ErrorKind::MissingRequiredArgument));
+        assert!(parse(&["generate", "hello", "--robustness", "-o", "code.png"]).is_ok());
+    }
+
+    #[test]
+    fn otp_export_format_needs_export() {
+        assert_eq!(parse(&["otp", "a.png", "--export-format", "json"]).err(), Some(ErrorKind::MissingRequiredArgument));
+        assert!(parse(&["otp", "a.png", "--export", "accounts.json", "--export-format", "json"]).is_ok());
+    }
+}
@@ -0,0 +1,1221 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use anyhow::Context;
+use zeroize::Zeroizing;
+
+use kripton_qr_reader::{AppResult, DecodeMode, DecodedQr, EncodeOptions, decode_path_report, encode, encode_structured_append};
+use kripton_qr_reader::batch::{decode_paths, resolve_threads};
+use kripton_qr_reader::caption::{Caption, CaptionSource};
+use kripton_qr_reader::encode::{RenderFormat, ec_level_name, format_color, parse_color, parse_ec_level, render_terminal};
+use kripton_qr_reader::input::{collect_image_files, is_supported_image, supported_extensions};
+use kripton_qr_reader::payload::{Contact, Email, Event, EventTime, Geo, Payload, Sms, Wifi, WifiSecurity};
+use kripton_qr_reader::preprocess::Pipeline;
+use kripton_qr_reader::transfer::{Transport, encode_fitted, pack};
+use super::batch_input::{DEFAULT_TEMPLATE, InputFormat, read_batch};
+use super::{describe_packing, generate_qr_batch, save_symbols, verify_generated};
+use super::otp::{OtpExportFormat, collect_accounts, export_accounts, print_accounts};
+use super::output::{copy_single_result, print_qr_results, print_report_warnings, save_payloads, write_qr_content};
+use super::settings::{AppSettings, parse_frame_stride, parse_optional_number, parse_pattern_list, save_settings};
+
+pub fn run_menu(settings: &mut AppSettings) -> AppResult<()> {
+    let mut running = true;
+
+    while running {
+        println!("\n--- Kripton QR Code Reader/Generator ---");
+        println!("1. Read QR Code from Image in Scan Directory");
+        println!("2. Read QR Code from Specific File");
+        println!("3. Batch Process QR Codes");
+        println!("4. Generate QR Code (Text, WiFi, Contact and more)");
+        println!("5. Batch Generate QR Codes (from Text File or Any File)");
+        println!("6. Import Authenticator Accounts (otpauth)");
+        println!("7. Settings");
+        println!("8. Exit");
+        print!("Enter your choice (1-8): ");
+        io::stdout().flush()?; 
+
+        let mut choice = String::new();
+        let bytes_read = io::stdin().read_line(&mut choice)
+            .context("Failed to read input.")?;
+        if bytes_read == 0 {
+            println!("\nExiting application...");
+            break;
+        }
+        
+        match choice.trim() {
+            "1" => {
+                if let Err(e) = read_qr_code(settings) {
+                    eprintln!("Error: QR code reading failed: {:?}", e);
+                }
+            },
+            "2" => {
+                if let Err(e) = read_qr_from_file(settings) {
+                    eprintln!("Error: QR code reading failed: {:?}", e);
+                }
+            },
+            "3" => {
+                if let Err(e) = batch_process_qr_codes(settings) {
+                    eprintln!("Error: Batch QR processing failed: {:?}", e);
+                }
+            },
+            "4" => {
+                if let Err(e) = generate_qr_code(settings) {
+                    eprintln!("Error: QR code generation failed: {:?}", e);
+                }
+            },
+            "5" => {
+                if let Err(e) = batch_generate_qr_codes(settings) {
+                    eprintln!("Error: Batch QR generation failed: {:?}", e);
+                }
+            },
+            "6" => {
+                if let Err(e) = import_authenticator_accounts(settings) {
+                    eprintln!("Error: Authenticator import failed: {:?}", e);
+                }
+            },
+            "7" => {
+                if let Err(e) = settings_menu(settings) {
+                    eprintln!("Error: Failed to change settings: {:?}", e);
+                }
+            },
+            "8" => {
+                println!("Exiting application...");
+                running = false;
+            },
+            _ => {
+                println!("Invalid choice. Please enter 1, 2, 3, 4, 5, 6, 7, or 8.");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn read_qr_code(settings: &AppSettings) -> AppResult<()> {
+    let scan_dir = match &settings.scan_directory {
+        Some(p) => p,
+        None => {
+            println!("Error: Please set the scan directory from menu 7 first.");
+            return Ok(());
+        }
+    };
+
+    println!("Scan Directory: {}", scan_dir.display());
+    let listing = collect_image_files(scan_dir, &settings.scan)?;
+    print_report_warnings(scan_dir, &listing.warnings);
+    let files = listing.files;
+    if files.is_empty() {
+        println!("No supported image files found in directory (Supported: {}).", supported_extensions().join(", "));
+        return Ok(());
+    }
+
+    println!("\nFound Images (Alphabetical Order):");
+    for (i, file) in files.iter().enumerate() {
+        let file_name = file.strip_prefix(scan_dir).unwrap_or(file).display();
+        println!("{}. {}", i + 1, file_name);
+    }
+
+    print!("Enter the number of the image to read (1-{}): ", files.len());
+    io::stdout().flush()?;
+
+    let mut choice = String::new();
+    io::stdin().read_line(&mut choice)?;
+
+    let index: usize = match choice.trim().parse::<usize>() {
+        Ok(n) if n > 0 && n <= files.len() => n - 1,
+        _ => {
+            println!("Invalid choice.");
+            return Ok(());
+        }
+    };
+
+    let path = &files[index];
+    let decode_options = settings.decode_options()?;
+    let report = decode_path_report(path, &decode_options)?;
+    print_report_warnings(path, &report.warnings);
+    let results = report.results;
+    if results.is_empty() {
+        println!("Could not decode QR code from selected image.");
+        println!("{} different processing techniques were tried.", report.variants_tried);
+        return Ok(());
+    }
+
+    println!("\nSuccessfully decoded {} unique QR code(s)!", results.len());
+    if settings.auto_copy_to_clipboard {
+        copy_single_result(&results, settings.block_flagged_copy);
+    }
+
+    print_qr_results(&results, settings.output_format, settings.verbose_output)?;
+    if results.iter().any(DecodedQr::is_binary)
+        && let Err(e) = save_binary_payload(&results, path) {
+        eprintln!("Error saving payload: {:?}", e);
+    }
+
+    Ok(())
+}
+
+fn read_qr_from_file(settings: &AppSettings) -> AppResult<()> {
+    print!("Enter the full path to the image file (leave empty to cancel): ");
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+
+    let path_str = input.trim();
+    if path_str.is_empty() {
+        println!("No path entered, operation cancelled.");
+        return Ok(());
+    }
+
+    let path = PathBuf::from(path_str);
+    if !path.is_file() {
+        println!("Error: The provided path is not a valid file.");
+        return Ok(());
+    }
+
+    if !is_supported_image(&path) {
+        println!("Unrecognized image format. Supported: {}.", supported_extensions().join(", "));
+        return Ok(());
+    }
+
+    let decode_options = settings.decode_options()?;
+    let report = decode_path_report(&path, &decode_options)?;
+    print_report_warnings(&path, &report.warnings);
+    let results = report.results;
+    if results.is_empty() {
+        println!("Could not decode QR code from selected image.");
+        println!("{} different processing techniques were tried.", report.variants_tried);
+        return Ok(());
+    }
+
+    println!("\nSuccessfully decoded {} unique QR code(s)!", results.len());
+    if settings.auto_copy_to_clipboard {
+        copy_single_result(&results, settings.block_flagged_copy);
+    }
+
+    print_qr_results(&results, settings.output_format, settings.verbose_output)?;
+    if results.iter().any(DecodedQr::is_binary)
+        && let Err(e) = save_binary_payload(&results, &path) {
+        eprintln!("Error saving payload: {:?}", e);
+    }
+
+    Ok(())
+}
+
+fn batch_process_qr_codes(settings: &AppSettings) -> AppResult<()> {
+    println!("\n--- Batch QR Code Processing ---");
+    let default_dir = settings.scan_directory.as_ref()
+        .map(|p| p.display().to_string())
+        .unwrap_or("Not set".to_string());
+    println!("Current scan directory: {}", default_dir);
+    print!("Enter new scan directory (press Enter to use current): ");
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let scan_dir = if input.trim().is_empty() {
+        match &settings.scan_directory {
+            Some(p) => p.clone(),
+            None => {
+                println!("Error: Scan directory is not set. Please set a directory in Settings.");
+                return Ok(());
+            }
+        }
+    } else {
+        let new_dir = PathBuf::from(input.trim());
+        if !new_dir.is_dir() {
+            println!("Error: The provided path is not a valid directory.");
+            return Ok(());
+        }
+        new_dir
+    };
+
+    let listing = collect_image_files(&scan_dir, &settings.scan)?;
+    print_report_warnings(&scan_dir, &listing.warnings);
+    let files = listing.files;
+    if files.is_empty() {
+        println!("No supported image files found in directory (Supported: {}).", supported_extensions().join(", "));
+        return Ok(());
+    }
+
+    println!("\nFound {} images in '{}'. Processing...", files.len(), scan_dir.display());
+    let decode_options = settings.decode_options()?;
+    let mut all_results = Vec::new();
+
+    let reports = decode_paths(&files, &decode_options, settings.worker_threads, |done, path, _| {
+        println!("Processed image {}/{}: {}", done, files.len(), path.display());
+    });
+
+    for (path, report) in files.iter().zip(reports) {
+        match report {
+            Ok(report) => {
+                print_report_warnings(path, &report.warnings);
+                if report.results.is_empty() {
+                    println!("No QR code found in {}.", path.display());
+                } else {
+                    all_results.extend(report.results);
+                }
+            }
+            Err(e) => println!("Error processing {}: {:?}", path.display(), e),
+        }
+    }
+
+    if all_results.is_empty() {
+        println!("\nNo QR codes could be decoded from the images.");
+        return Ok(());
+    }
+
+    println!("\nSuccessfully decoded {} unique QR code(s)!", all_results.len());
+    if settings.auto_copy_to_clipboard {
+        copy_single_result(&all_results, settings.block_flagged_copy);
+    }
+
+    print_qr_results(&all_results, settings.output_format, settings.verbose_output)?;
+
+    print!("\nDo you want to save the QR code contents to a file? (Y/N): ");
+    io::stdout().flush()?;
+    let mut save_choice = String::new();
+    io::stdin().read_line(&mut save_choice)?;
+    if save_choice.trim().to_lowercase() == "y"
+        && let Err(e) = save_qr_content(&all_results, settings) {
+        eprintln!("Error saving QR contents: {:?}", e);
+    }
+
+    Ok(())
+}
+
+fn save_binary_payload(results: &[DecodedQr], image: &Path) -> AppResult<()> {
+    if prompt("\nSave the binary payload to a file? (Y/N): ")?.to_lowercase() != "y" {
+        return Ok(());
+    }
+    let default_path = image.with_extension("bin");
+    let input = prompt(&format!("Save to (press Enter for {}): ", default_path.display()))?;
+    let path = if input.is_empty() { default_path } else { PathBuf::from(input) };
+    for path in save_payloads(results, &path, Transport::Raw, false)? {
+        println!("Payload saved to: {}", path.display());
+    }
+    Ok(())
+}
+
+fn save_qr_content(contents: &[DecodedQr], settings: &AppSettings) -> AppResult<()> {
+    print!("Enter file path to save QR contents (default: 'qr_batch_output.txt'): ");
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let path = if input.trim().is_empty() {
+        settings.scan_directory.as_ref()
+            .map(|p| p.join("qr_batch_output.txt"))
+            .unwrap_or_else(|| PathBuf::from("qr_batch_output.txt"))
+    } else {
+        PathBuf::from(input.trim())
+    };
+
+    write_qr_content(contents, &path, settings.output_format, settings.verbose_output)?;
+    println!("QR contents saved to: {}", path.display());
+    Ok(())
+}
+
+fn generate_qr_code(settings: &AppSettings) -> AppResult<()> {
+    
+    println!("\n--- Generate QR Code ---");
+    println!("1. Text  2. WiFi  3. Contact (vCard)  4. Contact (MeCard)  5. Location  6. SMS  7. Email  8. Calendar Event");
+    let kind = prompt("Choose content type (default: 1): ")?;
+
+    let text = match kind.as_str() {
+        "" | "1" => Zeroizing::new(prompt("Enter text to convert to QR code (or leave empty to cancel): ")?),
+        "2" | "3" | "4" | "5" | "6" | "7" | "8" => match prompt_payload(&kind)? {
+            Some(payload) => payload.build()?,
+            None => Zeroizing::new(String::new()),
+        },
+        _ => {
+            println!("Invalid choice. Please enter a number from 1 to 8.");
+            return Ok(());
+        }
+    };
+    let text = text.as_str();
+
+    if text.is_empty() {
+        println!("No text entered, operation cancelled.");
+        return Ok(());
+    }
+
+    let mut encode_options = settings.encode_options();
+    encode_options.caption.resolve(text.as_bytes(), None);
+    let symbols = match encode(text, &encode_options) {
+        Ok(symbol) => vec![symbol],
+        Err(_) => {
+            let symbols = encode_structured_append(text.as_bytes(), &encode_options)?;
+            print!("\nText is too long for one QR code. Split it into {} linked codes (Structured Append)? (Y/N): ", symbols.len());
+            io::stdout().flush()?;
+            let mut split_choice = String::new();
+            io::stdin().read_line(&mut split_choice)?;
+            if split_choice.trim().to_lowercase() != "y" {
+                println!("Operation cancelled.");
+                return Ok(());
+            }
+            symbols
+        }
+    };
+
+    println!("\nQR Code (Terminal View):");
+    for (i, symbol) in symbols.iter().enumerate() {
+        if symbols.len() > 1 {
+            println!("Part {} of {}:", i + 1, symbols.len());
+        }
+        println!("{}", render_terminal(symbol));
+    }
+
+    print!("\nSave QR code to a file? (Y/N): ");
+    io::stdout().flush()?;
+    let mut save_choice = String::new();
+    io::stdin().read_line(&mut save_choice)?;
+
+    if save_choice.trim().to_lowercase() == "y" {
+        let default_dir = settings.default_output_directory();
+        
+        print!("Enter file name, ending in .png, .svg, .eps or .pdf (default: qr_code.png, directory: {}): ", default_dir.display());
+        io::stdout().flush()?;
+        
+        let mut filename_input = String::new();
+        io::stdin().read_line(&mut filename_input)?;
+        let filename = filename_input.trim();
+        
+        let path = if filename.is_empty() {
+            default_dir.join("qr_code.png")
+        } else {
+            let input_path = PathBuf::from(filename);
+            if input_path.is_absolute() {
+                input_path
+            } else {
+                default_dir.join(filename)
+            }
+        };
+
+        let format = RenderFormat::from_path(&path).unwrap_or_default();
+        let mut contact_sheet = false;
+        if symbols.len() > 1 && !format.is_vector() {
+            print!("Save as 1) one numbered PNG per code or 2) a single contact sheet? (default: 1): ");
+            io::stdout().flush()?;
+            let mut layout_choice = String::new();
+            io::stdin().read_line(&mut layout_choice)?;
+            contact_sheet = layout_choice.trim() == "2";
+        }
+
+        let paths = save_symbols(&symbols, &path, contact_sheet, format, &encode_options)?;
+        for path in &paths {
+            println!("QR code saved successfully: {}", path.display());
+        }
+        verify_generated(&paths, format, &symbols, text.as_bytes(), &encode_options, settings.verify_robustness);
+    }
+
+    Ok(())
+}
+
+// Asks for the fields of a payload; the values are escaped when it is built. Returns `None`
+// when the first, required field is left empty.
+fn prompt_payload(kind: &str) -> AppResult<Option<Payload>> {
+    let optional = |message: &str| prompt(message).map(|value| Some(value).filter(|v| !v.is_empty()));
+    let payload = match kind {
+        "2" => {
+            let ssid = prompt("Network name (SSID): ")?;
+            if ssid.is_empty() {
+                return Ok(None);
+            }
+            let security: WifiSecurity = prompt("Security, wpa, wep or none (default: wpa): ")
+                .map(|value| if value.is_empty() { "wpa".to_string() } else { value })?
+                .parse()
+                .map_err(|e: String| anyhow::anyhow!(e))?;
+            let password = if security == WifiSecurity::None {
+                Zeroizing::new(String::new())
+            } else {
+                prompt_secret("Password: ")?
+            };
+            let hidden = prompt("Hidden network? (Y/N): ")?.to_lowercase() == "y";
+            Payload::Wifi(Wifi { ssid, password, security, hidden })
+        }
+        "3" | "4" => {
+            let first_name = prompt("First name: ")?;
+            let last_name = prompt("Last name: ")?;
+            let organization = optional("Organization (optional): ")?;
+            if first_name.is_empty() && last_name.is_empty() && organization.is_none() {
+                return Ok(None);
+            }
+            let contact = Contact {
+                first_name,
+                last_name,
+                organization,
+                title: if kind == "3" { optional("Job title (optional): ")? } else { None },
+                phone: optional("Phone (optional): ")?,
+                email: optional("Email (optional): ")?,
+                address: optional("Address (optional): ")?,
+                url: optional("Website (optional): ")?,
+                note: optional("Note (optional): ")?,
+            };
+            if kind == "3" { Payload::VCard(contact) } else { Payload::MeCard(contact) }
+        }
+        "5" => {
+            let latitude = prompt("Latitude, e.g. 48.8584: ")?;
+            if latitude.is_empty() {
+                return Ok(None);
+            }
+            let latitude = latitude.parse().context(format!("Expected a latitude, got: {}", latitude))?;
+            let longitude = prompt("Longitude, e.g. 2.2945: ")?;
+            let longitude = longitude.parse().context(format!("Expected a longitude, got: {}", longitude))?;
+            Payload::Geo(Geo { latitude, longitude, label: optional("Place name (optional): ")? })
+        }
+        "6" => {
+            let number = prompt("Phone number: ")?;
+            if number.is_empty() {
+                return Ok(None);
+            }
+            Payload::Sms(Sms { number, message: optional("Message (optional): ")? })
+        }
+        "7" => {
+            let to = prompt("Email address: ")?;
+            if to.is_empty() {
+                return Ok(None);
+            }
+            Payload::Email(Email { to, subject: optional("Subject (optional): ")?, body: optional("Body (optional): ")? })
+        }
+        _ => {
+            let summary = prompt("Event title: ")?;
+            if summary.is_empty() {
+                return Ok(None);
+            }
+            let start = prompt("Start, e.g. 2026-10-16 or 2026-10-16 14:30: ")?
+                .parse()
+                .map_err(|e: String| anyhow::anyhow!(e))?;
+            let end = optional("End, in the same form (optional): ")?
+                .map(|end| end.parse::<EventTime>())
+                .transpose()
+                .map_err(|e| anyhow::anyhow!(e))?;
+            Payload::Event(Event {
+                summary,
+                start,
+                end,
+                location: optional("Location (optional): ")?,
+                description: optional("Description (optional): ")?,
+            })
+        }
+    };
+    Ok(Some(payload))
+}
+
+fn batch_generate_qr_codes(settings: &AppSettings) -> AppResult<()> {
+    println!("\n--- Batch QR Code Generation ---");
+    println!("1. One QR code per line of a text file or per row of a CSV, TSV or JSONL file");
+    println!("2. Any file as raw bytes, split over several codes if needed");
+    let whole_file = match prompt("Choose mode (default: 1): ")?.as_str() {
+        "" | "1" => false,
+        "2" => true,
+        _ => {
+            println!("Invalid choice.");
+            return Ok(());
+        }
+    };
+    if whole_file {
+        print!("Enter path to the file to encode: ");
+    } else {
+        print!("Enter path to a text file (one code per line) or a CSV, TSV or JSONL file (one code per row): ");
+    }
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let file_path_str = input.trim();
+
+    if file_path_str.is_empty() {
+        println!("No file path entered, operation cancelled.");
+        return Ok(());
+    }
+
+    let file_path = PathBuf::from(file_path_str);
+    if !file_path.is_file() {
+        println!("Error: The provided path is not a valid file.");
+        return Ok(());
+    }
+
+    if whole_file {
+        return generate_file_codes(&file_path, settings);
+    }
+
+    let input_format = InputFormat::from_path(&file_path);
+    let items = read_batch(&file_path, input_format)?;
+    if items.is_empty() {
+        println!("No processable text found in file.");
+        return Ok(());
+    }
+
+    println!("\n{} codes found.", items.len());
+    let template = if input_format == InputFormat::Lines {
+        DEFAULT_TEMPLATE.to_string()
+    } else {
+        let input = prompt(&format!(
+            "File name template, e.g. {{id}}_{{name}}; a filename column takes precedence (press Enter for {}): ",
+            DEFAULT_TEMPLATE
+        ))?;
+        if input.is_empty() { DEFAULT_TEMPLATE.to_string() } else { input }
+    };
+
+    let default_dir = settings.default_output_directory();
+
+    print!("Output directory (default: {}): ", default_dir.display());
+    io::stdout().flush()?;
+    
+    let mut dir_input = String::new();
+    io::stdin().read_line(&mut dir_input)?;
+    let output_dir = if dir_input.trim().is_empty() {
+        default_dir
+    } else {
+        PathBuf::from(dir_input.trim())
+    };
+
+    print!("Output format, png, svg, eps or pdf (default: png): ");
+    io::stdout().flush()?;
+
+    let mut format_input = String::new();
+    io::stdin().read_line(&mut format_input)?;
+    let format = if format_input.trim().is_empty() {
+        RenderFormat::Png
+    } else {
+        match format_input.parse::<RenderFormat>() {
+            Ok(format) => format,
+            Err(e) => {
+                println!("Error: {}", e);
+                return Ok(());
+            }
+        }
+    };
+
+    println!("Generating QR codes...");
+    let tally = generate_qr_batch(
+        &items, &output_dir, &template, format, &settings.encode_options(), settings.verify_robustness, false,
+    )?;
+
+    println!("\nCompleted! {}", tally);
+    println!("QR codes saved to: {}", output_dir.display());
+
+    Ok(())
+}
+
+// Encodes the bytes of `file_path`, packed as the user chooses, in as few codes as
+// possible with the highest ECC level that allows it.
+fn generate_file_codes(file_path: &Path, settings: &AppSettings) -> AppResult<()> {
+    let data = Zeroizing::new(std::fs::read(file_path)
+        .context(format!("Could not read file: {}", file_path.display()))?);
+    let transport = match prompt("Transport, raw, base45 or base64 (default: raw): ")?.as_str() {
+        "" => Transport::Raw,
+        input => match input.parse::<Transport>() {
+            Ok(transport) => transport,
+            Err(e) => {
+                println!("Error: {}", e);
+                return Ok(());
+            }
+        },
+    };
+    let compress = prompt("Compress the data first? (Y/N): ")?.to_lowercase() == "y";
+    let data = pack(&data, transport, compress)?;
+
+    // The packed data means nothing to a reader, so captions show the file name instead.
+    let mut encode_options = settings.encode_options();
+    let file_name = file_path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    encode_options.caption.resolve(file_name.as_bytes(), None);
+    let symbols = match encode_fitted(&data, &encode_options) {
+        Ok(symbols) => symbols,
+        Err(e) => {
+            println!("Error: {:#}", e);
+            return Ok(());
+        }
+    };
+    println!(
+        "{} bytes ({}) fit into {} QR code(s) at ECC level {}.",
+        data.len(), describe_packing(transport, compress), symbols.len(), ec_level_name(symbols[0].ec_level())
+    );
+
+    let stem = file_path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "file".to_string());
+    let default_path = settings.default_output_directory().join(format!("{}.png", stem));
+    let input = prompt(&format!("Save to (press Enter for {}): ", default_path.display()))?;
+    let path = if input.is_empty() { default_path } else { PathBuf::from(input) };
+    let format = RenderFormat::from_path(&path).unwrap_or_default();
+
+    let paths = save_symbols(&symbols, &path, false, format, &encode_options)?;
+    for path in &paths {
+        println!("QR code saved successfully: {}", path.display());
+    }
+    verify_generated(&paths, format, &symbols, &data, &encode_options, settings.verify_robustness);
+    if compress || transport != Transport::Raw {
+        println!(
+            "Read it back with: decode <images> --save-bytes <file> --transport {}{}",
+            transport, if compress { " --decompress" } else { "" }
+        );
+    }
+    Ok(())
+}
+
+// Reads otpauth:// and authenticator export codes from an image or a directory of images,
+// then offers to show the current codes and to export the accounts. Secrets are only shown
+// on request.
+fn import_authenticator_accounts(settings: &AppSettings) -> AppResult<()> {
+    println!("\n--- Import Authenticator Accounts ---");
+    let input = prompt("Enter the path to an image, or a directory of images (leave empty to cancel): ")?;
+    if input.is_empty() {
+        println!("No path entered, operation cancelled.");
+        return Ok(());
+    }
+
+    let path = PathBuf::from(input);
+    let files = if path.is_dir() {
+        let listing = collect_image_files(&path, &settings.scan)?;
+        print_report_warnings(&path, &listing.warnings);
+        listing.files
+    } else if path.is_file() {
+        vec![path]
+    } else {
+        println!("Error: The provided path is not a valid file or directory.");
+        return Ok(());
+    };
+
+    let decode_options = settings.decode_options()?;
+    let mut all_results = Vec::new();
+    for (path, report) in files.iter().zip(decode_paths(&files, &decode_options, settings.worker_threads, |_, _, _| {})) {
+        match report {
+            Ok(report) => {
+                print_report_warnings(path, &report.warnings);
+                all_results.extend(report.results);
+            }
+            Err(e) => println!("Error processing {}: {:?}", path.display(), e),
+        }
+    }
+    let accounts = collect_accounts(&all_results);
+    if accounts.is_empty() {
+        println!("No otpauth:// or authenticator export QR codes were found.");
+        return Ok(());
+    }
+
+    print_accounts(&accounts, false, false);
+    let show_codes = prompt("\nShow the current codes? (Y/N): ")?.to_lowercase() == "y";
+    let show_secrets = prompt("Show the secrets? Anyone who sees them can generate your codes. (Y/N): ")?.to_lowercase() == "y";
+    if show_codes || show_secrets {
+        println!();
+        print_accounts(&accounts, show_codes, show_secrets);
+    }
+
+    println!("\nExport the accounts as:");
+    println!("1. otpauth:// links, one per line");
+    println!("2. andOTP JSON backup");
+    println!("3. One QR code per account");
+    let (format, default_name) = match prompt("Enter your choice (1-3, leave empty to skip): ")?.as_str() {
+        "" => return Ok(()),
+        "1" => (OtpExportFormat::Uri, "otp_accounts.txt"),
+        "2" => (OtpExportFormat::Json, "otp_accounts.json"),
+        "3" => (OtpExportFormat::Qr, "otp_accounts"),
+        _ => {
+            println!("Invalid choice.");
+            return Ok(());
+        }
+    };
+
+    let default_path = settings.default_output_directory().join(default_name);
+    let input = prompt(&format!("Save to (press Enter for {}): ", default_path.display()))?;
+    let path = if input.is_empty() { default_path } else { PathBuf::from(input) };
+    for path in export_accounts(&accounts, &path, format, &settings.encode_options())? {
+        println!("Exported: {}", path.display());
+    }
+    println!("The export holds your secrets unencrypted; delete it once the accounts are imported.");
+    Ok(())
+}
+
+fn settings_menu(settings: &mut AppSettings) -> AppResult<()> {
+    let mut in_settings_menu = true;
+    while in_settings_menu {
+        println!("\n--- Settings Menu ---");
+
+        match &settings.scan_directory {
+            Some(p) => println!("1. Set Scan Directory (Current: {})", p.display()),
+            None => println!("1. Set Scan Directory (Current: NOT SET)"),
+        }
+
+        let auto_copy_status = if settings.auto_copy_to_clipboard { "Enabled" } else { "Disabled" };
+        println!("2. Toggle Auto-Copy to Clipboard (Current: {})", auto_copy_status);
+        
+        match &settings.output_directory {
+            Some(p) => println!("3. Set Output Directory (Current: {})", p.display()),
+            None => println!("3. Set Output Directory (Current: Scan directory will be used)"),
+        }
+        
+        println!("4. Cycle Output Format (Current: {})", settings.output_format.name());
+        let verbose_status = if settings.verbose_output { "Enabled" } else { "Disabled" };
+        println!("5. Toggle Verbose Decode Details (Current: {})", verbose_status);
+        match &settings.pipeline_profile {
+            Some(p) => println!("6. Set Preprocessing Profile (Current: {})", p.display()),
+            None if settings.preprocessing_pipeline.is_some() => println!("6. Set Preprocessing Profile (Current: Custom pipeline in settings)"),
+            None => println!("6. Set Preprocessing Profile (Current: Built-in pipeline)"),
+        }
+        println!("7. Set Decode Mode (Current: {})", settings.decode_mode);
+        match settings.worker_threads {
+            0 => println!("8. Set Worker Threads (Current: Auto, {})", resolve_threads(0)),
+            n => println!("8. Set Worker Threads (Current: {})", n),
+        }
+        let parallel_variants_status = if settings.parallel_variants { "Enabled" } else { "Disabled" };
+        println!("9. Toggle Parallel Variants Within an Image (Current: {})", parallel_variants_status);
+        match settings.frame_stride {
+            1 => println!("10. Set Animation Frame Stride (Current: Every frame)"),
+            n => println!("10. Set Animation Frame Stride (Current: 1 in {} frames)", n),
+        }
+        println!("11. Directory Scanning Options (Current: {})", describe_scan_options(settings));
+        println!("12. QR Generation Options (Current: {})", describe_encode_options(settings));
+        let block_flagged_status = if settings.block_flagged_copy { "Enabled" } else { "Disabled" };
+        println!("13. Toggle Blocking Auto-Copy of Flagged Links (Current: {})", block_flagged_status);
+        println!("14. Return to Main Menu");
+        print!("Enter your choice (1-14): ");
+        io::stdout().flush()?;
+
+        let mut choice = String::new();
+        if io::stdin().read_line(&mut choice)? == 0 {
+            break;
+        }
+
+        match choice.trim() {
+            "1" => {
+                print!("Enter new Scan Directory path (leave empty to cancel): ");
+                io::stdout().flush()?;
+                let mut input = String::new();
+                io::stdin().read_line(&mut input)?;
+                let new_path_str = input.trim();
+
+                if !new_path_str.is_empty() {
+                    let path_buf = PathBuf::from(new_path_str);
+                    if path_buf.is_dir() {
+                        settings.scan_directory = Some(path_buf);
+                        println!("Scan directory updated successfully. Saving...");
+                        save_settings(settings)?;
+                    } else {
+                        println!("Error: The provided path is not a valid directory.");
+                    }
+                } else {
+                    println!("No path entered, operation cancelled.");
+                }
+            },
+            "2" => {
+                settings.auto_copy_to_clipboard = !settings.auto_copy_to_clipboard;
+                let new_status = if settings.auto_copy_to_clipboard { "Enabled" } else { "Disabled" };
+                println!("Auto-copy to clipboard is now {}. Saving...", new_status);
+                save_settings(settings)?;
+            },
+            "3" => {
+                print!("Enter new Output Directory path (leave empty for default): ");
+                io::stdout().flush()?;
+                let mut input = String::new();
+                io::stdin().read_line(&mut input)?;
+                let new_path_str = input.trim();
+
+                if !new_path_str.is_empty() {
+                    let path_buf = PathBuf::from(new_path_str);
+                    if path_buf.is_dir() {
+                        settings.output_directory = Some(path_buf);
+                        println!("Output directory updated successfully. Saving...");
+                        save_settings(settings)?;
+                    } else {
+                        println!("Error: The provided path is not a valid directory.");
+                    }
+                } else {
+                    settings.output_directory = None;
+                    println!("Output directory reset to default. Saving...");
+                    save_settings(settings)?;
+                }
+            },
+            "4" => {
+                settings.output_format = settings.output_format.next();
+                println!("Output format is now {}. Saving...", settings.output_format.name());
+                save_settings(settings)?;
+            },
+            "5" => {
+                settings.verbose_output = !settings.verbose_output;
+                let new_status = if settings.verbose_output { "Enabled" } else { "Disabled" };
+                println!("Verbose decode details are now {}. Saving...", new_status);
+                save_settings(settings)?;
+            },
+            "6" => {
+                print!("Enter path to a pipeline profile JSON file (leave empty for default): ");
+                io::stdout().flush()?;
+                let mut input = String::new();
+                io::stdin().read_line(&mut input)?;
+                let new_path_str = input.trim();
+
+                if !new_path_str.is_empty() {
+                    let path_buf = PathBuf::from(new_path_str);
+                    match Pipeline::load(&path_buf) {
+                        Ok(pipeline) => {
+                            settings.pipeline_profile = Some(path_buf);
+                            println!("Preprocessing profile with {} step(s) selected. Saving...", pipeline.steps.len());
+                            save_settings(settings)?;
+                        }
+                        Err(e) => println!("Error: Could not load pipeline profile: {:#}", e),
+                    }
+                } else {
+                    settings.pipeline_profile = None;
+                    println!("Preprocessing profile reset to default. Saving...");
+                    save_settings(settings)?;
+                }
+            },
+            "7" => {
+                print!("Enter decode mode (first-hit, exhaustive, or number of expected codes; leave empty to cancel): ");
+                io::stdout().flush()?;
+                let mut input = String::new();
+                io::stdin().read_line(&mut input)?;
+
+                if !input.trim().is_empty() {
+                    match input.trim().parse::<DecodeMode>() {
+                        Ok(mode) => {
+                            settings.decode_mode = mode;
+                            println!("Decode mode is now {}. Saving...", mode);
+                            save_settings(settings)?;
+                        }
+                        Err(e) => println!("Error: {}", e),
+                    }
+                } else {
+                    println!("No mode entered, operation cancelled.");
+                }
+            },
+            "8" => {
+                print!("Enter number of worker threads (0 for one per CPU, leave empty to cancel): ");
+                io::stdout().flush()?;
+                let mut input = String::new();
+                io::stdin().read_line(&mut input)?;
+
+                if !input.trim().is_empty() {
+                    match input.trim().parse::<usize>() {
+                        Ok(threads) => {
+                            settings.worker_threads = threads;
+                            println!("Worker threads set to {}. Saving...", resolve_threads(threads));
+                            save_settings(settings)?;
+                        }
+                        Err(_) => println!("Error: Please enter a non-negative number."),
+                    }
+                } else {
+                    println!("No value entered, operation cancelled.");
+                }
+            },
+            "9" => {
+                settings.parallel_variants = !settings.parallel_variants;
+                let new_status = if settings.parallel_variants { "Enabled" } else { "Disabled" };
+                println!("Parallel variants are now {}. Saving...", new_status);
+                save_settings(settings)?;
+            },
+            "10" => {
+                print!("Decode every N-th frame of animated images (1 for all frames, leave empty to cancel): ");
+                io::stdout().flush()?;
+                let mut input = String::new();
+                io::stdin().read_line(&mut input)?;
+
+                if !input.trim().is_empty() {
+                    match parse_frame_stride(&input) {
+                        Ok(stride) => {
+                            settings.frame_stride = stride;
+                            println!("Frame stride set to {}. Saving...", stride);
+                            save_settings(settings)?;
+                        }
+                        Err(e) => println!("Error: {:#}", e),
+                    }
+                } else {
+                    println!("No value entered, operation cancelled.");
+                }
+            },
+            "11" => {
+                scan_options_menu(settings)?;
+            },
+            "12" => {
+                encode_options_menu(settings)?;
+            },
+            "13" => {
+                settings.block_flagged_copy = !settings.block_flagged_copy;
+                let new_status = if settings.block_flagged_copy { "Enabled" } else { "Disabled" };
+                println!("Blocking auto-copy of flagged links is now {}. Saving...", new_status);
+                save_settings(settings)?;
+            },
+            "14" => {
+                in_settings_menu = false;
+            },
+            _ => {
+                println!("Invalid choice. Please enter a number from 1 to 14.");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn describe_scan_options(settings: &AppSettings) -> String {
+    let scan = &settings.scan;
+    if !scan.recursive {
+        return "Top level only".to_string();
+    }
+    match scan.max_depth {
+        Some(depth) => format!("Recursive, max depth {}", depth),
+        None => "Recursive".to_string(),
+    }
+}
+
+fn scan_options_menu(settings: &mut AppSettings) -> AppResult<()> {
+    loop {
+        let scan = &settings.scan;
+        let on_off = |enabled: bool| if enabled { "Enabled" } else { "Disabled" };
+        println!("\n--- Directory Scanning Options ---");
+        println!("1. Toggle Recursive Scanning (Current: {})", on_off(scan.recursive));
+        match scan.max_depth {
+            Some(depth) => println!("2. Set Max Depth (Current: {})", depth),
+            None => println!("2. Set Max Depth (Current: Unlimited)"),
+        }
+        println!("3. Toggle Following Symlinks (Current: {})", on_off(scan.follow_links));
+        println!("4. Set Include Patterns (Current: {})", if scan.include.is_empty() { "All files".to_string() } else { scan.include.join(", ") });
+        println!("5. Set Exclude Patterns (Current: {})", if scan.exclude.is_empty() { "None".to_string() } else { scan.exclude.join(", ") });
+        println!("6. Toggle Hidden Files (Current: {})", on_off(scan.include_hidden));
+        println!("7. Return to Settings Menu");
+        print!("Enter your choice (1-7): ");
+        io::stdout().flush()?;
+
+        let mut choice = String::new();
+        if io::stdin().read_line(&mut choice)? == 0 {
+            break;
+        }
+
+        match choice.trim() {
+            "1" => settings.scan.recursive = !settings.scan.recursive,
+            "2" => {
+                print!("Enter max depth (leave empty for unlimited): ");
+                io::stdout().flush()?;
+                let mut input = String::new();
+                io::stdin().read_line(&mut input)?;
+                if input.trim().is_empty() {
+                    settings.scan.max_depth = None;
+                } else {
+                    match input.trim().parse::<usize>() {
+                        Ok(depth) => settings.scan.max_depth = Some(depth),
+                        Err(_) => {
+                            println!("Error: Please enter a non-negative number.");
+                            continue;
+                        }
+                    }
+                }
+            },
+            "3" => settings.scan.follow_links = !settings.scan.follow_links,
+            "4" | "5" => {
+                print!("Enter comma-separated glob patterns, e.g. 2024-*/**/*.png (leave empty to clear): ");
+                io::stdout().flush()?;
+                let mut input = String::new();
+                io::stdin().read_line(&mut input)?;
+                match parse_pattern_list(&input) {
+                    Ok(patterns) if choice.trim() == "4" => settings.scan.include = patterns,
+                    Ok(patterns) => settings.scan.exclude = patterns,
+                    Err(e) => {
+                        println!("Error: {:#}", e);
+                        continue;
+                    }
+                }
+            },
+            "6" => settings.scan.include_hidden = !settings.scan.include_hidden,
+            "7" => break,
+            _ => {
+                println!("Invalid choice. Please enter a number from 1 to 7.");
+                continue;
+            }
+        }
+
+        println!("Scanning options updated. Saving...");
+        save_settings(settings)?;
+    }
+    Ok(())
+}
+
+fn describe_version(encode: &EncodeOptions) -> String {
+    let prefix = if encode.micro { "M" } else { "" };
+    match (encode.version, encode.fixed_version) {
+        (Some(version), true) => format!("version {}{}", prefix, version),
+        (Some(version), false) => format!("version {}{}+", prefix, version),
+        (None, _) if encode.micro => "Micro QR".to_string(),
+        (None, _) => "automatic version".to_string(),
+    }
+}
+
+fn describe_caption(caption: &Caption) -> String {
+    match (caption.source, &caption.label) {
+        (CaptionSource::Off, _) => "None".to_string(),
+        (CaptionSource::Label, Some(label)) => format!("label \"{}\", {}", label, caption.position),
+        (CaptionSource::Truncated, _) => format!("truncated to {} characters, {}", caption.max_length, caption.position),
+        (source, _) => format!("{}, {}", source, caption.position),
+    }
+}
+
+fn describe_encode_options(settings: &AppSettings) -> String {
+    format!("ECC {}, {}", ec_level_name(settings.encode.ec_level), describe_version(&settings.encode))
+}
+
+fn encode_options_menu(settings: &mut AppSettings) -> AppResult<()> {
+    loop {
+        let encode = &settings.encode;
+        println!("\n--- QR Generation Options ---");
+        println!("1. Set Error Correction Level (Current: {})", ec_level_name(encode.ec_level));
+        println!("2. Set Version (Current: {})", describe_version(encode));
+        println!("3. Toggle Micro QR (Current: {})", if encode.micro { "Enabled" } else { "Disabled" });
+        match encode.mask {
+            Some(mask) => println!("4. Set Mask Pattern (Current: {})", mask),
+            None => println!("4. Set Mask Pattern (Current: Automatic)"),
+        }
+        match encode.quiet_zone {
+            Some(width) => println!("5. Set Quiet Zone (Current: {} modules)", width),
+            None => println!("5. Set Quiet Zone (Current: Standard)"),
+        }
+        match encode.module_size {
+            Some(size) => println!("6. Set Module Size (Current: {} px)", size),
+            None => println!("6. Set Module Size (Current: Automatic, at least {} px per image)", encode.min_dimensions),
+        }
+        println!("7. Set Colors (Current: {} on {})", format_color(encode.dark_color), format_color(encode.light_color));
+        println!("8. Set Shapes (Current: {} modules, {} finders)", encode.style.modules, encode.style.finders);
+        match &encode.style.logo {
+            Some(logo) => println!("9. Set Logo (Current: {}, {}% wide)", logo.display(), (encode.style.logo_size * 100.0).round()),
+            None => println!("9. Set Logo (Current: None)"),
+        }
+        println!("10. Set Caption (Current: {})", describe_caption(&encode.caption));
+        println!("11. Toggle Robustness Check (Current: {})", if settings.verify_robustness { "Enabled" } else { "Disabled" });
+        println!("12. Return to Settings Menu");
+        print!("Enter your choice (1-12): ");
+        io::stdout().flush()?;
+
+        let mut choice = String::new();
+        if io::stdin().read_line(&mut choice)? == 0 {
+            break;
+        }
+
+        let mut encode = settings.encode.clone();
+        let result = match choice.trim() {
+            "1" => prompt("Enter ECC level (L, M, Q or H): ")
+                .and_then(|input| parse_ec_level(&input))
+                .map(|ec_level| encode.ec_level = ec_level),
+            "2" => prompt("Enter version (leave empty for automatic): ").and_then(|input| {
+                encode.version = parse_optional_number(&input, "version")?;
+                encode.fixed_version = encode.version.is_some()
+                    && prompt("Use exactly this version instead of it as a minimum? (Y/N): ")?.to_lowercase() == "y";
+                Ok(())
+            }),
+            "3" => {
+                encode.micro = !encode.micro;
+                // Regular and Micro QR versions are numbered separately.
+                encode.version = None;
+                encode.fixed_version = false;
+                Ok(())
+            },
+            "4" => prompt("Enter mask pattern, 0-7 or 0-3 for Micro QR (leave empty for automatic): ")
+                .and_then(|input| parse_optional_number(&input, "mask"))
+                .map(|mask| encode.mask = mask),
+            "5" => prompt("Enter quiet zone width in modules (leave empty for standard): ")
+                .and_then(|input| parse_optional_number(&input, "quiet zone width"))
+                .map(|width| encode.quiet_zone = width),
+            "6" => prompt("Enter module size in pixels (leave empty for automatic): ")
+                .and_then(|input| parse_optional_number(&input, "module size"))
+                .map(|size| encode.module_size = size),
+            "7" => prompt("Enter dark color, e.g. #000000 (leave empty to keep): ").and_then(|input| {
+                if !input.is_empty() {
+                    encode.dark_color = parse_color(&input)?;
+                }
+                let input = prompt("Enter light color, e.g. #ffffff or transparent (leave empty to keep): ")?;
+                if !input.is_empty() {
+                    encode.light_color = parse_color(&input)?;
+                }
+                Ok(())
+            }),
+            "8" => prompt("Enter module shape: square, rounded or dots (leave empty to keep): ").and_then(|input| {
+                if !input.is_empty() {
+                    encode.style.modules = input.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+                }
+                let input = prompt("Enter finder shape: square, rounded or circle (leave empty to keep): ")?;
+                if !input.is_empty() {
+                    encode.style.finders = input.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+                }
+                Ok(())
+            }),
+            "9" => prompt("Enter path to logo image (leave empty for none): ").and_then(|input| {
+                encode.style.logo = Some(PathBuf::from(input)).filter(|path| !path.as_os_str().is_empty());
+                if encode.style.logo.is_some() {
+                    let input = prompt("Enter logo width as a fraction of the code, 0.05-0.35 (leave empty to keep): ")?;
+                    if !input.is_empty() {
+                        encode.style.logo_size = input.parse()
+                            .context(format!("Expected a logo size like 0.2, got: {}", input))?;
+                    }
+                }
+                Ok(())
+            }),
+            "10" => prompt("Enter caption: off, content, truncated or label (leave empty to keep): ").and_then(|input| {
+                let caption = &mut encode.caption;
+                if !input.is_empty() {
+                    caption.source = input.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+                }
+                match caption.source {
+                    CaptionSource::Off => return Ok(()),
+                    CaptionSource::Label => {
+                        let input = prompt("Enter label text (leave empty to keep): ")?;
+                        if !input.is_empty() {
+                            caption.label = Some(input);
+                        }
+                    }
+                    CaptionSource::Truncated => {
+                        let input = prompt("Enter the longest caption, in characters (leave empty to keep): ")?;
+                        if !input.is_empty() {
+                            caption.max_length = input.parse()
+                                .context(format!("Expected a number of characters, got: {}", input))?;
+                        }
+                    }
+                    CaptionSource::Content => {}
+                }
+                let input = prompt("Enter position: below or above (leave empty to keep): ")?;
+                if !input.is_empty() {
+                    caption.position = input.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+                }
+                let input = prompt("Enter margin around the caption in modules (leave empty to keep): ")?;
+                if !input.is_empty() {
+                    caption.margin = input.parse()
+                        .context(format!("Expected a number of modules, got: {}", input))?;
+                }
+                Ok(())
+            }),
+            "11" => {
+                // Not an encoding option, so it is saved without validation.
+                settings.verify_robustness = !settings.verify_robustness;
+                println!("Robustness check {}. Saving...", if settings.verify_robustness { "enabled" } else { "disabled" });
+                save_settings(settings)?;
+                continue;
+            }
+            "12" => break,
+            _ => {
+                println!("Invalid choice. Please enter a number from 1 to 12.");
+                continue;
+            }
+        };
+
+        match result.and_then(|()| encode.validate()) {
+            Ok(()) => {
+                settings.encode = encode;
+                println!("Generation options updated. Saving...");
+                save_settings(settings)?;
+            }
+            Err(e) => println!("Error: {:#}", e),
+        }
+    }
+    Ok(())
+}
+
+// Like `prompt`, but keeps leading and trailing spaces, which can be part of a password.
+fn prompt_secret(message: &str) -> AppResult<Zeroizing<String>> {
+    print!("{}", message);
+    io::stdout().flush()?;
+    let mut input = Zeroizing::new(String::new());
+    io::stdin().read_line(&mut input)?;
+    let len = input.trim_end_matches(['\r', '\n']).len();
+    input.truncate(len);
+    Ok(input)
+}
+
+fn prompt(message: &str) -> AppResult<String> {
+    print!("{}", message);
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().to_string())
+}
@@ -0,0 +1,139 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::Context;
+use clap::ValueEnum;
+
+use kripton_qr_reader::{AppResult, DecodedQr, EncodeOptions, encode};
+use kripton_qr_reader::encode::{RenderFormat, save_symbol};
+use kripton_qr_reader::otp::{MigrationBatch, OtpAccount, OtpKind, export_andotp, export_uris, is_otp_link, parse_otp};
+use kripton_qr_reader::verify::verify_saved;
+use super::output::{restrict_permissions, write_private_file};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OtpExportFormat {
+    /// One otpauth:// link per line
+    #[default]
+    Uri,
+    /// Unencrypted andOTP JSON backup
+    Json,
+    /// One QR code PNG per account, for scanning into another app
+    Qr,
+}
+
+// Reads the accounts of every otpauth:// and otpauth-migration:// code, skipping accounts
+// seen twice. Codes that cannot be read are reported and skipped, and a note is printed when
+// parts of a multi-code authenticator export are missing.
+pub fn collect_accounts(results: &[DecodedQr]) -> Vec<OtpAccount> {
+    let mut accounts: Vec<OtpAccount> = Vec::new();
+    let mut batches: Vec<MigrationBatch> = Vec::new();
+    for result in results.iter().filter(|result| is_otp_link(&result.content)) {
+        match parse_otp(&result.content) {
+            Ok(import) => {
+                for account in import.accounts {
+                    if !accounts.iter().any(|known| *known.to_uri() == *account.to_uri()) {
+                        accounts.push(account);
+                    }
+                }
+                if let Some(batch) = import.batch
+                    && !batches.contains(&batch) {
+                    batches.push(batch);
+                }
+            }
+            Err(e) => eprintln!("⚠ Skipped an authenticator code in {}: {:#}", result.source, e),
+        }
+    }
+
+    let mut ids: Vec<i64> = batches.iter().map(|batch| batch.id).collect();
+    ids.sort();
+    ids.dedup();
+    for id in ids {
+        let found: Vec<&MigrationBatch> = batches.iter().filter(|batch| batch.id == id).collect();
+        let size = found[0].size as usize;
+        if found.len() < size {
+            eprintln!(
+                "Note: only {} of the {} QR codes of an authenticator export were found; scan the others to import every account.",
+                found.len(), size
+            );
+        }
+    }
+    accounts
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+pub fn print_accounts(accounts: &[OtpAccount], show_codes: bool, show_secrets: bool) {
+    let now = unix_time();
+    println!("Found {} authenticator account(s):", accounts.len());
+    for (i, account) in accounts.iter().enumerate() {
+        println!("{}. {}", i + 1, account.name());
+        match account.kind {
+            OtpKind::Totp => println!(
+                "   TOTP, {}, {} digits, new code every {} s",
+                account.algorithm, account.digits, account.period
+            ),
+            OtpKind::Hotp => println!(
+                "   HOTP, {}, {} digits, counter {}",
+                account.algorithm, account.digits, account.counter
+            ),
+        }
+        if show_codes {
+            match account.seconds_left(now) {
+                Some(seconds) => println!("   Code: {} (valid for {} s)", account.code(now).as_str(), seconds),
+                None => println!("   Code: {} (at counter {})", account.code(now).as_str(), account.counter),
+            }
+        }
+        if show_secrets {
+            println!("   Secret: {}", account.secret_base32().as_str());
+        }
+    }
+}
+
+// Exported files hold the secrets in the clear, so they are readable only by the owner.
+// QR codes are saved as numbered PNGs in the directory `path`.
+pub fn export_accounts(
+    accounts: &[OtpAccount],
+    path: &Path,
+    format: OtpExportFormat,
+    options: &EncodeOptions,
+) -> AppResult<Vec<PathBuf>> {
+    match format {
+        OtpExportFormat::Uri => {
+            write_private_file(path, export_uris(accounts).as_bytes())?;
+            Ok(vec![path.to_path_buf()])
+        }
+        OtpExportFormat::Json => {
+            write_private_file(path, export_andotp(accounts)?.as_bytes())?;
+            Ok(vec![path.to_path_buf()])
+        }
+        OtpExportFormat::Qr => {
+            std::fs::create_dir_all(path)
+                .context(format!("Could not create output directory: {}", path.display()))?;
+            let mut paths = Vec::with_capacity(accounts.len());
+            for (i, account) in accounts.iter().enumerate() {
+                let file = path.join(format!("otp_{:02}_{}.png", i + 1, file_stem(&account.name())));
+                let uri = account.to_uri();
+                let symbol = encode(&uri, options)
+                    .context(format!("Could not generate a QR code for {}", account.name()))?;
+                save_symbol(&symbol, &file, RenderFormat::Png, options)?;
+                restrict_permissions(&file)?;
+                let saved = verify_saved(std::slice::from_ref(&file), RenderFormat::Png, std::slice::from_ref(&symbol), uri.as_bytes(), options, false);
+                if saved.is_ok_and(|v| !v.readable) {
+                    anyhow::bail!("The QR code for {} does not read back; try a larger module size.", account.name());
+                }
+                paths.push(file);
+            }
+            Ok(paths)
+        }
+    }
+}
+
+// Keeps letters, digits, dashes and dots; anything else becomes an underscore.
+fn file_stem(name: &str) -> String {
+    let stem: String = name.chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+        .collect();
+    let stem = stem.trim_matches(['_', '.']);
+    if stem.is_empty() { "account".to_string() } else { stem.chars().take(40).collect() }
+}
@@ -0,0 +1,302 @@
+use std::path::{Path, PathBuf};
+use anyhow::Context;
+use arboard::Clipboard;
+use clap::ValueEnum;
+use data_encoding::{BASE64, HEXUPPER};
+use serde::{Serialize, Deserialize};
+use zeroize::Zeroizing;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+
+use kripton_qr_reader::{AppResult, AssembledMessage, DecodedQr, assemble_messages};
+use kripton_qr_reader::content::Classification;
+use kripton_qr_reader::safety::LinkWarning;
+use kripton_qr_reader::transfer::{Transport, unpack};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    Ndjson,
+}
+
+impl OutputFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Text => "text",
+            OutputFormat::Json => "json",
+            OutputFormat::Ndjson => "ndjson",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            OutputFormat::Text => OutputFormat::Json,
+            OutputFormat::Json => OutputFormat::Ndjson,
+            OutputFormat::Ndjson => OutputFormat::Text,
+        }
+    }
+}
+
+fn copy_to_clipboard(content: &str) -> AppResult<()> {
+    let mut clipboard = Clipboard::new().context("Could not initialize clipboard")?;
+    clipboard.set_text(content.to_string())
+        .context("Could not copy content to clipboard")?;
+    #[cfg(target_os = "linux")]
+    {
+        use std::thread;
+        use std::time::Duration;
+        thread::sleep(Duration::from_millis(100));
+    }
+    Ok(())
+}
+
+// With `block_flagged`, links the check found deceptive are left off the clipboard, so a
+// disguised link cannot be pasted into a browser by accident. Plain HTTP links, shorteners
+// and unusual domains are still copied.
+pub fn copy_single_result(results: &[DecodedQr], block_flagged: bool) {
+    if results.len() != 1 {
+        return;
+    }
+    if let Some(result) = results.first() {
+        if result.is_binary() {
+            println!("The content was not copied to the clipboard because it is binary data.");
+            return;
+        }
+        if block_flagged && result.link_warnings.iter().any(LinkWarning::is_deceptive) {
+            println!("The content was not copied to the clipboard because it was flagged as a possibly deceptive link.");
+            return;
+        }
+        match copy_to_clipboard(result.content.as_str()) {
+            Ok(()) => println!("Content of the single QR code was automatically copied to the clipboard."),
+            Err(e) => eprintln!("Warning: Could not copy content to clipboard: {:?}", e),
+        }
+    }
+}
+
+/// Prints the problems that did not stop `path` from being decoded, such as unreadable pages.
+pub fn print_report_warnings(path: &Path, warnings: &[String]) {
+    for warning in warnings {
+        eprintln!("Warning: {}: {}", path.display(), warning);
+    }
+}
+
+fn format_qr_heading(index: usize, result: &DecodedQr) -> String {
+    let mut location = String::new();
+    if let Some(page) = result.page {
+        location.push_str(&format!(" (page {})", page));
+    }
+    if let Some(frame) = result.frame {
+        location.push_str(&format!(" (frame {})", frame));
+    }
+    if let Some(header) = result.structured_append {
+        location.push_str(&format!(" (part {} of {})", header.part, header.total));
+    }
+    format!("--- QR Code {} / {}{} ---", index + 1, result.source, location)
+}
+
+fn format_qr_details(result: &DecodedQr) -> String {
+    let corners: Vec<String> = result.bounds.iter()
+        .map(|[x, y]| format!("({}, {})", x, y))
+        .collect();
+    let eci = result.eci.map(|eci| format!(" (ECI {})", eci)).unwrap_or_default();
+    format!(
+        "Version: {}, ECC Level: {}, Mask: {}\nEncoding: {}{}, {} bytes\nCorners: {}\nTechnique: {} (scale {}x)\n",
+        result.version, result.ecc_level, result.mask, result.encoding, eci, result.bytes.len(),
+        corners.join(" "), result.technique, result.scale
+    )
+}
+
+// Binary payloads are shown as hex and base64 instead of their unreadable UTF-8 reading.
+fn format_content(content: &str, bytes: &[u8], binary: bool) -> Zeroizing<String> {
+    if !binary {
+        return Zeroizing::new(format!("Content: {}\n", content));
+    }
+    Zeroizing::new(format!(
+        "Content: (binary data, {} bytes)\nHex: {}\nBase64: {}\n",
+        bytes.len(), HEXUPPER.encode(bytes), BASE64.encode(bytes)
+    ))
+}
+
+// "Type:" line followed by one indented line per field; values spanning several lines are
+// indented further.
+fn format_classification(classification: &Classification) -> Zeroizing<String> {
+    let mut output = Zeroizing::new(format!("Type: {}\n", classification.kind));
+    for field in &classification.fields {
+        let value = Zeroizing::new(field.value.replace('\n', "\n    "));
+        output.push_str(&format!("  {}: {}\n", field.name, value.as_str()));
+    }
+    output
+}
+
+fn format_link_warnings(warnings: &[LinkWarning]) -> String {
+    warnings.iter().map(|warning| format!("⚠ Warning: {}\n", warning)).collect()
+}
+
+fn format_message(message: &AssembledMessage, verbose: bool) -> Zeroizing<String> {
+    let mut output = Zeroizing::new(format!(
+        "=== Structured Append message: {} of {} parts found (parity 0x{:02X}) ===\n",
+        message.found.len(), message.total, message.parity
+    ));
+    match &message.content {
+        Some(content) => {
+            let bytes = message.bytes.as_deref().map(Vec::as_slice).unwrap_or_default();
+            output.push_str(&format_content(content, bytes, message.is_binary()));
+            if let Some(classification) = &message.classification {
+                output.push_str(&format_classification(classification));
+            }
+            output.push_str(&format_link_warnings(&message.link_warnings));
+        }
+        None => {
+            let missing: Vec<String> = message.missing.iter().map(|part| part.to_string()).collect();
+            output.push_str(&format!("Missing part(s): {}\n", missing.join(", ")));
+        }
+    }
+    if message.parity_ok == Some(false) {
+        output.push_str("Warning: Parity check failed; the parts may be damaged or from different messages.\n");
+    }
+    if verbose {
+        output.push_str(&format!("Sources: {}\n", message.sources.join(", ")));
+    }
+    output
+}
+
+// Reassembled messages follow the individual codes; in JSON they are wrapped in a
+// "message" object so they can be told apart from codes. Binary payloads also carry their
+// bytes in base64.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum OutputRecord<'a> {
+    Code {
+        #[serde(flatten)]
+        code: &'a DecodedQr,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        base64: Option<Zeroizing<String>>,
+    },
+    Message {
+        message: &'a AssembledMessage,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        base64: Option<Zeroizing<String>>,
+    },
+}
+
+fn output_records<'a>(results: &'a [DecodedQr], messages: &'a [AssembledMessage]) -> Vec<OutputRecord<'a>> {
+    let base64 = |bytes: &[u8], binary: bool| binary.then(|| Zeroizing::new(BASE64.encode(bytes)));
+    results.iter()
+        .map(|code| OutputRecord::Code { code, base64: base64(&code.bytes, code.is_binary()) })
+        .chain(messages.iter().map(|message| OutputRecord::Message {
+            message,
+            base64: message.bytes.as_ref().and_then(|bytes| base64(bytes, message.is_binary())),
+        }))
+        .collect()
+}
+
+fn format_qr_results(results: &[DecodedQr], format: OutputFormat, verbose: bool) -> AppResult<Zeroizing<String>> {
+    let messages = assemble_messages(results);
+    let mut output = Zeroizing::new(String::new());
+    match format {
+        OutputFormat::Text => {
+            for (i, result) in results.iter().enumerate() {
+                output.push_str(&format_qr_heading(i, result));
+                output.push('\n');
+                output.push_str(&format_content(&result.content, &result.bytes, result.is_binary()));
+                output.push_str(&format_classification(&result.classification));
+                output.push_str(&format_link_warnings(&result.link_warnings));
+                if verbose {
+                    output.push_str(&format_qr_details(result));
+                }
+                output.push('\n');
+            }
+            for message in &messages {
+                output.push_str(&format_message(message, verbose));
+                output.push('\n');
+            }
+        }
+        OutputFormat::Json => {
+            let json = Zeroizing::new(serde_json::to_string_pretty(&output_records(results, &messages))
+                .context("Could not convert QR results to JSON format.")?);
+            output.push_str(&json);
+            output.push('\n');
+        }
+        OutputFormat::Ndjson => {
+            for record in output_records(results, &messages) {
+                let json = Zeroizing::new(serde_json::to_string(&record)
+                    .context("Could not convert QR result to JSON format.")?);
+                output.push_str(&json);
+                output.push('\n');
+            }
+        }
+    }
+    Ok(output)
+}
+
+pub fn print_qr_results(results: &[DecodedQr], format: OutputFormat, verbose: bool) -> AppResult<()> {
+    print!("{}", format_qr_results(results, format, verbose)?.as_str());
+    Ok(())
+}
+
+pub fn write_qr_content(contents: &[DecodedQr], path: &Path, format: OutputFormat, verbose: bool) -> AppResult<()> {
+    let output = format_qr_results(contents, format, verbose)?;
+
+    std::fs::write(path, output.as_bytes())
+        .context(format!("Could not write QR contents to file: {}", path.display()))?;
+    restrict_permissions(path)
+}
+
+// Writes the raw payload of every code to `path`, unpacked from `transport` text and
+// inflated with `decompress`. Complete Structured Append messages are written whole instead
+// of their parts. Several payloads are numbered `name_01.bin`, ...
+pub fn save_payloads(results: &[DecodedQr], path: &Path, transport: Transport, decompress: bool) -> AppResult<Vec<PathBuf>> {
+    let messages: Vec<AssembledMessage> = assemble_messages(results).into_iter()
+        .filter(AssembledMessage::is_complete)
+        .collect();
+    let in_message = |result: &DecodedQr| result.structured_append.is_some_and(|header| {
+        messages.iter().any(|message| message.parity == header.parity && message.total == header.total)
+    });
+    let payloads = results.iter()
+        .filter(|result| !in_message(result))
+        .map(|result| result.bytes.as_slice())
+        .chain(messages.iter().filter_map(|message| message.bytes.as_deref().map(Vec::as_slice)))
+        .map(|payload| unpack(payload, transport, decompress))
+        .collect::<AppResult<Vec<_>>>()?;
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty() && !p.exists()) {
+        std::fs::create_dir_all(parent)
+            .context(format!("Could not create output directory: {}", parent.display()))?;
+    }
+
+    if payloads.len() == 1 {
+        write_private_file(path, &payloads[0])?;
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "payload".to_string());
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let mut paths = Vec::with_capacity(payloads.len());
+    for (i, payload) in payloads.iter().enumerate() {
+        let part_path = path.with_file_name(format!("{}_{:02}{}", stem, i + 1, extension));
+        write_private_file(&part_path, payload)?;
+        paths.push(part_path);
+    }
+    Ok(paths)
+}
+
+pub fn write_private_file(path: &Path, contents: &[u8]) -> AppResult<()> {
+    std::fs::write(path, contents)
+        .context(format!("Could not write file: {}", path.display()))?;
+    restrict_permissions(path)
+}
+
+pub fn restrict_permissions(path: &Path) -> AppResult<()> {
+    #[cfg(unix)]
+    {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .context(format!("Could not set file permissions: {}", path.display()))?;
+    }
+    #[cfg(not(unix))]
+    {
+        println!("Warning: Could not set file permissions (not supported on this platform).");
+    }
+    
+    Ok(())
+}
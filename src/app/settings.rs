@@ -0,0 +1,331 @@
+use std::path::PathBuf;
+use anyhow::Context;
+use serde::{Serialize, Deserialize};
+use clap::ValueEnum;
+
+use kripton_qr_reader::{AppResult, DecodeMode, DecodeOptions, EncodeOptions};
+use kripton_qr_reader::batch::resolve_threads;
+use kripton_qr_reader::encode::{ec_level_name, format_color, parse_color, parse_ec_level};
+use kripton_qr_reader::input::ScanOptions;
+use kripton_qr_reader::preprocess::Pipeline;
+use super::output::OutputFormat;
+
+pub const APP_NAME: &str = "kripton-qr-reader";
+const SETTINGS_FILENAME: &str = "settings.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppSettings {
+    #[serde(default)] 
+    pub scan_directory: Option<PathBuf>,
+    #[serde(default)]
+    pub auto_copy_to_clipboard: bool,
+    #[serde(default)]
+    pub block_flagged_copy: bool,
+    #[serde(default)]
+    pub output_directory: Option<PathBuf>,
+    #[serde(default)]
+    pub output_format: OutputFormat,
+    #[serde(default)]
+    pub verbose_output: bool,
+    #[serde(default)]
+    pub pipeline_profile: Option<PathBuf>,
+    #[serde(default)]
+    pub preprocessing_pipeline: Option<Pipeline>,
+    #[serde(default)]
+    pub decode_mode: DecodeMode,
+    #[serde(default)]
+    pub worker_threads: usize,
+    #[serde(default)]
+    pub parallel_variants: bool,
+    #[serde(default)]
+    pub scan: ScanOptions,
+    #[serde(default = "default_frame_stride")]
+    pub frame_stride: usize,
+    #[serde(default)]
+    pub encode: EncodeOptions,
+    #[serde(default)]
+    pub verify_robustness: bool,
+}
+
+fn default_frame_stride() -> usize {
+    1
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        AppSettings {
+            scan_directory: None,
+            auto_copy_to_clipboard: false,
+            block_flagged_copy: false,
+            output_directory: None,
+            output_format: OutputFormat::default(),
+            verbose_output: false,
+            pipeline_profile: None,
+            preprocessing_pipeline: None,
+            decode_mode: DecodeMode::default(),
+            worker_threads: 0,
+            parallel_variants: false,
+            scan: ScanOptions::default(),
+            frame_stride: default_frame_stride(),
+            encode: EncodeOptions::default(),
+            verify_robustness: false,
+        }
+    }
+}
+
+impl AppSettings {
+    pub fn get(&self, key: &str) -> AppResult<String> {
+        let value = match key {
+            "scan_directory" => self.scan_directory.as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+            "auto_copy_to_clipboard" => self.auto_copy_to_clipboard.to_string(),
+            "block_flagged_copy" => self.block_flagged_copy.to_string(),
+            "output_directory" => self.output_directory.as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+            "output_format" => self.output_format.name().to_string(),
+            "verbose_output" => self.verbose_output.to_string(),
+            "pipeline_profile" => self.pipeline_profile.as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+            "decode_mode" => self.decode_mode.to_string(),
+            "worker_threads" => self.worker_threads.to_string(),
+            "parallel_variants" => self.parallel_variants.to_string(),
+            "frame_stride" => self.frame_stride.to_string(),
+            "scan_recursive" => self.scan.recursive.to_string(),
+            "scan_max_depth" => self.scan.max_depth.map(|d| d.to_string()).unwrap_or_default(),
+            "scan_follow_links" => self.scan.follow_links.to_string(),
+            "scan_include" => self.scan.include.join(","),
+            "scan_exclude" => self.scan.exclude.join(","),
+            "scan_include_hidden" => self.scan.include_hidden.to_string(),
+            "encode_ec_level" => ec_level_name(self.encode.ec_level).to_string(),
+            "encode_version" => self.encode.version.map(|v| v.to_string()).unwrap_or_default(),
+            "encode_fixed_version" => self.encode.fixed_version.to_string(),
+            "encode_micro" => self.encode.micro.to_string(),
+            "encode_mask" => self.encode.mask.map(|m| m.to_string()).unwrap_or_default(),
+            "encode_quiet_zone" => self.encode.quiet_zone.map(|q| q.to_string()).unwrap_or_default(),
+            "encode_module_size" => self.encode.module_size.map(|s| s.to_string()).unwrap_or_default(),
+            "encode_dark_color" => format_color(self.encode.dark_color),
+            "encode_light_color" => format_color(self.encode.light_color),
+            "encode_modules" => self.encode.style.modules.to_string(),
+            "encode_finders" => self.encode.style.finders.to_string(),
+            "encode_logo" => self.encode.style.logo.as_ref().map(|p| p.display().to_string()).unwrap_or_default(),
+            "encode_logo_size" => self.encode.style.logo_size.to_string(),
+            "encode_caption" => self.encode.caption.source.to_string(),
+            "encode_caption_label" => self.encode.caption.label.clone().unwrap_or_default(),
+            "encode_caption_position" => self.encode.caption.position.to_string(),
+            "encode_caption_length" => self.encode.caption.max_length.to_string(),
+            "encode_caption_margin" => self.encode.caption.margin.to_string(),
+            "encode_caption_scale" => self.encode.caption.scale.map(|s| s.to_string()).unwrap_or_default(),
+            "verify_robustness" => self.verify_robustness.to_string(),
+            _ => anyhow::bail!("Unknown setting: {}", key),
+        };
+        Ok(value)
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> AppResult<()> {
+        match key {
+            "scan_directory" => self.scan_directory = parse_directory_setting(value)?,
+            "auto_copy_to_clipboard" => {
+                self.auto_copy_to_clipboard = parse_bool_setting(value)?;
+            }
+            "block_flagged_copy" => self.block_flagged_copy = parse_bool_setting(value)?,
+            "output_directory" => self.output_directory = parse_directory_setting(value)?,
+            "output_format" => {
+                self.output_format = OutputFormat::from_str(value.trim(), true)
+                    .map_err(|e| anyhow::anyhow!(e))?;
+            }
+            "verbose_output" => self.verbose_output = parse_bool_setting(value)?,
+            "pipeline_profile" => self.pipeline_profile = parse_profile_setting(value)?,
+            "decode_mode" => self.decode_mode = value.parse().map_err(|e: String| anyhow::anyhow!(e))?,
+            "worker_threads" => {
+                self.worker_threads = value.trim().parse()
+                    .context(format!("Expected a non-negative number of threads, got: {}", value))?;
+            }
+            "parallel_variants" => self.parallel_variants = parse_bool_setting(value)?,
+            "frame_stride" => self.frame_stride = parse_frame_stride(value)?,
+            "scan_recursive" => self.scan.recursive = parse_bool_setting(value)?,
+            "scan_max_depth" => {
+                self.scan.max_depth = if value.trim().is_empty() {
+                    None
+                } else {
+                    Some(value.trim().parse()
+                        .context(format!("Expected a non-negative depth, got: {}", value))?)
+                };
+            }
+            "scan_follow_links" => self.scan.follow_links = parse_bool_setting(value)?,
+            "scan_include" => self.scan.include = parse_pattern_list(value)?,
+            "scan_exclude" => self.scan.exclude = parse_pattern_list(value)?,
+            "scan_include_hidden" => self.scan.include_hidden = parse_bool_setting(value)?,
+            "verify_robustness" => self.verify_robustness = parse_bool_setting(value)?,
+            key if key.starts_with("encode_") => {
+                // Options are checked together, so an invalid combination is not saved.
+                let mut encode = self.encode.clone();
+                match key {
+                    "encode_ec_level" => encode.ec_level = parse_ec_level(value)?,
+                    "encode_version" => encode.version = parse_optional_number(value, "version")?,
+                    "encode_fixed_version" => encode.fixed_version = parse_bool_setting(value)?,
+                    "encode_micro" => encode.micro = parse_bool_setting(value)?,
+                    "encode_mask" => encode.mask = parse_optional_number(value, "mask")?,
+                    "encode_quiet_zone" => encode.quiet_zone = parse_optional_number(value, "quiet zone width")?,
+                    "encode_module_size" => encode.module_size = parse_optional_number(value, "module size")?,
+                    "encode_dark_color" => encode.dark_color = parse_color(value)?,
+                    "encode_light_color" => encode.light_color = parse_color(value)?,
+                    "encode_modules" => encode.style.modules = value.parse().map_err(|e: String| anyhow::anyhow!(e))?,
+                    "encode_finders" => encode.style.finders = value.parse().map_err(|e: String| anyhow::anyhow!(e))?,
+                    "encode_logo" => {
+                        encode.style.logo = Some(value.trim()).filter(|v| !v.is_empty()).map(PathBuf::from);
+                    }
+                    "encode_logo_size" => {
+                        encode.style.logo_size = value.trim().parse()
+                            .context(format!("Expected a logo size like 0.2, got: {}", value))?;
+                    }
+                    "encode_caption" => encode.caption.source = value.parse().map_err(|e: String| anyhow::anyhow!(e))?,
+                    "encode_caption_label" => encode.caption.label = Some(value.to_string()).filter(|v| !v.is_empty()),
+                    "encode_caption_position" => encode.caption.position = value.parse().map_err(|e: String| anyhow::anyhow!(e))?,
+                    "encode_caption_length" => {
+                        encode.caption.max_length = value.trim().parse()
+                            .context(format!("Expected a number of characters, got: {}", value))?;
+                    }
+                    "encode_caption_margin" => {
+                        encode.caption.margin = value.trim().parse()
+                            .context(format!("Expected a number of modules, got: {}", value))?;
+                    }
+                    "encode_caption_scale" => encode.caption.scale = parse_optional_number(value, "caption scale")?,
+                    _ => anyhow::bail!("Unknown setting: {}", key),
+                }
+                encode.validate()?;
+                self.encode = encode;
+            }
+            _ => anyhow::bail!("Unknown setting: {}", key),
+        }
+        Ok(())
+    }
+
+    pub fn default_output_directory(&self) -> PathBuf {
+        self.output_directory.as_ref()
+            .or(self.scan_directory.as_ref())
+            .cloned()
+            .unwrap_or_else(|| PathBuf::from("."))
+    }
+
+    // A profile file takes precedence over a pipeline stored inline in the settings.
+    pub fn decode_options(&self) -> AppResult<DecodeOptions> {
+        let pipeline = match (&self.pipeline_profile, &self.preprocessing_pipeline) {
+            (Some(path), _) => Pipeline::load(path)?,
+            (None, Some(pipeline)) => {
+                pipeline.validate().context("Preprocessing pipeline in settings is invalid.")?;
+                pipeline.clone()
+            }
+            (None, None) => Pipeline::default(),
+        };
+        let variant_threads = if self.parallel_variants { resolve_threads(self.worker_threads) } else { 1 };
+        Ok(DecodeOptions { pipeline, mode: self.decode_mode, variant_threads, frame_stride: self.frame_stride.max(1) })
+    }
+
+    pub fn encode_options(&self) -> EncodeOptions {
+        self.encode.clone()
+    }
+}
+
+fn parse_directory_setting(value: &str) -> AppResult<Option<PathBuf>> {
+    if value.trim().is_empty() {
+        return Ok(None);
+    }
+    let path = PathBuf::from(value.trim());
+    if !path.is_dir() {
+        anyhow::bail!("The provided path is not a valid directory: {}", path.display());
+    }
+    Ok(Some(path))
+}
+
+fn parse_profile_setting(value: &str) -> AppResult<Option<PathBuf>> {
+    if value.trim().is_empty() {
+        return Ok(None);
+    }
+    let path = PathBuf::from(value.trim());
+    Pipeline::load(&path)?;
+    Ok(Some(path))
+}
+
+pub fn parse_pattern_list(value: &str) -> AppResult<Vec<String>> {
+    let patterns: Vec<String> = value.split(',')
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect();
+    for pattern in &patterns {
+        globset::Glob::new(pattern).context(format!("Invalid glob pattern: {}", pattern))?;
+    }
+    Ok(patterns)
+}
+
+pub fn parse_optional_number<T: std::str::FromStr>(value: &str, what: &str) -> AppResult<Option<T>> {
+    if value.trim().is_empty() {
+        return Ok(None);
+    }
+    match value.trim().parse() {
+        Ok(number) => Ok(Some(number)),
+        Err(_) => anyhow::bail!("Expected a non-negative {}, got: {}", what, value),
+    }
+}
+
+pub fn parse_frame_stride(value: &str) -> AppResult<usize> {
+    match value.trim().parse::<usize>() {
+        Ok(stride) if stride > 0 => Ok(stride),
+        _ => anyhow::bail!("Expected a positive frame stride, got: {}", value),
+    }
+}
+
+fn parse_bool_setting(value: &str) -> AppResult<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => anyhow::bail!("Expected a boolean value (true/false), got: {}", value),
+    }
+}
+
+fn get_settings_path() -> AppResult<PathBuf> {
+    let mut path = dirs::data_dir()
+        .context("User data directory not found.")?;
+    
+    path.push(APP_NAME);
+    if !path.exists() {
+        std::fs::create_dir_all(&path)
+            .context(format!("Could not create settings directory: {}", path.display()))?;
+    }
+    
+    path.push(SETTINGS_FILENAME);
+    Ok(path)
+}
+
+pub fn load_settings() -> AppResult<AppSettings> {
+    let settings_path = get_settings_path()?;
+
+    if settings_path.exists() {
+        let content = std::fs::read_to_string(&settings_path)
+            .context(format!("Could not read settings file: {}", settings_path.display()))?;
+        let mut settings: AppSettings = serde_json::from_str(&content)
+            .context("Settings file format is invalid.")?;
+        // A hand-edited value must not reach the encoder unchecked; the other settings stay.
+        if let Err(e) = settings.encode.validate() {
+            eprintln!("Warning: invalid encode settings in {} ({:#}); using the defaults instead.", settings_path.display(), e);
+            settings.encode = EncodeOptions::default();
+        }
+        Ok(settings)
+    } else {
+        eprintln!("Settings file ({}) not found, using default settings.", settings_path.display());
+        Ok(AppSettings::default())
+    }
+}
+
+pub fn save_settings(settings: &AppSettings) -> AppResult<()> {
+    let settings_path = get_settings_path()?;
+    
+    let content = serde_json::to_string_pretty(settings)
+        .context("Could not convert settings to JSON format.")?;
+    
+    std::fs::write(&settings_path, content)
+        .context(format!("Could not write settings to file: {}", settings_path.display()))?;
+    Ok(())
+}
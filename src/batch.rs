@@ -0,0 +1,112 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::AppResult;
+use crate::decode::{DecodeOptions, DecodeReport, decode_path_report};
+
+/// Resolves a configured thread count, where 0 means one thread per available CPU.
+pub fn resolve_threads(threads: usize) -> usize {
+    if threads > 0 {
+        threads
+    } else {
+        thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+    }
+}
+
+/// Decodes `paths` on a pool of `threads` workers.
+///
+/// The threads are one budget shared with `options.variant_threads`: each of the file
+/// workers decodes variants on at most its share of them, so a single file can use all
+/// of them while a full queue decodes variants sequentially.
+///
+/// Reports are returned in the same order as `paths` regardless of which worker
+/// finished first. `on_done` is called from the workers as each file completes,
+/// with the number of files completed so far.
+pub fn decode_paths<F>(paths: &[PathBuf], options: &DecodeOptions, threads: usize, on_done: F) -> Vec<AppResult<DecodeReport>>
+where
+    F: Fn(usize, &Path, &AppResult<DecodeReport>) + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let completed = AtomicUsize::new(0);
+    let slots: Vec<Mutex<Option<AppResult<DecodeReport>>>> = paths.iter().map(|_| Mutex::new(None)).collect();
+    let (workers, variant_threads) = split_threads(resolve_threads(threads), paths.len(), options.variant_threads);
+    let options = &DecodeOptions { variant_threads, ..options.clone() };
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(path) = paths.get(index) else {
+                    break;
+                };
+                let report = decode_path_report(path, options);
+                let done = completed.fetch_add(1, Ordering::SeqCst) + 1;
+                on_done(done, path, &report);
+                *slots[index].lock().unwrap_or_else(|e| e.into_inner()) = Some(report);
+            });
+        }
+    });
+
+    slots.into_iter()
+        .map(|slot| {
+            slot.into_inner()
+                .unwrap_or_else(|e| e.into_inner())
+                .unwrap_or_else(|| Err(anyhow::anyhow!("File was not processed.")))
+        })
+        .collect()
+}
+
+// Returns how many file workers to start and how many variant threads each may use,
+// so that the two together stay within `threads`.
+fn split_threads(threads: usize, files: usize, variant_threads: usize) -> (usize, usize) {
+    let workers = threads.min(files).max(1);
+    (workers, variant_threads.min(threads / workers).max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Luma};
+
+    use super::*;
+    use crate::encode::{EncodeOptions, encode, render_image};
+
+    #[test]
+    fn shares_threads_between_files_and_variants() {
+        assert_eq!(split_threads(8, 1, 8), (1, 8));
+        assert_eq!(split_threads(8, 2, 8), (2, 4));
+        assert_eq!(split_threads(8, 100, 8), (8, 1));
+        assert_eq!(split_threads(8, 3, 1), (3, 1));
+        assert_eq!(split_threads(1, 0, 4), (1, 1));
+    }
+
+    #[test]
+    fn returns_reports_in_input_order() {
+        let dir = std::env::temp_dir().join(format!("{}-batch-{}", env!("CARGO_PKG_NAME"), std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut paths = Vec::new();
+        for i in 0..6 {
+            let path = dir.join(format!("{}.png", i));
+            if i == 3 {
+                // A blank image takes every variant to fail, so it finishes last.
+                DynamicImage::ImageLuma8(image::ImageBuffer::from_pixel(400, 400, Luma([255u8]))).save(&path).unwrap();
+            } else {
+                let options = EncodeOptions::default();
+                let symbol = encode(&format!("file {}", i), &options).unwrap();
+                render_image(&symbol, &options).unwrap().save(&path).unwrap();
+            }
+            paths.push(path);
+        }
+
+        let options = DecodeOptions { variant_threads: 4, ..DecodeOptions::default() };
+        let reports = decode_paths(&paths, &options, 4, |_, _, _| {});
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(reports.len(), paths.len());
+        for (i, report) in reports.iter().enumerate() {
+            let contents: Vec<_> = report.as_ref().unwrap().results.iter().map(|r| r.content.to_string()).collect();
+            let expected = if i == 3 { Vec::new() } else { vec![format!("file {}", i)] };
+            assert_eq!(contents, expected);
+        }
+    }
+}
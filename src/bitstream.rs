@@ -0,0 +1,602 @@
+use anyhow::Context;
+use qrcode::{EcLevel, Version};
+use qrcode::bits::Bits;
+use qrcode::canvas::is_functional;
+use qrcode::ec::construct_codewords;
+use rqrr::{BitGrid, Grid, MetaData};
+use zeroize::Zeroizing;
+
+use crate::AppResult;
+use crate::structured_append::StructuredAppend;
+
+pub(crate) const ALPHANUMERIC_CHARSET: &[u8; 45] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// Data segments of a symbol, decoded without rqrr's payload parser.
+pub(crate) struct Payload {
+    pub(crate) structured_append: Option<StructuredAppend>,
+    pub(crate) bytes: Zeroizing<Vec<u8>>,
+    /// How each stretch of `bytes` was encoded, in order.
+    pub(crate) runs: Vec<Run>,
+}
+
+/// Encoding of the payload bytes up to `end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Run {
+    pub(crate) kind: RunKind,
+    pub(crate) end: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RunKind {
+    /// Numeric or alphanumeric segments.
+    Ascii,
+    /// Byte segments, under the ECI designator in effect, if any.
+    Bytes(Option<u32>),
+    /// Kanji segments, written out as Shift JIS.
+    Kanji,
+}
+
+impl Payload {
+    fn end_run(&mut self, kind: RunKind) {
+        let end = self.bytes.len();
+        match self.runs.last_mut() {
+            Some(run) if run.kind == kind => run.end = end,
+            _ => self.runs.push(Run { kind, end }),
+        }
+    }
+}
+
+/// Decodes a grid into its payload bytes, Structured Append header and segment layout,
+/// which rqrr's own decoder does not report.
+///
+/// rqrr only exposes the uncorrected, still-masked bit stream, so the mask is removed
+/// here and the Reed-Solomon correction is redone before the segments are parsed.
+pub(crate) fn decode_grid(grid: &dyn BitGrid) -> AppResult<(MetaData, Payload)> {
+    decode_oriented(grid, false).or_else(|e| decode_oriented(grid, true).map_err(|_| e))
+}
+
+fn decode_oriented(grid: &dyn BitGrid, mirrored: bool) -> AppResult<(MetaData, Payload)> {
+    let (meta, _) = Grid::new(GridView { grid, mirrored, mask: None }).get_raw_data()
+        .map_err(|e| anyhow::anyhow!("Could not read format information: {}", e))?;
+    let version = Version::Normal(meta.version.0 as i16);
+    let (_, raw) = Grid::new(GridView { grid, mirrored, mask: Some((meta.mask, version)) }).get_raw_data()
+        .map_err(|e| anyhow::anyhow!("Could not read data modules: {}", e))?;
+
+    let ec_level = match meta.ecc_level {
+        0 => EcLevel::M,
+        1 => EcLevel::L,
+        2 => EcLevel::H,
+        _ => EcLevel::Q,
+    };
+    let data = correct_codewords(&raw.data[..raw.len / 8], version, ec_level)?;
+    let payload = parse_segments(&data, meta.version.0)?;
+    Ok((meta, payload))
+}
+
+struct GridView<'a> {
+    grid: &'a dyn BitGrid,
+    mirrored: bool,
+    mask: Option<(u16, Version)>,
+}
+
+impl BitGrid for GridView<'_> {
+    fn size(&self) -> usize {
+        self.grid.size()
+    }
+
+    fn bit(&self, y: usize, x: usize) -> bool {
+        let bit = if self.mirrored { self.grid.bit(x, y) } else { self.grid.bit(y, x) };
+        match self.mask {
+            Some((mask, version)) if !is_functional(version, self.size() as i16, x as i16, y as i16) => {
+                bit ^ mask_bit(mask, y, x)
+            }
+            _ => bit,
+        }
+    }
+}
+
+fn mask_bit(mask: u16, y: usize, x: usize) -> bool {
+    match mask {
+        0 => (y + x).is_multiple_of(2),
+        1 => y.is_multiple_of(2),
+        2 => x.is_multiple_of(3),
+        3 => (y + x).is_multiple_of(3),
+        4 => (y / 2 + x / 3).is_multiple_of(2),
+        5 => (y * x) % 2 + (y * x) % 3 == 0,
+        6 => ((y * x) % 2 + (y * x) % 3).is_multiple_of(2),
+        _ => ((y + x) % 2 + (y * x) % 3).is_multiple_of(2),
+    }
+}
+
+// Splits the interleaved codewords into blocks, corrects each one and returns the data
+// codewords in their original order.
+fn correct_codewords(codewords: &[u8], version: Version, ec_level: EcLevel) -> AppResult<Vec<u8>> {
+    let data_len = Bits::new(version).max_len(ec_level)? / 8;
+    if codewords.len() <= data_len {
+        anyhow::bail!("Symbol has fewer codewords than its version requires");
+    }
+
+    // construct_codewords interleaves whatever it is given, so feeding it the data indices
+    // reveals the block layout. Block starts come first, then the second codeword of block 0.
+    let low: Vec<u8> = (0..data_len).map(|i| i as u8).collect();
+    let high: Vec<u8> = (0..data_len).map(|i| (i >> 8) as u8).collect();
+    let (low, _) = construct_codewords(&low, version, ec_level)?;
+    let (high, _) = construct_codewords(&high, version, ec_level)?;
+    let order: Vec<usize> = low.iter().zip(&high)
+        .map(|(&low, &high)| (high as usize) << 8 | low as usize)
+        .collect();
+    let block_count = order.iter().position(|&i| i == 1).unwrap_or(1);
+
+    let mut data = vec![0u8; data_len];
+    for (&index, &codeword) in order.iter().zip(codewords) {
+        data[index] = codeword;
+    }
+    let ec = &codewords[data_len..];
+    let ec_per_block = ec.len() / block_count;
+    let short_len = data_len / block_count;
+    let long_blocks = data_len % block_count;
+
+    let mut corrected = Vec::with_capacity(data_len);
+    let mut start = 0;
+    for block_index in 0..block_count {
+        let len = short_len + usize::from(block_index >= block_count - long_blocks);
+        let mut block = data[start..start + len].to_vec();
+        block.extend((0..ec_per_block).map(|j| ec[j * block_count + block_index]));
+        correct_block(&mut block, ec_per_block)
+            .context(format!("Too many errors in block {}", block_index + 1))?;
+        corrected.extend_from_slice(&block[..len]);
+        start += len;
+    }
+    Ok(corrected)
+}
+
+const GF_EXP: [u8; 512] = {
+    let mut table = [0u8; 512];
+    let mut x: u16 = 1;
+    let mut i = 0;
+    while i < 512 {
+        table[i] = x as u8;
+        x <<= 1;
+        if x & 0x100 != 0 {
+            x ^= 0x11d;
+        }
+        i += 1;
+    }
+    table
+};
+
+const GF_LOG: [u8; 256] = {
+    let mut table = [0u8; 256];
+    let mut i = 0;
+    while i < 255 {
+        table[GF_EXP[i] as usize] = i as u8;
+        i += 1;
+    }
+    table
+};
+
+fn gf_mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+    GF_EXP[GF_LOG[a as usize] as usize + GF_LOG[b as usize] as usize]
+}
+
+fn gf_div(a: u8, b: u8) -> u8 {
+    if a == 0 {
+        return 0;
+    }
+    GF_EXP[GF_LOG[a as usize] as usize + 255 - GF_LOG[b as usize] as usize]
+}
+
+fn gf_alpha(power: usize) -> u8 {
+    GF_EXP[power % 255]
+}
+
+// Evaluates a polynomial stored lowest degree first.
+fn poly_eval(poly: &[u8], x: u8) -> u8 {
+    poly.iter().rev().fold(0, |acc, &c| gf_mul(acc, x) ^ c)
+}
+
+// Reed-Solomon correction of one block (data then EC codewords, highest power first),
+// using Berlekamp-Massey for the error locator and Forney for the magnitudes.
+fn correct_block(block: &mut [u8], ec_len: usize) -> AppResult<()> {
+    let syndromes = |block: &[u8]| -> Vec<u8> {
+        (0..ec_len)
+            .map(|i| block.iter().fold(0, |acc, &c| gf_mul(acc, gf_alpha(i)) ^ c))
+            .collect()
+    };
+    let s = syndromes(block);
+    if s.iter().all(|&v| v == 0) {
+        return Ok(());
+    }
+
+    let mut locator = vec![1u8];
+    let mut previous = vec![1u8];
+    let mut errors = 0;
+    let mut shift = 1;
+    let mut previous_discrepancy = 1u8;
+    for r in 0..ec_len {
+        let discrepancy = (1..locator.len().min(r + 1)).fold(s[r], |acc, i| acc ^ gf_mul(locator[i], s[r - i]));
+        if discrepancy == 0 {
+            shift += 1;
+            continue;
+        }
+        let factor = gf_div(discrepancy, previous_discrepancy);
+        let snapshot = locator.clone();
+        if locator.len() < previous.len() + shift {
+            locator.resize(previous.len() + shift, 0);
+        }
+        for (i, &c) in previous.iter().enumerate() {
+            locator[i + shift] ^= gf_mul(factor, c);
+        }
+        if 2 * errors <= r {
+            errors = r + 1 - errors;
+            previous = snapshot;
+            previous_discrepancy = discrepancy;
+            shift = 1;
+        } else {
+            shift += 1;
+        }
+    }
+    locator.truncate(errors + 1);
+    if 2 * errors > ec_len {
+        anyhow::bail!("Uncorrectable block");
+    }
+
+    let n = block.len();
+    let positions: Vec<usize> = (0..n)
+        .filter(|&p| poly_eval(&locator, gf_alpha(255 - p % 255)) == 0)
+        .collect();
+    if positions.len() != errors {
+        anyhow::bail!("Uncorrectable block");
+    }
+
+    let mut evaluator = vec![0u8; ec_len];
+    for (i, &si) in s.iter().enumerate() {
+        for (j, &lj) in locator.iter().enumerate().take(ec_len - i) {
+            evaluator[i + j] ^= gf_mul(si, lj);
+        }
+    }
+    for p in positions {
+        let x_inv = gf_alpha(255 - p % 255);
+        let derivative = (1..locator.len()).step_by(2)
+            .fold(0, |acc, i| acc ^ gf_mul(locator[i], gf_alpha(GF_LOG[x_inv as usize] as usize * (i - 1))));
+        if derivative == 0 {
+            anyhow::bail!("Uncorrectable block");
+        }
+        let magnitude = gf_mul(gf_alpha(p), gf_div(poly_eval(&evaluator, x_inv), derivative));
+        block[n - 1 - p] ^= magnitude;
+    }
+
+    if syndromes(block).iter().any(|&v| v != 0) {
+        anyhow::bail!("Uncorrectable block");
+    }
+    Ok(())
+}
+
+struct BitWriter {
+    data: Vec<u8>,
+    len: usize,
+}
+
+impl BitWriter {
+    fn push(&mut self, bits: usize, value: usize) {
+        for i in (0..bits).rev() {
+            if self.len.is_multiple_of(8) {
+                self.data.push(0);
+            }
+            if value >> i & 1 == 1 {
+                *self.data.last_mut().unwrap() |= 0x80 >> (self.len % 8);
+            }
+            self.len += 1;
+        }
+    }
+}
+
+/// Whether `data` can be written as one alphanumeric segment.
+pub(crate) fn is_alphanumeric(data: &[u8]) -> bool {
+    data.iter().all(|byte| ALPHANUMERIC_CHARSET.contains(byte))
+}
+
+/// Bits needed for a Structured Append symbol carrying `len` bytes in one segment, which is
+/// alphanumeric when `alphanumeric` is set and a byte segment otherwise.
+pub(crate) fn structured_append_bits(len: usize, alphanumeric: bool, version: i16) -> usize {
+    if alphanumeric {
+        20 + 4 + count_bits(version as usize, [9, 11, 13]) + len / 2 * 11 + len % 2 * 6
+    } else {
+        20 + 4 + count_bits(version as usize, [8, 16, 16]) + len * 8
+    }
+}
+
+/// Data codewords of a Structured Append symbol: `header`, then `data` as an alphanumeric
+/// segment when every byte allows it, or as a byte segment.
+///
+/// `qrcode` has no public way to write the header, so the codewords are laid out here and
+/// only error correction and drawing are left to it.
+pub(crate) fn structured_append_codewords(
+    header: StructuredAppend,
+    data: &[u8],
+    version: Version,
+    ec_level: EcLevel,
+) -> AppResult<Vec<u8>> {
+    let Version::Normal(number) = version else {
+        anyhow::bail!("Structured Append is not available for Micro QR codes");
+    };
+    let capacity = Bits::new(version).max_len(ec_level)?;
+    let alphanumeric = is_alphanumeric(data);
+    if structured_append_bits(data.len(), alphanumeric, number) > capacity {
+        anyhow::bail!("Data does not fit in a version {} symbol", number);
+    }
+
+    let mut writer = BitWriter { data: Vec::new(), len: 0 };
+    writer.push(4, 3);
+    writer.push(4, header.part as usize - 1);
+    writer.push(4, header.total as usize - 1);
+    writer.push(8, header.parity as usize);
+    if alphanumeric {
+        let value = |byte: &u8| ALPHANUMERIC_CHARSET.iter().position(|c| c == byte).unwrap_or(0);
+        writer.push(4, 2);
+        writer.push(count_bits(number as usize, [9, 11, 13]), data.len());
+        for pair in data.chunks(2) {
+            match pair {
+                [a, b] => writer.push(11, value(a) * 45 + value(b)),
+                [a] => writer.push(6, value(a)),
+                _ => unreachable!("chunks of two"),
+            }
+        }
+    } else {
+        writer.push(4, 4);
+        writer.push(count_bits(number as usize, [8, 16, 16]), data.len());
+        for &byte in data {
+            writer.push(8, byte as usize);
+        }
+    }
+    // Terminator, then zero bits up to a whole codeword, then alternating pad codewords.
+    writer.push((capacity - writer.len).min(4), 0);
+    writer.len = writer.len.next_multiple_of(8);
+    let mut codewords = writer.data;
+    codewords.extend([0xec, 0x11].into_iter().cycle().take(capacity / 8 - codewords.len()));
+    Ok(codewords)
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn remaining(&self) -> usize {
+        self.data.len() * 8 - self.position
+    }
+
+    fn take(&mut self, bits: usize) -> AppResult<usize> {
+        if self.remaining() < bits {
+            anyhow::bail!("Data ends in the middle of a segment");
+        }
+        let mut value = 0;
+        for _ in 0..bits {
+            let bit = self.data[self.position / 8] >> (7 - self.position % 8) & 1;
+            value = value << 1 | bit as usize;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+}
+
+fn count_bits(version: usize, bits: [usize; 3]) -> usize {
+    match version {
+        0..=9 => bits[0],
+        10..=26 => bits[1],
+        _ => bits[2],
+    }
+}
+
+fn parse_segments(data: &[u8], version: usize) -> AppResult<Payload> {
+    let mut reader = BitReader { data, position: 0 };
+    let mut payload = Payload { structured_append: None, bytes: Zeroizing::new(Vec::new()), runs: Vec::new() };
+    let mut eci = None;
+
+    while reader.remaining() >= 4 {
+        match reader.take(4)? {
+            0 => break,
+            // Numeric
+            1 => {
+                let mut count = reader.take(count_bits(version, [10, 12, 14]))?;
+                while count > 0 {
+                    let digits = count.min(3);
+                    let value = reader.take([4, 7, 10][digits - 1])?;
+                    let text = format!("{:0width$}", value, width = digits);
+                    if text.len() != digits {
+                        anyhow::bail!("Invalid numeric segment");
+                    }
+                    payload.bytes.extend_from_slice(text.as_bytes());
+                    count -= digits;
+                }
+                payload.end_run(RunKind::Ascii);
+            }
+            // Alphanumeric
+            2 => {
+                let mut count = reader.take(count_bits(version, [9, 11, 13]))?;
+                while count > 0 {
+                    if count >= 2 {
+                        let value = reader.take(11)?;
+                        let (first, second) = (value / 45, value % 45);
+                        if first >= 45 {
+                            anyhow::bail!("Invalid alphanumeric segment");
+                        }
+                        payload.bytes.push(ALPHANUMERIC_CHARSET[first]);
+                        payload.bytes.push(ALPHANUMERIC_CHARSET[second]);
+                        count -= 2;
+                    } else {
+                        let value = reader.take(6)?;
+                        payload.bytes.push(*ALPHANUMERIC_CHARSET.get(value).context("Invalid alphanumeric segment")?);
+                        count -= 1;
+                    }
+                }
+                payload.end_run(RunKind::Ascii);
+            }
+            // Structured Append header
+            3 => {
+                let index = reader.take(4)? as u8;
+                let total = reader.take(4)? as u8 + 1;
+                let parity = reader.take(8)? as u8;
+                if index >= total {
+                    anyhow::bail!("Structured Append part {} is beyond the {} parts of its sequence", index + 1, total);
+                }
+                let header = StructuredAppend { part: index + 1, total, parity };
+                if payload.structured_append.is_some_and(|first| (first.total, first.parity) != (total, parity)) {
+                    anyhow::bail!("Structured Append headers of one symbol disagree");
+                }
+                payload.structured_append.get_or_insert(header);
+            }
+            // Byte
+            4 => {
+                let count = reader.take(count_bits(version, [8, 16, 16]))?;
+                for _ in 0..count {
+                    payload.bytes.push(reader.take(8)? as u8);
+                }
+                payload.end_run(RunKind::Bytes(eci));
+            }
+            // FNC1 in first position
+            5 => {}
+            // ECI designator: the character set of the byte segments that follow.
+            7 => {
+                let first = reader.take(8)?;
+                let value = match first {
+                    0x00..=0x7f => first,
+                    0x80..=0xbf => (first & 0x3f) << 8 | reader.take(8)?,
+                    _ => (first & 0x1f) << 16 | reader.take(16)?,
+                };
+                eci = Some(value as u32);
+            }
+            // Kanji, as Shift JIS bytes
+            8 => {
+                let count = reader.take(count_bits(version, [8, 10, 12]))?;
+                for _ in 0..count {
+                    let value = reader.take(13)?;
+                    let value = ((value / 0xc0) << 8) | (value % 0xc0);
+                    let sjis = if value + 0x8140 <= 0x9ffc { value + 0x8140 } else { value + 0xc140 };
+                    payload.bytes.push((sjis >> 8) as u8);
+                    payload.bytes.push(sjis as u8);
+                }
+                payload.end_run(RunKind::Kanji);
+            }
+            // FNC1 in second position, followed by an application indicator
+            9 => {
+                reader.take(8)?;
+            }
+            mode => anyhow::bail!("Unknown segment mode: {}", mode),
+        }
+    }
+
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoded(data: &[u8], version: Version, ec_level: EcLevel) -> Vec<u8> {
+        let (data, ec) = construct_codewords(data, version, ec_level).unwrap();
+        [data, ec].concat()
+    }
+
+    #[test]
+    fn corrects_errors_up_to_half_the_ec_codewords() {
+        let data: Vec<u8> = (0..19).map(|i| i * 13 + 7).collect();
+        let clean = encoded(&data, Version::Normal(1), EcLevel::L);
+        assert_eq!(clean.len(), 26);
+
+        let mut block = clean.clone();
+        correct_block(&mut block, 7).unwrap();
+        assert_eq!(block, clean);
+
+        for positions in [&[0][..], &[3, 20], &[1, 10, 25]] {
+            let mut block = clean.clone();
+            for &p in positions {
+                block[p] ^= 0x5a;
+            }
+            correct_block(&mut block, 7).unwrap();
+            assert_eq!(block, clean, "errors at {:?}", positions);
+        }
+    }
+
+    #[test]
+    fn rejects_too_many_errors() {
+        let data: Vec<u8> = (0..9).collect();
+        let mut block = encoded(&data, Version::Normal(1), EcLevel::H);
+        for p in [0, 2, 4, 6, 8, 10, 12, 14, 16] {
+            block[p] ^= 0xff;
+        }
+        assert!(correct_block(&mut block, 17).is_err());
+    }
+
+    #[test]
+    fn corrects_interleaved_blocks() {
+        // Version 5-Q has two blocks of 15 and two of 16 data codewords.
+        let version = Version::Normal(5);
+        let data_len = Bits::new(version).max_len(EcLevel::Q).unwrap() / 8;
+        let data: Vec<u8> = (0..data_len).map(|i| (i * 31 % 251) as u8).collect();
+        let mut codewords = encoded(&data, version, EcLevel::Q);
+        for p in (0..codewords.len()).step_by(11) {
+            codewords[p] ^= 0xa5;
+        }
+        assert_eq!(correct_codewords(&codewords, version, EcLevel::Q).unwrap(), data);
+    }
+
+    #[test]
+    fn structured_append_round_trip() {
+        let header = StructuredAppend { part: 2, total: 3, parity: 0x5c };
+        for data in [&b"HELLO WORLD 123"[..], b"mixed Case bytes \xff\x00"] {
+            let codewords = structured_append_codewords(header, data, Version::Normal(3), EcLevel::M).unwrap();
+            let payload = parse_segments(&codewords, 3).unwrap();
+            assert_eq!(payload.structured_append, Some(header));
+            assert_eq!(&payload.bytes[..], data);
+        }
+    }
+
+    #[test]
+    fn parses_numeric_and_eci_segments() {
+        let mut writer = BitWriter { data: Vec::new(), len: 0 };
+        writer.push(4, 1);
+        writer.push(10, 5);
+        writer.push(10, 123);
+        writer.push(7, 45);
+        writer.push(4, 7);
+        writer.push(8, 26);
+        writer.push(4, 4);
+        writer.push(8, 2);
+        writer.push(16, 0xc3a9);
+        writer.push(4, 0);
+        let payload = parse_segments(&writer.data, 1).unwrap();
+        assert_eq!(&payload.bytes[..], b"12345\xc3\xa9");
+        assert_eq!(payload.runs, [
+            Run { kind: RunKind::Ascii, end: 5 },
+            Run { kind: RunKind::Bytes(Some(26)), end: 7 },
+        ]);
+    }
+
+    #[test]
+    fn rejects_invalid_structured_append_headers() {
+        let header = |writer: &mut BitWriter, index, total, parity| {
+            writer.push(4, 3);
+            writer.push(4, index);
+            writer.push(4, total);
+            writer.push(8, parity);
+        };
+
+        // Part 3 of a sequence of 2.
+        let mut writer = BitWriter { data: Vec::new(), len: 0 };
+        header(&mut writer, 2, 1, 0);
+        writer.push(4, 0);
+        assert!(parse_segments(&writer.data, 1).is_err());
+
+        // Two headers that disagree on the sequence.
+        let mut writer = BitWriter { data: Vec::new(), len: 0 };
+        header(&mut writer, 0, 1, 0x10);
+        header(&mut writer, 0, 1, 0x20);
+        writer.push(4, 0);
+        assert!(parse_segments(&writer.data, 1).is_err());
+    }
+}
@@ -0,0 +1,339 @@
+use std::fmt;
+use std::str::FromStr;
+use image::RgbaImage;
+use serde::{Serialize, Deserialize};
+
+use crate::AppResult;
+use crate::encode::EncodeOptions;
+
+/// What the caption under or over a code shows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptionSource {
+    /// No caption.
+    #[default]
+    Off,
+    /// The whole payload, wrapped over as many lines as it needs.
+    Content,
+    /// The payload cut to `max_length` characters.
+    Truncated,
+    /// A custom label, such as an asset ID.
+    Label,
+}
+
+/// Side of the code the caption is drawn on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptionPosition {
+    #[default]
+    Below,
+    Above,
+}
+
+/// Human-readable text printed next to a code, outside its quiet zone.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Caption {
+    pub source: CaptionSource,
+    /// Text of `Label` captions; a label given per code, as in a batch, takes precedence.
+    pub label: Option<String>,
+    pub position: CaptionPosition,
+    /// Longest `Truncated` caption, in characters, including the "..." it ends with.
+    pub max_length: usize,
+    /// Space on both sides of the caption, in modules.
+    pub margin: u32,
+    /// Pixels per font dot; the largest size that fits, up to half a module, when `None`.
+    pub scale: Option<u32>,
+    /// Text drawn with the next code, set by [`Caption::resolve`]; never saved.
+    #[serde(skip)]
+    pub text: Option<String>,
+}
+
+impl Default for Caption {
+    fn default() -> Self {
+        Caption {
+            source: CaptionSource::Off,
+            label: None,
+            position: CaptionPosition::Below,
+            max_length: 24,
+            margin: 1,
+            scale: None,
+            text: None,
+        }
+    }
+}
+
+impl Caption {
+    pub fn validate(&self) -> AppResult<()> {
+        if self.max_length < 4 {
+            anyhow::bail!("Caption length must be at least 4 characters, got: {}", self.max_length);
+        }
+        if self.scale == Some(0) {
+            anyhow::bail!("Caption scale must be at least 1 pixel.");
+        }
+        Ok(())
+    }
+
+    /// Chooses the text drawn with a code holding `content`; `label` replaces the configured
+    /// label. Characters the font lacks are drawn as `?`.
+    pub fn resolve(&mut self, content: &[u8], label: Option<&str>) {
+        let content = || String::from_utf8_lossy(content).into_owned();
+        let text = match self.source {
+            CaptionSource::Off => None,
+            CaptionSource::Content => Some(content()),
+            CaptionSource::Truncated => {
+                let content = content();
+                let content = content.lines().next().unwrap_or("");
+                if content.chars().count() > self.max_length {
+                    Some(content.chars().take(self.max_length.saturating_sub(3)).collect::<String>() + "...")
+                } else {
+                    Some(content.to_string())
+                }
+            }
+            CaptionSource::Label => label.map(str::to_string).or_else(|| self.label.clone()),
+        };
+        self.text = text.filter(|text| !text.trim().is_empty());
+    }
+
+    /// This caption for part `index` (from 0) of `count` codes, numbered like "(1/3)".
+    pub fn for_part(&self, index: usize, count: usize) -> Caption {
+        let text = self.text.as_ref().map(|text| format!("{} ({}/{})", text, index + 1, count));
+        Caption { text, ..self.clone() }
+    }
+}
+
+impl fmt::Display for CaptionSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptionSource::Off => write!(f, "off"),
+            CaptionSource::Content => write!(f, "content"),
+            CaptionSource::Truncated => write!(f, "truncated"),
+            CaptionSource::Label => write!(f, "label"),
+        }
+    }
+}
+
+impl FromStr for CaptionSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "off" | "none" => Ok(CaptionSource::Off),
+            "content" => Ok(CaptionSource::Content),
+            "truncated" => Ok(CaptionSource::Truncated),
+            "label" => Ok(CaptionSource::Label),
+            _ => Err(format!("Expected 'off', 'content', 'truncated' or 'label', got: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for CaptionPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptionPosition::Below => write!(f, "below"),
+            CaptionPosition::Above => write!(f, "above"),
+        }
+    }
+}
+
+impl FromStr for CaptionPosition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "below" => Ok(CaptionPosition::Below),
+            "above" => Ok(CaptionPosition::Above),
+            _ => Err(format!("Expected 'below' or 'above', got: {}", s)),
+        }
+    }
+}
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 8;
+// Glyphs are one dot apart, and lines two; the bottom row holds descenders.
+const ADVANCE: u32 = GLYPH_WIDTH + 1;
+const LINE_HEIGHT: u32 = GLYPH_HEIGHT + 2;
+
+// Printable ASCII from ' ' to '~', five columns per glyph, least significant bit at the top.
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], [0x00, 0x00, 0x5f, 0x00, 0x00], [0x00, 0x07, 0x00, 0x07, 0x00],
+    [0x14, 0x7f, 0x14, 0x7f, 0x14], [0x24, 0x2a, 0x7f, 0x2a, 0x12], [0x23, 0x13, 0x08, 0x64, 0x62],
+    [0x36, 0x49, 0x55, 0x22, 0x50], [0x00, 0x05, 0x03, 0x00, 0x00], [0x00, 0x1c, 0x22, 0x41, 0x00],
+    [0x00, 0x41, 0x22, 0x1c, 0x00], [0x14, 0x08, 0x3e, 0x08, 0x14], [0x08, 0x08, 0x3e, 0x08, 0x08],
+    [0x00, 0x50, 0x30, 0x00, 0x00], [0x08, 0x08, 0x08, 0x08, 0x08], [0x00, 0x60, 0x60, 0x00, 0x00],
+    [0x20, 0x10, 0x08, 0x04, 0x02], [0x3e, 0x51, 0x49, 0x45, 0x3e], [0x00, 0x42, 0x7f, 0x40, 0x00],
+    [0x42, 0x61, 0x51, 0x49, 0x46], [0x21, 0x41, 0x45, 0x4b, 0x31], [0x18, 0x14, 0x12, 0x7f, 0x10],
+    [0x27, 0x45, 0x45, 0x45, 0x39], [0x3c, 0x4a, 0x49, 0x49, 0x30], [0x01, 0x71, 0x09, 0x05, 0x03],
+    [0x36, 0x49, 0x49, 0x49, 0x36], [0x06, 0x49, 0x49, 0x29, 0x1e], [0x00, 0x36, 0x36, 0x00, 0x00],
+    [0x00, 0x56, 0x36, 0x00, 0x00], [0x08, 0x14, 0x22, 0x41, 0x00], [0x14, 0x14, 0x14, 0x14, 0x14],
+    [0x00, 0x41, 0x22, 0x14, 0x08], [0x02, 0x01, 0x51, 0x09, 0x06], [0x32, 0x49, 0x79, 0x41, 0x3e],
+    [0x7e, 0x11, 0x11, 0x11, 0x7e], [0x7f, 0x49, 0x49, 0x49, 0x36], [0x3e, 0x41, 0x41, 0x41, 0x22],
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], [0x7f, 0x49, 0x49, 0x49, 0x41], [0x7f, 0x09, 0x09, 0x09, 0x01],
+    [0x3e, 0x41, 0x49, 0x49, 0x7a], [0x7f, 0x08, 0x08, 0x08, 0x7f], [0x00, 0x41, 0x7f, 0x41, 0x00],
+    [0x20, 0x40, 0x41, 0x3f, 0x01], [0x7f, 0x08, 0x14, 0x22, 0x41], [0x7f, 0x40, 0x40, 0x40, 0x40],
+    [0x7f, 0x02, 0x0c, 0x02, 0x7f], [0x7f, 0x04, 0x08, 0x10, 0x7f], [0x3e, 0x41, 0x41, 0x41, 0x3e],
+    [0x7f, 0x09, 0x09, 0x09, 0x06], [0x3e, 0x41, 0x51, 0x21, 0x5e], [0x7f, 0x09, 0x19, 0x29, 0x46],
+    [0x46, 0x49, 0x49, 0x49, 0x31], [0x01, 0x01, 0x7f, 0x01, 0x01], [0x3f, 0x40, 0x40, 0x40, 0x3f],
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], [0x3f, 0x40, 0x38, 0x40, 0x3f], [0x63, 0x14, 0x08, 0x14, 0x63],
+    [0x07, 0x08, 0x70, 0x08, 0x07], [0x61, 0x51, 0x49, 0x45, 0x43], [0x00, 0x7f, 0x41, 0x41, 0x00],
+    [0x02, 0x04, 0x08, 0x10, 0x20], [0x00, 0x41, 0x41, 0x7f, 0x00], [0x04, 0x02, 0x01, 0x02, 0x04],
+    [0x40, 0x40, 0x40, 0x40, 0x40], [0x00, 0x01, 0x02, 0x04, 0x00], [0x20, 0x54, 0x54, 0x54, 0x78],
+    [0x7f, 0x48, 0x44, 0x44, 0x38], [0x38, 0x44, 0x44, 0x44, 0x20], [0x38, 0x44, 0x44, 0x48, 0x7f],
+    [0x38, 0x54, 0x54, 0x54, 0x18], [0x08, 0x7e, 0x09, 0x01, 0x02], [0x18, 0xa4, 0xa4, 0xa4, 0x7c],
+    [0x7f, 0x08, 0x04, 0x04, 0x78], [0x00, 0x44, 0x7d, 0x40, 0x00], [0x40, 0x80, 0x84, 0x7d, 0x00],
+    [0x7f, 0x10, 0x28, 0x44, 0x00], [0x00, 0x41, 0x7f, 0x40, 0x00], [0x7c, 0x04, 0x18, 0x04, 0x78],
+    [0x7c, 0x08, 0x04, 0x04, 0x78], [0x38, 0x44, 0x44, 0x44, 0x38], [0xfc, 0x24, 0x24, 0x24, 0x18],
+    [0x18, 0x24, 0x24, 0x18, 0xfc], [0x7c, 0x08, 0x04, 0x04, 0x08], [0x48, 0x54, 0x54, 0x54, 0x20],
+    [0x04, 0x3f, 0x44, 0x40, 0x20], [0x3c, 0x40, 0x40, 0x20, 0x7c], [0x1c, 0x20, 0x40, 0x20, 0x1c],
+    [0x3c, 0x40, 0x30, 0x40, 0x3c], [0x44, 0x28, 0x10, 0x28, 0x44], [0x9c, 0xa0, 0xa0, 0xa0, 0x7c],
+    [0x44, 0x64, 0x54, 0x4c, 0x44], [0x00, 0x08, 0x36, 0x41, 0x00], [0x00, 0x00, 0x7f, 0x00, 0x00],
+    [0x00, 0x41, 0x36, 0x08, 0x00], [0x08, 0x04, 0x08, 0x10, 0x08],
+];
+
+fn glyph(c: char) -> &'static [u8; 5] {
+    let c = if c == '\t' { ' ' } else { c };
+    match c {
+        ' '..='~' => &FONT[c as usize - ' ' as usize],
+        _ => &FONT['?' as usize - ' ' as usize],
+    }
+}
+
+// Breaks `text` into lines of at most `columns` characters, at spaces where possible.
+fn wrap(text: &str, columns: usize) -> Vec<Vec<char>> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line: Vec<char> = Vec::new();
+        for word in paragraph.split(' ') {
+            let mut word: Vec<char> = word.chars().collect();
+            if !line.is_empty() && line.len() + 1 + word.len() <= columns {
+                line.push(' ');
+                line.append(&mut word);
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            while word.len() > columns {
+                let rest = word.split_off(columns);
+                lines.push(std::mem::replace(&mut word, rest));
+            }
+            line = word;
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Adds the caption in `options.caption.text` to a rendered code, whose modules are `unit`
+/// pixels wide. The image grows by the caption's height; the code is not touched.
+pub(crate) fn draw(code: RgbaImage, unit: u32, options: &EncodeOptions) -> RgbaImage {
+    let caption = &options.caption;
+    let Some(text) = caption.text.as_deref() else {
+        return code;
+    };
+    // Lines stay one module clear of the sides.
+    let room = code.width().saturating_sub(2 * unit).max(ADVANCE);
+    let columns_at = |scale: u32| ((room + scale) / (ADVANCE * scale)).max(1) as usize;
+    let longest = text.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+    let largest = (unit / 2).max(1);
+    let scale = caption.scale.unwrap_or_else(|| {
+        (1..=largest).rev().find(|&scale| columns_at(scale) >= longest).unwrap_or(largest.div_ceil(2))
+    });
+    let lines = wrap(text, columns_at(scale));
+
+    let margin = caption.margin * unit;
+    let text_height = (lines.len() as u32 * LINE_HEIGHT - (LINE_HEIGHT - GLYPH_HEIGHT)) * scale;
+    let widest = lines.iter().map(|line| (line.len() as u32 * ADVANCE).saturating_sub(1)).max().unwrap_or(0) * scale;
+    let width = code.width().max(widest + 2 * unit);
+    let height = code.height() + text_height + 2 * margin;
+    let mut image = RgbaImage::from_pixel(width, height, options.light_color);
+
+    let (code_y, text_y) = match caption.position {
+        CaptionPosition::Below => (0, code.height() + margin),
+        CaptionPosition::Above => (text_height + 2 * margin, margin),
+    };
+    image::imageops::replace(&mut image, &code, ((width - code.width()) / 2) as i64, code_y as i64);
+    for (row, line) in lines.iter().enumerate() {
+        let line_width = (line.len() as u32 * ADVANCE).saturating_sub(1) * scale;
+        let left = (width - line_width) / 2;
+        let top = text_y + row as u32 * LINE_HEIGHT * scale;
+        for (i, &c) in line.iter().enumerate() {
+            for (column, bits) in glyph(c).iter().enumerate() {
+                for dot in (0..GLYPH_HEIGHT).filter(|dot| bits >> dot & 1 == 1) {
+                    let x = left + (i as u32 * ADVANCE + column as u32) * scale;
+                    let y = top + dot * scale;
+                    for dy in 0..scale {
+                        for dx in 0..scale {
+                            image.put_pixel(x + dx, y + dy, options.dark_color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    fn resolved(source: CaptionSource, content: &str, label: Option<&str>) -> Option<String> {
+        let mut caption = Caption { source, label: Some("Configured".to_string()), max_length: 10, ..Caption::default() };
+        caption.resolve(content.as_bytes(), label);
+        caption.text
+    }
+
+    #[test]
+    fn resolves_the_text() {
+        assert_eq!(resolved(CaptionSource::Off, "content", None), None);
+        assert_eq!(resolved(CaptionSource::Content, "line one\nline two", None).as_deref(), Some("line one\nline two"));
+        assert_eq!(resolved(CaptionSource::Truncated, "https://example.com/long", None).as_deref(), Some("https:/..."));
+        assert_eq!(resolved(CaptionSource::Truncated, "short\nsecond line", None).as_deref(), Some("short"));
+        assert_eq!(resolved(CaptionSource::Label, "content", None).as_deref(), Some("Configured"));
+        assert_eq!(resolved(CaptionSource::Label, "content", Some("Row")).as_deref(), Some("Row"));
+        assert_eq!(resolved(CaptionSource::Label, "content", Some("  ")), None);
+    }
+
+    #[test]
+    fn truncates_with_a_length_below_the_minimum() {
+        // Settings loaded from a file may skip validation.
+        let mut caption = Caption { source: CaptionSource::Truncated, max_length: 1, ..Caption::default() };
+        caption.resolve(b"longer than one", None);
+        assert_eq!(caption.text.as_deref(), Some("..."));
+    }
+
+    #[test]
+    fn wraps_at_spaces() {
+        let lines: Vec<String> = wrap("one two three\nabcdefghij", 7).into_iter().map(|line| line.into_iter().collect()).collect();
+        assert_eq!(lines, ["one two", "three", "abcdefg", "hij"]);
+    }
+
+    #[test]
+    fn draws_above_or_below() {
+        let code_color = Rgba([255, 0, 0, 255]);
+        let code = RgbaImage::from_pixel(100, 100, code_color);
+        let draw_at = |position| {
+            let caption = Caption { position, text: Some("AB".to_string()), ..Caption::default() };
+            draw(code.clone(), 10, &EncodeOptions { caption, ..EncodeOptions::default() })
+        };
+        let is_dark = |pixel: &Rgba<u8>| *pixel == EncodeOptions::default().dark_color;
+
+        let below = draw_at(CaptionPosition::Below);
+        assert_eq!(below.width(), 100);
+        assert!(below.height() > 100);
+        assert_eq!(*below.get_pixel(50, 0), code_color);
+        assert!((100..below.height()).any(|y| (0..100).any(|x| is_dark(below.get_pixel(x, y)))));
+
+        let above = draw_at(CaptionPosition::Above);
+        assert_eq!(above.height(), below.height());
+        assert_eq!(*above.get_pixel(50, above.height() - 1), code_color);
+        let caption_height = above.height() - 100;
+        assert!((0..caption_height).any(|y| (0..100).any(|x| is_dark(above.get_pixel(x, y)))));
+
+        let options = EncodeOptions::default();
+        assert_eq!(draw(code.clone(), 10, &options), code);
+    }
+}
@@ -0,0 +1,133 @@
+use encoding_rs::Encoding;
+use zeroize::Zeroizing;
+
+use crate::bitstream::{Run, RunKind};
+
+/// Name reported for payloads that are not text.
+pub(crate) const BINARY: &str = "binary";
+
+/// ECI designator marking a payload as binary data rather than text.
+const ECI_BINARY: u32 = 899;
+
+/// A payload read as text.
+pub(crate) struct Text {
+    pub(crate) content: Zeroizing<String>,
+    /// Character set of the payload, or [`BINARY`].
+    pub(crate) encoding: &'static str,
+    /// First ECI designator of the payload.
+    pub(crate) eci: Option<u32>,
+}
+
+enum Charset {
+    Latin1,
+    Utf8,
+    Other(&'static Encoding),
+}
+
+impl Charset {
+    fn name(&self) -> &'static str {
+        match self {
+            Charset::Latin1 => "ISO-8859-1",
+            Charset::Utf8 => "UTF-8",
+            Charset::Other(encoding) => encoding.name(),
+        }
+    }
+
+    // Returns `None` when the bytes are not valid in this character set.
+    fn decode(&self, bytes: &[u8]) -> Option<String> {
+        match self {
+            Charset::Latin1 => Some(bytes.iter().map(|&b| b as char).collect()),
+            Charset::Utf8 => std::str::from_utf8(bytes).ok().map(str::to_string),
+            Charset::Other(encoding) => encoding.decode_without_bom_handling_and_without_replacement(bytes)
+                .map(|text| text.into_owned()),
+        }
+    }
+}
+
+// Character sets assigned to ECI designators (AIM ECI specification). CP437 has no decoder
+// here and is read as ISO-8859-1, which it shares the ASCII range with.
+fn eci_charset(eci: u32) -> Option<Charset> {
+    let name = match eci {
+        0..=3 | 27 | 170 => return Some(Charset::Latin1),
+        26 => return Some(Charset::Utf8),
+        4 => "ISO-8859-2",
+        5 => "ISO-8859-3",
+        6 => "ISO-8859-4",
+        7 => "ISO-8859-5",
+        8 => "ISO-8859-6",
+        9 => "ISO-8859-7",
+        10 => "ISO-8859-8",
+        11 => "windows-1254",
+        12 => "ISO-8859-10",
+        13 => "windows-874",
+        15 => "ISO-8859-13",
+        16 => "ISO-8859-14",
+        17 => "ISO-8859-15",
+        18 => "ISO-8859-16",
+        20 => "Shift_JIS",
+        21 => "windows-1250",
+        22 => "windows-1251",
+        23 => "windows-1252",
+        24 => "windows-1256",
+        25 => "UTF-16BE",
+        28 => "Big5",
+        29 => "gb18030",
+        30 => "EUC-KR",
+        _ => return None,
+    };
+    Encoding::for_label(name.as_bytes()).map(Charset::Other)
+}
+
+/// Reads `bytes` as text, following the ECI designators and Kanji segments in `runs`.
+///
+/// Byte segments without a designator are read as UTF-8, which nearly every generator
+/// writes, and otherwise as ISO-8859-1, the standard's default. Payloads marked as binary,
+/// not valid in their character set, or holding control characters other than tabs and
+/// line breaks are reported as [`BINARY`].
+pub(crate) fn decode_text(bytes: &[u8], runs: &[Run]) -> Text {
+    let fallback = [Run { kind: RunKind::Bytes(None), end: bytes.len() }];
+    let runs = match runs.last() {
+        Some(run) if run.end == bytes.len() => runs,
+        _ => &fallback,
+    };
+    let eci = runs.iter().find_map(|run| match run.kind {
+        RunKind::Bytes(eci) => eci,
+        _ => None,
+    });
+
+    let mut content = Zeroizing::new(String::with_capacity(bytes.len()));
+    let mut encoding = None;
+    let mut binary = false;
+    let mut start = 0;
+    for run in runs {
+        let chunk = &bytes[start..run.end.max(start)];
+        start = run.end.max(start);
+        let charset = match run.kind {
+            RunKind::Ascii => Charset::Utf8,
+            RunKind::Kanji => Charset::Other(encoding_rs::SHIFT_JIS),
+            RunKind::Bytes(Some(ECI_BINARY)) => {
+                binary = true;
+                continue;
+            }
+            RunKind::Bytes(eci) => match eci.and_then(eci_charset) {
+                Some(charset) => charset,
+                None if std::str::from_utf8(chunk).is_ok() => Charset::Utf8,
+                None => Charset::Latin1,
+            },
+        };
+        match charset.decode(chunk) {
+            Some(text) => content.push_str(&Zeroizing::new(text)),
+            None => binary = true,
+        }
+        let declared = matches!(run.kind, RunKind::Kanji | RunKind::Bytes(Some(_)));
+        if declared || (run.kind != RunKind::Ascii && !chunk.is_ascii()) {
+            encoding.get_or_insert(charset.name());
+        }
+    }
+
+    binary |= content.chars().any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r'));
+    if binary {
+        return Text { content: Zeroizing::new(String::from_utf8_lossy(bytes).into_owned()), encoding: BINARY, eci };
+    }
+    Text { content, encoding: encoding.unwrap_or("UTF-8"), eci }
+}
@@ -0,0 +1,706 @@
+use std::fmt;
+use serde::Serialize;
+use zeroize::Zeroizing;
+
+use crate::otp::parse_otp;
+
+/// What a decoded payload represents.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentKind {
+    Url,
+    Wifi,
+    VCard,
+    MeCard,
+    /// An `otpauth://` key or an authenticator app export.
+    Otp,
+    Geo,
+    Email,
+    Phone,
+    Sms,
+    /// An EMVCo merchant code or a SEPA credit transfer (EPC) code.
+    Payment,
+    Event,
+    #[default]
+    Text,
+    /// A payload that is not text.
+    Binary,
+}
+
+/// One named value taken from a payload, such as the network name of a WiFi code.
+#[derive(Debug, Clone, Serialize)]
+pub struct Field {
+    pub name: &'static str,
+    pub value: Zeroizing<String>,
+}
+
+/// The kind of a payload and the fields read from it, in the order they appear.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Classification {
+    pub kind: ContentKind,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<Field>,
+}
+
+impl fmt::Display for ContentKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ContentKind::Url => "URL",
+            ContentKind::Wifi => "WiFi network",
+            ContentKind::VCard => "Contact (vCard)",
+            ContentKind::MeCard => "Contact (MeCard)",
+            ContentKind::Otp => "One-time password key",
+            ContentKind::Geo => "Location",
+            ContentKind::Email => "Email",
+            ContentKind::Phone => "Phone number",
+            ContentKind::Sms => "SMS",
+            ContentKind::Payment => "Payment",
+            ContentKind::Event => "Calendar event",
+            ContentKind::Text => "Text",
+            ContentKind::Binary => "Binary data",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Collects fields, skipping empty values.
+#[derive(Default)]
+struct Fields(Vec<Field>);
+
+impl Fields {
+    fn push(&mut self, name: &'static str, value: impl Into<String>) {
+        let value = value.into();
+        if !value.trim().is_empty() {
+            self.0.push(Field { name, value: Zeroizing::new(value) });
+        }
+    }
+
+    fn into_classification(self, kind: ContentKind) -> Classification {
+        Classification { kind, fields: self.0 }
+    }
+}
+
+// Leading bytes of common file formats.
+const FILE_SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "PNG image"),
+    (b"\xff\xd8\xff", "JPEG image"),
+    (b"GIF8", "GIF image"),
+    (b"%PDF-", "PDF document"),
+    (b"PK\x03\x04", "ZIP archive"),
+    (b"\x1f\x8b", "gzip data"),
+    (b"\x28\xb5\x2f\xfd", "Zstandard data"),
+    (b"BZh", "bzip2 data"),
+    (b"\xfd7zXZ\x00", "XZ data"),
+    (b"7z\xbc\xaf\x27\x1c", "7-Zip archive"),
+];
+
+/// Describes a payload that is not text by its size and, when recognized, its file format.
+pub fn classify_binary(bytes: &[u8]) -> Classification {
+    let mut fields = Fields::default();
+    fields.push("Size", format!("{} bytes", bytes.len()));
+    if let Some((_, format)) = FILE_SIGNATURES.iter().find(|(magic, _)| bytes.starts_with(magic)) {
+        fields.push("Format", *format);
+    }
+    fields.into_classification(ContentKind::Binary)
+}
+
+/// Works out what `content` is and reads its fields.
+///
+/// Recognition goes by the well-known prefixes and layouts phone scanners use; anything
+/// else is plain text. Secrets, such as one-time password keys and WiFi passwords, are
+/// not copied into the fields.
+pub fn classify(content: &str) -> Classification {
+    let trimmed = content.trim();
+    let upper = trimmed.get(..trimmed.len().min(20)).unwrap_or(trimmed).to_ascii_uppercase();
+    let starts = |prefix: &str| upper.starts_with(prefix);
+
+    let (kind, fields) = if starts("WIFI:") {
+        (ContentKind::Wifi, wifi(&trimmed[5..]))
+    } else if starts("BEGIN:VCARD") {
+        (ContentKind::VCard, vcard(trimmed))
+    } else if starts("MECARD:") {
+        (ContentKind::MeCard, mecard(&trimmed[7..]))
+    } else if starts("BEGIN:VEVENT") || starts("BEGIN:VCALENDAR") {
+        (ContentKind::Event, event(trimmed))
+    } else if starts("OTPAUTH://") || starts("OTPAUTH-MIGRATION://") {
+        (ContentKind::Otp, otpauth(trimmed))
+    } else if starts("GEO:") {
+        (ContentKind::Geo, geo(&trimmed[4..]))
+    } else if starts("MAILTO:") {
+        (ContentKind::Email, mailto(&trimmed[7..]))
+    } else if starts("MATMSG:") {
+        (ContentKind::Email, matmsg(&trimmed[7..]))
+    } else if starts("TEL:") {
+        let mut fields = Fields::default();
+        fields.push("Number", percent_decode(&trimmed[4..]));
+        (ContentKind::Phone, fields)
+    } else if starts("SMSTO:") || starts("SMS:") || starts("MMSTO:") {
+        (ContentKind::Sms, sms(trimmed))
+    } else if trimmed.starts_with("000201") && let Some(fields) = emvco(trimmed) {
+        (ContentKind::Payment, fields)
+    } else if starts("BCD\n") || starts("BCD\r\n") {
+        (ContentKind::Payment, epc(trimmed))
+    } else if let Some(fields) = url(trimmed) {
+        (ContentKind::Url, fields)
+    } else if is_email_address(trimmed) {
+        let mut fields = Fields::default();
+        fields.push("To", trimmed);
+        (ContentKind::Email, fields)
+    } else {
+        (ContentKind::Text, Fields::default())
+    };
+    fields.into_classification(kind)
+}
+
+// Splits on `separator` where it is not escaped with a backslash. The escapes are kept, so
+// each part can be unescaped in the way its format needs.
+fn split_unescaped(value: &str, separator: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut escaped = false;
+    for c in value.chars() {
+        if c == separator && !escaped {
+            parts.push(String::new());
+            continue;
+        }
+        escaped = c == '\\' && !escaped;
+        parts.last_mut().unwrap().push(c);
+    }
+    parts
+}
+
+// Removes backslash escapes, as in WiFi and MeCard payloads.
+fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => text.extend(chars.next()),
+            c => text.push(c),
+        }
+    }
+    text
+}
+
+// `KEY:value` pairs separated by `;`, as in WiFi, MeCard and MATMSG payloads.
+fn key_values(value: &str) -> Vec<(String, String)> {
+    split_unescaped(value, ';').into_iter()
+        .filter_map(|pair| {
+            let (key, value) = pair.split_once(':')?;
+            Some((key.trim().to_ascii_uppercase(), unescape(value)))
+        })
+        .collect()
+}
+
+fn wifi(value: &str) -> Fields {
+    let mut fields = Fields::default();
+    for (key, value) in key_values(value) {
+        match key.as_str() {
+            "S" => fields.push("Network name", value),
+            "T" => fields.push("Security", if value.eq_ignore_ascii_case("nopass") { "None".to_string() } else { value }),
+            "P" => fields.push("Password", "present (hidden)"),
+            "H" => fields.push("Hidden", if value.eq_ignore_ascii_case("true") { "Yes" } else { "No" }),
+            "E" => fields.push("EAP method", value),
+            "I" => fields.push("Identity", value),
+            _ => {}
+        }
+    }
+    fields
+}
+
+fn mecard(value: &str) -> Fields {
+    let mut fields = Fields::default();
+    for (key, value) in key_values(value) {
+        match key.as_str() {
+            // Written as "Last,First".
+            "N" => fields.push("Name", match value.split_once(',') {
+                Some((last, first)) => format!("{} {}", first.trim(), last.trim()).trim().to_string(),
+                None => value,
+            }),
+            "ORG" => fields.push("Organization", value),
+            "TEL" => fields.push("Phone", value),
+            "EMAIL" => fields.push("Email", value),
+            "ADR" => fields.push("Address", value),
+            "URL" => fields.push("Website", value),
+            "BDAY" => fields.push("Birthday", value),
+            "NOTE" => fields.push("Note", value),
+            "NICKNAME" => fields.push("Nickname", value),
+            _ => {}
+        }
+    }
+    fields
+}
+
+fn matmsg(value: &str) -> Fields {
+    let mut fields = Fields::default();
+    for (key, value) in key_values(value) {
+        match key.as_str() {
+            "TO" => fields.push("To", value),
+            "SUB" => fields.push("Subject", value),
+            "BODY" => fields.push("Body", value),
+            _ => {}
+        }
+    }
+    fields
+}
+
+// Lines of a vCard or iCalendar object as (name, value), with folded lines joined and the
+// parameters after `;` in the name dropped.
+fn content_lines(text: &str) -> Vec<(String, String)> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines.into_iter()
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            let name = name.split(';').next().unwrap_or_default();
+            // Grouped properties such as "item1.TEL" keep only the property name.
+            let name = name.rsplit('.').next().unwrap_or_default();
+            Some((name.trim().to_ascii_uppercase(), value.to_string()))
+        })
+        .collect()
+}
+
+// Text values of vCard and iCalendar, where `\n` is a line break.
+fn unescape_text(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (c, c == '\\') {
+            (_, true) => match chars.next() {
+                Some('n' | 'N') => text.push('\n'),
+                Some(next) => text.push(next),
+                None => {}
+            },
+            (c, false) => text.push(c),
+        }
+    }
+    text
+}
+
+fn vcard(text: &str) -> Fields {
+    let mut fields = Fields::default();
+    let lines = content_lines(text);
+    let has_full_name = lines.iter().any(|(name, value)| name == "FN" && !value.trim().is_empty());
+    for (name, value) in lines {
+        match name.as_str() {
+            "FN" => fields.push("Name", unescape_text(&value)),
+            "N" if !has_full_name => {
+                let parts: Vec<String> = split_unescaped(&value, ';').iter().map(|part| unescape_text(part)).collect();
+                let name: Vec<&str> = [parts.get(3), parts.get(1), parts.get(2), parts.first(), parts.get(4)].into_iter()
+                    .flatten()
+                    .map(|part| part.trim())
+                    .filter(|part| !part.is_empty())
+                    .collect();
+                fields.push("Name", name.join(" "));
+            }
+            "ORG" => {
+                let units: Vec<String> = split_unescaped(&value, ';').iter()
+                    .map(|unit| unescape_text(unit))
+                    .filter(|unit| !unit.trim().is_empty())
+                    .collect();
+                fields.push("Organization", units.join(", "));
+            }
+            "TITLE" => fields.push("Title", unescape_text(&value)),
+            "TEL" => fields.push("Phone", unescape_text(&value)),
+            "EMAIL" => fields.push("Email", unescape_text(&value)),
+            "ADR" => {
+                let parts: Vec<String> = split_unescaped(&value, ';').iter()
+                    .map(|part| unescape_text(part).replace('\n', ", "))
+                    .filter(|part| !part.trim().is_empty())
+                    .collect();
+                fields.push("Address", parts.join(", "));
+            }
+            "URL" => fields.push("Website", value),
+            "BDAY" => fields.push("Birthday", value),
+            "NOTE" => fields.push("Note", unescape_text(&value)),
+            _ => {}
+        }
+    }
+    fields
+}
+
+fn event(text: &str) -> Fields {
+    let mut fields = Fields::default();
+    for (name, value) in content_lines(text) {
+        match name.as_str() {
+            "SUMMARY" => fields.push("Summary", unescape_text(&value)),
+            "DTSTART" => fields.push("Start", format_ical_time(&value)),
+            "DTEND" => fields.push("End", format_ical_time(&value)),
+            "LOCATION" => fields.push("Location", unescape_text(&value)),
+            "DESCRIPTION" => fields.push("Description", unescape_text(&value)),
+            _ => {}
+        }
+    }
+    fields
+}
+
+// "20261016T143000Z" reads as "2026-10-16 14:30:00 UTC"; anything else is kept as it is.
+fn format_ical_time(value: &str) -> String {
+    let (value, utc) = match value.strip_suffix('Z') {
+        Some(value) => (value, " UTC"),
+        None => (value, ""),
+    };
+    let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    match value.split_once('T') {
+        None if value.len() == 8 && digits(value) => format!("{}-{}-{}", &value[..4], &value[4..6], &value[6..]),
+        Some((date, time)) if date.len() == 8 && time.len() == 6 && digits(date) && digits(time) => format!(
+            "{}-{}-{} {}:{}:{}{}",
+            &date[..4], &date[4..6], &date[6..], &time[..2], &time[2..4], &time[4..], utc
+        ),
+        _ => format!("{}{}", value, if utc.is_empty() { "" } else { "Z" }),
+    }
+}
+
+// `name=value` pairs of a URI query, percent-decoded.
+pub(crate) fn query_pairs(query: &str) -> Vec<(String, String)> {
+    query.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(name).to_ascii_lowercase(), percent_decode(&value.replace('+', " ")))
+        })
+        .collect()
+}
+
+fn otpauth(uri: &str) -> Fields {
+    let mut fields = Fields::default();
+    let (scheme, rest) = uri.split_once("://").unwrap_or((uri, ""));
+    if scheme.eq_ignore_ascii_case("otpauth-migration") {
+        fields.push("Format", "Authenticator app export");
+        match parse_otp(uri) {
+            Ok(import) => {
+                if let Some(batch) = import.batch {
+                    fields.push("Batch", format!("{} of {}", batch.index + 1, batch.size));
+                }
+                for account in &import.accounts {
+                    fields.push("Account", format!("{} [{}]", account.name(), account.kind));
+                }
+            }
+            Err(e) => fields.push("Error", e.to_string()),
+        }
+        return fields;
+    }
+    let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let (kind, label) = path.split_once('/').unwrap_or((path, ""));
+    fields.push("OTP type", kind.to_ascii_uppercase());
+    let label = percent_decode(label);
+    let (label_issuer, account) = match label.split_once(':') {
+        Some((issuer, account)) => (Some(issuer.trim().to_string()), account.trim().to_string()),
+        None => (None, label.clone()),
+    };
+    let pairs = query_pairs(query);
+    let value = |name: &str| pairs.iter().find(|(n, _)| n == name).map(|(_, v)| v.clone());
+    fields.push("Issuer", value("issuer").or(label_issuer).unwrap_or_default());
+    fields.push("Account", account);
+    if value("secret").is_some() {
+        fields.push("Secret", "present (hidden)");
+    }
+    fields.push("Algorithm", value("algorithm").unwrap_or_default());
+    fields.push("Digits", value("digits").unwrap_or_default());
+    fields.push("Period", value("period").map(|p| format!("{} s", p)).unwrap_or_default());
+    fields.push("Counter", value("counter").unwrap_or_default());
+    fields
+}
+
+fn geo(value: &str) -> Fields {
+    let mut fields = Fields::default();
+    let (coordinates, query) = value.split_once('?').unwrap_or((value, ""));
+    // Parameters such as ";u=35" follow the coordinates.
+    let coordinates = coordinates.split(';').next().unwrap_or_default();
+    let mut parts = coordinates.split(',').map(str::trim);
+    fields.push("Latitude", parts.next().unwrap_or_default());
+    fields.push("Longitude", parts.next().unwrap_or_default());
+    fields.push("Altitude", parts.next().map(|a| format!("{} m", a)).unwrap_or_default());
+    for (name, value) in query_pairs(query) {
+        if name == "q" {
+            fields.push("Place", value);
+        }
+    }
+    fields
+}
+
+fn mailto(value: &str) -> Fields {
+    let mut fields = Fields::default();
+    let (to, query) = value.split_once('?').unwrap_or((value, ""));
+    fields.push("To", percent_decode(to));
+    for (name, value) in query_pairs(query) {
+        match name.as_str() {
+            "to" => fields.push("To", value),
+            "cc" => fields.push("Cc", value),
+            "bcc" => fields.push("Bcc", value),
+            "subject" => fields.push("Subject", value),
+            "body" => fields.push("Body", value),
+            _ => {}
+        }
+    }
+    fields
+}
+
+// SMSTO:number:message, MMSTO:number:message, or sms:number?body=message.
+fn sms(value: &str) -> Fields {
+    let mut fields = Fields::default();
+    let (scheme, rest) = value.split_once(':').unwrap_or((value, ""));
+    if scheme.eq_ignore_ascii_case("sms") {
+        let (number, query) = rest.split_once('?').unwrap_or((rest, ""));
+        fields.push("Number", percent_decode(number));
+        for (name, value) in query_pairs(query) {
+            if name == "body" {
+                fields.push("Message", value);
+            }
+        }
+    } else {
+        let (number, message) = rest.split_once(':').unwrap_or((rest, ""));
+        fields.push("Number", number);
+        fields.push("Message", message);
+    }
+    fields
+}
+
+fn url(value: &str) -> Option<Fields> {
+    if value.chars().any(char::is_whitespace) {
+        return None;
+    }
+    let lower = value.to_ascii_lowercase();
+    let rest = if lower.starts_with("http://") || lower.starts_with("https://") || lower.starts_with("ftp://") {
+        value.split_once("://")?.1
+    } else if lower.starts_with("www.") {
+        value
+    } else {
+        return None;
+    };
+
+    let mut fields = Fields::default();
+    let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (authority, remainder) = rest.split_at(end);
+    let host = authority.rsplit('@').next().unwrap_or(authority);
+    fields.push("Host", host);
+    let (remainder, fragment) = remainder.split_once('#').unwrap_or((remainder, ""));
+    let (path, query) = remainder.split_once('?').unwrap_or((remainder, ""));
+    if path != "/" {
+        fields.push("Path", percent_decode(path));
+    }
+    fields.push("Query", query);
+    fields.push("Fragment", fragment);
+    Some(fields)
+}
+
+fn is_email_address(value: &str) -> bool {
+    let Some((local, domain)) = value.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !domain.contains('@')
+        && !value.chars().any(char::is_whitespace)
+}
+
+// EMVCo merchant-presented codes: two-digit ID, two-digit length, value.
+fn emv_tlv(data: &str) -> Option<Vec<(&str, &str)>> {
+    let mut items = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let id = rest.get(..2)?;
+        let len: usize = rest.get(2..4)?.parse().ok()?;
+        let value = rest.get(4..4 + len)?;
+        items.push((id, value));
+        rest = &rest[4 + len..];
+    }
+    Some(items)
+}
+
+fn emvco(data: &str) -> Option<Fields> {
+    let items = emv_tlv(data)?;
+    let mut fields = Fields::default();
+    for (index, &(id, value)) in items.iter().enumerate() {
+        match id {
+            "01" => fields.push("Initiation", match value {
+                "11" => "Static",
+                "12" => "Dynamic",
+                other => other,
+            }),
+            // Merchant account templates carry the payment network in sub-field 00.
+            id if ("26"..="51").contains(&id) => {
+                let network = emv_tlv(value).and_then(|sub| sub.iter().find(|(id, _)| *id == "00").map(|(_, v)| v.to_string()));
+                fields.push("Account", network.unwrap_or_else(|| value.to_string()));
+            }
+            "52" => fields.push("Merchant category", value),
+            "53" => fields.push("Currency", currency_name(value).map_or_else(|| value.to_string(), str::to_string)),
+            "54" => fields.push("Amount", value),
+            "58" => fields.push("Country", value),
+            "59" => fields.push("Merchant", value),
+            "60" => fields.push("City", value),
+            "61" => fields.push("Postal code", value),
+            "62" => {
+                for (sub_id, sub_value) in emv_tlv(value).unwrap_or_default() {
+                    match sub_id {
+                        "01" => fields.push("Bill number", sub_value),
+                        "05" => fields.push("Reference", sub_value),
+                        "08" => fields.push("Purpose", sub_value),
+                        _ => {}
+                    }
+                }
+            }
+            // The CRC covers everything up to its own value, so it only holds as the last field.
+            "63" => {
+                let valid = index + 1 == items.len() && {
+                    let checked = &data[..data.len() - value.len()];
+                    format!("{:04X}", crc16_ccitt(checked.as_bytes())).eq_ignore_ascii_case(value)
+                };
+                fields.push("Checksum", if valid { "Valid" } else { "Invalid" });
+            }
+            _ => {}
+        }
+    }
+    Some(fields)
+}
+
+// CRC-16/CCITT-FALSE, which EMVCo codes end with.
+fn crc16_ccitt(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+fn currency_name(code: &str) -> Option<&'static str> {
+    let name = match code {
+        "036" => "AUD",
+        "124" => "CAD",
+        "156" => "CNY",
+        "344" => "HKD",
+        "356" => "INR",
+        "360" => "IDR",
+        "392" => "JPY",
+        "410" => "KRW",
+        "458" => "MYR",
+        "484" => "MXN",
+        "608" => "PHP",
+        "702" => "SGD",
+        "704" => "VND",
+        "764" => "THB",
+        "826" => "GBP",
+        "840" => "USD",
+        "978" => "EUR",
+        "986" => "BRL",
+        _ => return None,
+    };
+    Some(name)
+}
+
+// European Payments Council credit transfer: one field per line after the header.
+fn epc(text: &str) -> Fields {
+    let lines: Vec<&str> = text.lines().map(str::trim).collect();
+    let line = |index: usize| lines.get(index).copied().unwrap_or_default();
+    let mut fields = Fields::default();
+    fields.push("Format", "SEPA credit transfer");
+    fields.push("Beneficiary", line(5));
+    fields.push("IBAN", line(6));
+    fields.push("BIC", line(4));
+    let amount = line(7);
+    match amount.get(..3) {
+        Some(currency) if currency.chars().all(|c| c.is_ascii_alphabetic()) => {
+            fields.push("Amount", &amount[3..]);
+            fields.push("Currency", currency);
+        }
+        _ => fields.push("Amount", amount),
+    }
+    fields.push("Purpose", line(8));
+    fields.push("Reference", line(9));
+    fields.push("Remittance", line(10));
+    fields
+}
+
+pub(crate) fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = value.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            decoded.push(byte);
+            i += 3;
+            continue;
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field<'a>(classification: &'a Classification, name: &str) -> Option<&'a str> {
+        classification.fields.iter().find(|field| field.name == name).map(|field| field.value.as_str())
+    }
+
+    #[test]
+    fn crc16_matches_check_value() {
+        assert_eq!(crc16_ccitt(b"123456789"), 0x29B1);
+    }
+
+    #[test]
+    fn emvco_with_valid_crc() {
+        let code = classify("00020101021153038405406100.005802US5909Acme Shop6008New York6304E51C");
+        assert_eq!(code.kind, ContentKind::Payment);
+        assert_eq!(field(&code, "Initiation"), Some("Static"));
+        assert_eq!(field(&code, "Currency"), Some("USD"));
+        assert_eq!(field(&code, "Amount"), Some("100.00"));
+        assert_eq!(field(&code, "Merchant"), Some("Acme Shop"));
+        assert_eq!(field(&code, "Checksum"), Some("Valid"));
+    }
+
+    #[test]
+    fn emvco_with_bad_crc() {
+        let code = classify("00020101021153038405406100.005802US5909Acme Shop6008New York6304E51D");
+        assert_eq!(code.kind, ContentKind::Payment);
+        assert_eq!(field(&code, "Checksum"), Some("Invalid"));
+    }
+
+    #[test]
+    fn emvco_crc_must_be_last() {
+        let code = classify("0002016304E51C5909Acme Shop");
+        assert_eq!(field(&code, "Merchant"), Some("Acme Shop"));
+        assert_eq!(field(&code, "Checksum"), Some("Invalid"));
+    }
+
+    #[test]
+    fn emvco_with_multibyte_values() {
+        let code = classify("0002016304ABCD5906a€xx");
+        assert_eq!(field(&code, "Merchant"), Some("a€xx"));
+        assert_eq!(field(&code, "Checksum"), Some("Invalid"));
+
+        let code = classify("0002015906a€xx63040B38");
+        assert_eq!(field(&code, "Merchant"), Some("a€xx"));
+        assert_eq!(field(&code, "Checksum"), Some("Valid"));
+    }
+
+    #[test]
+    fn malformed_emvco_is_text() {
+        assert_eq!(classify("000201599").kind, ContentKind::Text);
+        assert_eq!(classify("0002015906a€").kind, ContentKind::Text);
+    }
+
+    #[test]
+    fn classifies_common_prefixes() {
+        let wifi = classify(r"WIFI:T:WPA;S:Home\;Net;P:secret;;");
+        assert_eq!(wifi.kind, ContentKind::Wifi);
+        assert_eq!(field(&wifi, "Network name"), Some("Home;Net"));
+        assert_eq!(field(&wifi, "Password"), Some("present (hidden)"));
+        assert!(wifi.fields.iter().all(|field| !field.value.contains("secret")));
+        assert_eq!(classify("https://example.com/path").kind, ContentKind::Url);
+        assert_eq!(classify("someone@example.com").kind, ContentKind::Email);
+        assert_eq!(classify("just some words").kind, ContentKind::Text);
+    }
+}
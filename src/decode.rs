@@ -0,0 +1,468 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use anyhow::Context;
+use image::{DynamicImage, Frames, GrayImage};
+use serde::{Serialize, Deserialize};
+use zeroize::Zeroizing;
+
+use crate::AppResult;
+use crate::bitstream::{self, Payload};
+use crate::charset::{BINARY, Text, decode_text};
+use crate::content::{Classification, classify, classify_binary};
+use crate::input::Pages;
+use crate::preprocess::Pipeline;
+use crate::safety::{LinkWarning, check_link};
+use crate::structured_append::StructuredAppend;
+
+mod finder;
+
+/// A decoded QR symbol together with the metadata rqrr reported for it.
+#[derive(Debug, Clone, Serialize)]
+pub struct DecodedQr {
+    /// Path of the source image; empty for in-memory images.
+    pub source: String,
+    pub content: Zeroizing<String>,
+    /// What the content represents, with the fields read from it.
+    pub classification: Classification,
+    /// Reasons the content may be an unsafe link; empty when nothing was flagged.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub link_warnings: Vec<LinkWarning>,
+    /// Character set `content` was read in, such as "UTF-8" or "Shift_JIS", or "binary"
+    /// when the payload is not text and `content` is only a lossy UTF-8 reading of it.
+    pub encoding: &'static str,
+    /// ECI designator that declared the character set, if the symbol has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eci: Option<u32>,
+    /// Decoded payload bytes, exactly as stored in the symbol.
+    #[serde(skip)]
+    pub bytes: Zeroizing<Vec<u8>>,
+    pub version: usize,
+    pub ecc_level: &'static str,
+    pub mask: u16,
+    /// Corners in original image coordinates: top-left, top-right, bottom-right, bottom-left.
+    pub bounds: [[i32; 2]; 4],
+    /// Name of the preprocessing variant that decoded the symbol.
+    pub technique: String,
+    /// Resize factor of that variant relative to the original image.
+    pub scale: f32,
+    /// Index of the animation frame the symbol was found in; `None` for still images.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frame: Option<usize>,
+    /// Page number, from 1, for symbols found in a multi-page document.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<usize>,
+    /// Set when the symbol is one part of a Structured Append sequence.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub structured_append: Option<StructuredAppend>,
+}
+
+impl DecodedQr {
+    /// Whether the payload is binary data rather than text.
+    pub fn is_binary(&self) -> bool {
+        self.encoding == BINARY
+    }
+}
+
+fn ecc_level_name(ecc_level: u16) -> &'static str {
+    // rqrr reports the raw format-information bits, which are not in L/M/Q/H order.
+    match ecc_level {
+        0 => "M",
+        1 => "L",
+        2 => "H",
+        3 => "Q",
+        _ => "?",
+    }
+}
+
+/// When to stop trying further preprocessing variants.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecodeMode {
+    /// Stop after the first variant that decodes at least one code.
+    FirstHit,
+    /// Stop once this many distinct codes have been found.
+    Expected(usize),
+    /// Try every variant, so codes only later variants find are not missed.
+    #[default]
+    Exhaustive,
+}
+
+impl DecodeMode {
+    fn is_satisfied(self, found: usize) -> bool {
+        match self {
+            DecodeMode::FirstHit => found > 0,
+            DecodeMode::Expected(count) => found >= count,
+            DecodeMode::Exhaustive => false,
+        }
+    }
+}
+
+impl fmt::Display for DecodeMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeMode::FirstHit => write!(f, "first-hit"),
+            DecodeMode::Expected(count) => write!(f, "{}", count),
+            DecodeMode::Exhaustive => write!(f, "exhaustive"),
+        }
+    }
+}
+
+impl FromStr for DecodeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace('_', "-").as_str() {
+            "first-hit" | "first" => Ok(DecodeMode::FirstHit),
+            "exhaustive" | "all" => Ok(DecodeMode::Exhaustive),
+            other => match other.parse::<usize>() {
+                Ok(count) if count > 0 => Ok(DecodeMode::Expected(count)),
+                _ => Err(format!("Expected 'first-hit', 'exhaustive' or a positive number of codes, got: {}", s)),
+            },
+        }
+    }
+}
+
+/// Controls how hard [`decode_image`] works to find codes.
+#[derive(Debug, Clone)]
+pub struct DecodeOptions {
+    /// Preprocessing variants to try, in order.
+    pub pipeline: Pipeline,
+    pub mode: DecodeMode,
+    /// Decode up to this many variants of one image concurrently; 1 keeps it sequential.
+    /// [`decode_paths`](crate::batch::decode_paths) lowers it to share threads with its file workers.
+    pub variant_threads: usize,
+    /// Only decode every n-th frame of an animation; 1 decodes all of them.
+    pub frame_stride: usize,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        DecodeOptions {
+            pipeline: Pipeline::default(),
+            mode: DecodeMode::default(),
+            variant_threads: 1,
+            frame_stride: 1,
+        }
+    }
+}
+
+/// Decoded codes plus how many preprocessing variants were tried to find them.
+#[derive(Debug, Clone)]
+pub struct DecodeReport {
+    pub results: Vec<DecodedQr>,
+    pub variants_tried: usize,
+    /// Problems that did not stop decoding, such as document pages that could not be read.
+    pub warnings: Vec<String>,
+}
+
+/// Decodes every QR code found in `img`, deduplicated by content.
+///
+/// `source` is left empty on the results; [`decode_path`] fills it in.
+pub fn decode_image(img: &DynamicImage, options: &DecodeOptions) -> Vec<DecodedQr> {
+    decode_image_report(img, options).results
+}
+
+/// Like [`decode_image`], but also reports how many variants were tried.
+pub fn decode_image_report(img: &DynamicImage, options: &DecodeOptions) -> DecodeReport {
+    if options.variant_threads > 1 {
+        return decode_variants_parallel(img, options);
+    }
+
+    let mut all_results: Vec<DecodedQr> = Vec::new();
+    let mut variants_tried = 0;
+
+    for (technique, processed_img) in options.pipeline.variants(img) {
+        variants_tried += 1;
+        merge_results(&mut all_results, decode_variant(img, technique, processed_img));
+
+        if options.mode.is_satisfied(all_results.len()) {
+            break;
+        }
+    }
+
+    DecodeReport { results: all_results, variants_tried, warnings: Vec::new() }
+}
+
+fn decode_variant(img: &DynamicImage, technique: &str, processed_img: GrayImage) -> Vec<DecodedQr> {
+    // Variants may be resized, so corners are mapped back to the original image.
+    let scale_x = processed_img.width() as f32 / img.width().max(1) as f32;
+    let scale_y = processed_img.height() as f32 / img.height().max(1) as f32;
+    let mut prepared_img = rqrr::PreparedImage::prepare(processed_img.clone());
+    let mut read = Vec::new();
+
+    for grid in prepared_img.detect_grids() {
+        // The payload is decoded here rather than by rqrr, which drops ECI designators and
+        // rejects Structured Append symbols; rqrr's decoder is only a fallback.
+        match bitstream::decode_grid(&grid.grid) {
+            Ok((metadata, payload)) => read.push((metadata, payload, grid.bounds)),
+            Err(_) => {
+                let mut bytes = Zeroizing::new(Vec::new());
+                if let Ok(metadata) = grid.decode_to(&mut *bytes) {
+                    read.push((metadata, Payload { structured_append: None, bytes, runs: Vec::new() }, grid.bounds));
+                }
+            }
+        }
+    }
+    if read.is_empty() {
+        read = finder::decode(&processed_img);
+    }
+
+    let mut found = Vec::new();
+    for (metadata, Payload { structured_append, bytes, runs }, bounds) in read {
+        let Text { content, encoding, eci } = decode_text(&bytes, &runs);
+        let (classification, link_warnings) = if encoding == BINARY {
+            (classify_binary(&bytes), Vec::new())
+        } else {
+            (classify(&content), check_link(&content))
+        };
+        found.push(DecodedQr {
+            source: String::new(),
+            classification,
+            link_warnings,
+            content,
+            encoding,
+            eci,
+            bytes,
+            version: metadata.version.0,
+            ecc_level: ecc_level_name(metadata.ecc_level),
+            mask: metadata.mask,
+            bounds: bounds.map(|p| [
+                (p.x as f32 / scale_x).round() as i32,
+                (p.y as f32 / scale_y).round() as i32,
+            ]),
+            technique: technique.to_string(),
+            scale: (scale_x * 100.0).round() / 100.0,
+            frame: None,
+            page: None,
+            structured_append,
+        });
+    }
+
+    found
+}
+
+fn merge_results(all_results: &mut Vec<DecodedQr>, found: Vec<DecodedQr>) {
+    for result in found {
+        // Structured Append parts may repeat content, so their header is part of the key.
+        if !all_results.iter().any(|r| r.content == result.content && r.structured_append == result.structured_append) {
+            all_results.push(result);
+        }
+    }
+}
+
+struct VariantMerge {
+    slots: Vec<Option<Vec<DecodedQr>>>,
+    merged_upto: usize,
+    results: Vec<DecodedQr>,
+    done: bool,
+}
+
+// Variants are decoded concurrently but merged strictly in pipeline order, so the
+// results and the early-exit point match the sequential path exactly.
+fn decode_variants_parallel(img: &DynamicImage, options: &DecodeOptions) -> DecodeReport {
+    let steps: Vec<_> = options.pipeline.steps.iter()
+        .filter(|step| step.applies_to(img.width(), img.height()))
+        .collect();
+    let next = AtomicUsize::new(0);
+    let state = Mutex::new(VariantMerge {
+        slots: vec![None; steps.len()],
+        merged_upto: 0,
+        results: Vec::new(),
+        done: steps.is_empty(),
+    });
+
+    thread::scope(|scope| {
+        for _ in 0..options.variant_threads.min(steps.len()) {
+            scope.spawn(|| loop {
+                if state.lock().unwrap_or_else(|e| e.into_inner()).done {
+                    break;
+                }
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(step) = steps.get(index) else {
+                    break;
+                };
+                let found = decode_variant(img, &step.name, step.apply(img));
+
+                let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+                state.slots[index] = Some(found);
+                while !state.done && state.merged_upto < state.slots.len() {
+                    let upto = state.merged_upto;
+                    let Some(found) = state.slots[upto].take() else {
+                        break;
+                    };
+                    merge_results(&mut state.results, found);
+                    state.merged_upto += 1;
+                    if options.mode.is_satisfied(state.results.len()) || state.merged_upto == state.slots.len() {
+                        state.done = true;
+                    }
+                }
+            });
+        }
+    });
+
+    let state = state.into_inner().unwrap_or_else(|e| e.into_inner());
+    DecodeReport { results: state.results, variants_tried: state.merged_upto, warnings: Vec::new() }
+}
+
+/// Decodes every `frame_stride`-th frame of an animation with [`decode_image`].
+///
+/// Results are deduplicated across frames and keep the index of the first frame they
+/// appeared in. In [`DecodeMode::Expected`] mode, decoding stops once enough codes are
+/// found; the other modes apply to each frame on its own, so every frame is visited.
+pub fn decode_frames(frames: Frames<'_>, options: &DecodeOptions) -> AppResult<DecodeReport> {
+    let stride = options.frame_stride.max(1);
+    let mut report = DecodeReport { results: Vec::new(), variants_tried: 0, warnings: Vec::new() };
+    let mut frames = frames.enumerate().peekable();
+
+    while let Some((index, frame)) = frames.next() {
+        if index % stride != 0 {
+            continue;
+        }
+        let frame = frame.context(format!("Could not decode animation frame {}", index))?;
+        // A single-frame GIF is a still image as far as callers are concerned.
+        let animated = index > 0 || frames.peek().is_some();
+        let img = DynamicImage::ImageRgba8(frame.into_buffer());
+
+        let done = decode_into(&mut report, &img, options, |result| {
+            DecodedQr { frame: animated.then_some(index), ..result }
+        });
+        if done {
+            break;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Decodes every page of a document with [`decode_image`], like [`decode_frames`].
+///
+/// Pages that cannot be read are listed in the report's warnings and skipped.
+pub fn decode_pages(pages: Pages, options: &DecodeOptions) -> AppResult<DecodeReport> {
+    let mut report = DecodeReport { results: Vec::new(), variants_tried: 0, warnings: Vec::new() };
+
+    for page in pages {
+        let (number, img) = match page {
+            Ok(page) => page,
+            Err(e) => {
+                report.warnings.push(format!("{:#}", e));
+                continue;
+            }
+        };
+        if decode_into(&mut report, &img, options, |result| DecodedQr { page: Some(number), ..result }) {
+            break;
+        }
+    }
+
+    Ok(report)
+}
+
+// Adds one image of a multi-image file to `report`; returns true once no further images
+// need to be decoded.
+fn decode_into(
+    report: &mut DecodeReport,
+    img: &DynamicImage,
+    options: &DecodeOptions,
+    tag: impl Fn(DecodedQr) -> DecodedQr,
+) -> bool {
+    let image_report = decode_image_report(img, options);
+    report.variants_tried += image_report.variants_tried;
+    merge_results(&mut report.results, image_report.results.into_iter().map(tag).collect());
+
+    matches!(options.mode, DecodeMode::Expected(count) if report.results.len() >= count)
+}
+
+/// Opens the image at `path` and decodes it with [`decode_image`], with
+/// [`decode_frames`] for animated GIF and WebP files, or with [`decode_pages`] for PDFs
+/// and multi-page TIFFs.
+pub fn decode_path(path: &Path, options: &DecodeOptions) -> AppResult<Vec<DecodedQr>> {
+    Ok(decode_path_report(path, options)?.results)
+}
+
+/// Like [`decode_path`], but also reports how many variants were tried.
+pub fn decode_path_report(path: &Path, options: &DecodeOptions) -> AppResult<DecodeReport> {
+    let mut report = match crate::input::open_animation(path)? {
+        Some(frames) => decode_frames(frames, options)?,
+        None => match crate::input::open_pages(path)? {
+            Some(pages) => decode_pages(pages, options)?,
+            None => decode_image_report(&crate::input::open_image(path)?, options),
+        },
+    };
+    for result in &mut report.results {
+        result.source = path.display().to_string();
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::{EncodeOptions, encode, render_image};
+
+    fn round_trip(text: &str) -> DecodedQr {
+        let options = EncodeOptions::default();
+        let image = DynamicImage::ImageRgba8(render_image(&encode(text, &options).unwrap(), &options).unwrap());
+        let mut results = decode_image(&image, &DecodeOptions::default());
+        assert_eq!(results.len(), 1);
+        results.remove(0)
+    }
+
+    #[test]
+    fn tries_every_variant_by_default() {
+        assert_eq!(DecodeMode::default(), DecodeMode::Exhaustive);
+        assert_eq!("first-hit".parse(), Ok(DecodeMode::FirstHit));
+        assert_eq!("3".parse(), Ok(DecodeMode::Expected(3)));
+        assert!("0".parse::<DecodeMode>().is_err());
+    }
+
+    #[test]
+    fn decodes_text_with_metadata() {
+        let result = round_trip("HELLO 123");
+        assert_eq!(result.content.as_str(), "HELLO 123");
+        assert_eq!(result.bytes.as_slice(), b"HELLO 123");
+        assert_eq!(result.ecc_level, "M");
+        assert_eq!(result.version, 1);
+        assert_eq!(result.structured_append, None);
+    }
+
+    #[test]
+    fn decodes_utf8_bytes() {
+        let result = round_trip("Grüße, 東京");
+        assert_eq!(result.content.as_str(), "Grüße, 東京");
+        assert_eq!(result.encoding, "UTF-8");
+    }
+
+    #[cfg(feature = "gif")]
+    #[test]
+    fn reports_animation_frames_and_skips_by_stride() {
+        use image::codecs::gif::GifEncoder;
+        use image::{Frame, Rgba, RgbaImage};
+
+        let options = EncodeOptions::default();
+        let code = |text| render_image(&encode(text, &options).unwrap(), &options).unwrap();
+        let first = code("frame two");
+        let blank = RgbaImage::from_pixel(first.width(), first.height(), Rgba([255, 255, 255, 255]));
+        let images = [blank.clone(), blank, first, code("frame three")];
+
+        let path = std::env::temp_dir().join(format!("{}-frames-{}.gif", env!("CARGO_PKG_NAME"), std::process::id()));
+        let mut encoder = GifEncoder::new_with_speed(std::fs::File::create(&path).unwrap(), 10);
+        encoder.encode_frames(images.into_iter().map(Frame::new)).unwrap();
+        drop(encoder);
+
+        let found = |frame_stride| {
+            let report = decode_path_report(&path, &DecodeOptions { frame_stride, ..DecodeOptions::default() }).unwrap();
+            report.results.iter().map(|r| (r.content.to_string(), r.frame)).collect::<Vec<_>>()
+        };
+        let all = found(1);
+        let even = found(2);
+        let every_third = found(3);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(all, [("frame two".to_string(), Some(2)), ("frame three".to_string(), Some(3))]);
+        assert_eq!(even, [("frame two".to_string(), Some(2))]);
+        assert_eq!(every_third, [("frame three".to_string(), Some(3))]);
+    }
+}
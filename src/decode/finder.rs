@@ -0,0 +1,354 @@
+use image::GrayImage;
+use rqrr::{BitGrid, MetaData, Point};
+
+use crate::bitstream::{self, Payload};
+
+// Finder candidates beyond this many are ignored, since every triple of them is tried.
+const MAX_FINDERS: usize = 24;
+
+// Maps module coordinates of a `size`-module grid to image coordinates.
+#[derive(Debug, Clone, Copy)]
+struct Perspective {
+    c: [f64; 8],
+    size: f64,
+}
+
+impl Perspective {
+    // The mapping that takes the grid corners (0, 0), (size, 0), (size, size) and
+    // (0, size) to `corners`; `None` when the corners do not form a quadrilateral.
+    fn new(corners: [(f64, f64); 4], size: f64) -> Option<Self> {
+        let [(x0, y0), (x1, y1), (x2, y2), (x3, y3)] = corners;
+        let (sx, sy) = (x0 - x1 + x2 - x3, y0 - y1 + y2 - y3);
+        let (dx1, dx2, dy1, dy2) = (x1 - x2, x3 - x2, y1 - y2, y3 - y2);
+        let den = dx1 * dy2 - dx2 * dy1;
+        if den.abs() < f64::EPSILON {
+            return None;
+        }
+        let g = (sx * dy2 - dx2 * sy) / den;
+        let h = (dx1 * sy - sx * dy1) / den;
+        let c = [x1 - x0 + g * x1, x3 - x0 + h * x3, x0, y1 - y0 + g * y1, y3 - y0 + h * y3, y0, g, h];
+        Some(Perspective { c, size })
+    }
+
+    fn map(&self, u: f64, v: f64) -> (f64, f64) {
+        let (u, v) = (u / self.size, v / self.size);
+        let c = &self.c;
+        let den = c[6] * u + c[7] * v + 1.0;
+        ((c[0] * u + c[1] * v + c[2]) / den, (c[3] * u + c[4] * v + c[5]) / den)
+    }
+
+    fn corners(&self) -> [(f64, f64); 4] {
+        let size = self.size;
+        [self.map(0.0, 0.0), self.map(size, 0.0), self.map(size, size), self.map(0.0, size)]
+    }
+}
+
+// The image split into dark and light at the level that best separates its histogram.
+struct Binarized<'a> {
+    image: &'a GrayImage,
+    level: u8,
+}
+
+impl<'a> Binarized<'a> {
+    fn new(image: &'a GrayImage) -> Self {
+        let mut histogram = [0u64; 256];
+        for pixel in image.pixels() {
+            histogram[pixel[0] as usize] += 1;
+        }
+        // Otsu's method: the level with the largest variance between the two classes.
+        let total: u64 = histogram.iter().sum();
+        let sum: f64 = histogram.iter().enumerate().map(|(level, &count)| level as f64 * count as f64).sum();
+        let (mut below, mut below_sum, mut level, mut best) = (0u64, 0.0, 128u8, 0.0);
+        for (value, &count) in histogram.iter().enumerate() {
+            below += count;
+            below_sum += value as f64 * count as f64;
+            if below == 0 || below == total {
+                continue;
+            }
+            let above = total - below;
+            let mean_below = below_sum / below as f64;
+            let mean_above = (sum - below_sum) / above as f64;
+            let variance = below as f64 * above as f64 * (mean_below - mean_above).powi(2);
+            if variance > best {
+                best = variance;
+                level = value as u8;
+            }
+        }
+        Binarized { image, level }
+    }
+
+    fn dark(&self, x: u32, y: u32) -> bool {
+        self.image.get_pixel(x, y)[0] <= self.level
+    }
+
+    // `None` outside the image.
+    fn is_dark(&self, (x, y): (f64, f64)) -> Option<bool> {
+        if x < 0.0 || y < 0.0 || x >= self.image.width() as f64 || y >= self.image.height() as f64 {
+            return None;
+        }
+        Some(self.dark(x as u32, y as u32))
+    }
+}
+
+// Whether five run lengths, starting with a dark one, are in the 1:1:3:1:1 ratio of a
+// finder pattern crossed through its centre. Returns the module size.
+fn finder_ratio(runs: &[u32]) -> Option<f64> {
+    let total: u32 = runs.iter().sum();
+    if total < 7 {
+        return None;
+    }
+    let module = total as f64 / 7.0;
+    let tolerance = module * 0.6;
+    let fits = runs.iter().zip([1.0, 1.0, 3.0, 1.0, 1.0]).all(|(&run, expected)| {
+        (run as f64 - module * expected).abs() <= tolerance * expected.min(2.0)
+    });
+    fits.then_some(module)
+}
+
+// Measures the finder pattern around (x, y) along one column, returning the centre row
+// and the module size.
+fn cross_check(image: &Binarized, x: u32, y: u32) -> Option<(f64, f64)> {
+    if !image.dark(x, y) {
+        return None;
+    }
+    // Runs are counted outwards from the centre: centre, gap and ring on either side.
+    let mut up = [0u32; 3];
+    let mut row = y as i64;
+    for (index, dark) in [(0, true), (1, false), (2, true)] {
+        while row >= 0 && image.dark(x, row as u32) == dark {
+            up[index] += 1;
+            row -= 1;
+        }
+    }
+    let mut down = [0u32; 3];
+    let mut row = y as i64 + 1;
+    for (index, dark) in [(0, true), (1, false), (2, true)] {
+        while row < image.image.height() as i64 && image.dark(x, row as u32) == dark {
+            down[index] += 1;
+            row += 1;
+        }
+    }
+    let module = finder_ratio(&[up[2], up[1], up[0] + down[0], down[1], down[2]])?;
+    let center = y as f64 - up[0] as f64 + (up[0] + down[0]) as f64 / 2.0 + 0.5;
+    Some((center, module))
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Finder {
+    x: f64,
+    y: f64,
+    module: f64,
+    hits: u32,
+}
+
+// Scans every row for the 1:1:3:1:1 pattern and confirms it along the column, which works
+// for square, rounded and circular finder patterns alike.
+fn find_finders(image: &Binarized) -> Vec<Finder> {
+    let (width, height) = image.image.dimensions();
+    let mut finders: Vec<Finder> = Vec::new();
+    for y in 0..height {
+        let mut runs: Vec<(u32, u32)> = Vec::new();
+        let mut start = 0;
+        for x in 1..=width {
+            if x == width || image.dark(x, y) != image.dark(start, y) {
+                runs.push((start, x - start));
+                start = x;
+            }
+        }
+        let first_dark = usize::from(!runs.first().is_some_and(|&(x, _)| image.dark(x, y)));
+        for window in runs[first_dark.min(runs.len())..].windows(5).step_by(2) {
+            let lengths: Vec<u32> = window.iter().map(|&(_, length)| length).collect();
+            if finder_ratio(&lengths).is_none() {
+                continue;
+            }
+            let (center_start, center_length) = window[2];
+            let x = center_start + center_length / 2;
+            let Some((cy, module_y)) = cross_check(image, x, y) else {
+                continue;
+            };
+            let module_x = lengths.iter().sum::<u32>() as f64 / 7.0;
+            let (cx, module) = (center_start as f64 + center_length as f64 / 2.0, (module_x + module_y) / 2.0);
+            match finders.iter_mut().find(|f| (f.x - cx).abs() < f.module * 2.0 && (f.y - cy).abs() < f.module * 2.0) {
+                Some(finder) => {
+                    let hits = finder.hits as f64;
+                    finder.x = (finder.x * hits + cx) / (hits + 1.0);
+                    finder.y = (finder.y * hits + cy) / (hits + 1.0);
+                    finder.module = (finder.module * hits + module) / (hits + 1.0);
+                    finder.hits += 1;
+                }
+                None => finders.push(Finder { x: cx, y: cy, module, hits: 1 }),
+            }
+        }
+    }
+    finders.sort_by_key(|finder| std::cmp::Reverse(finder.hits));
+    finders.truncate(MAX_FINDERS);
+    finders
+}
+
+// Orders three finders as top-left, top-right and bottom-left when they can be the
+// corners of one symbol.
+fn arrange(a: Finder, b: Finder, c: Finder) -> Option<[Finder; 3]> {
+    let module = (a.module + b.module + c.module) / 3.0;
+    if [a, b, c].iter().any(|f| (f.module - module).abs() > module * 0.5) {
+        return None;
+    }
+    for (corner, p, q) in [(a, b, c), (b, a, c), (c, a, b)] {
+        let (px, py) = (p.x - corner.x, p.y - corner.y);
+        let (qx, qy) = (q.x - corner.x, q.y - corner.y);
+        let (lp, lq) = (px.hypot(py), qx.hypot(qy));
+        if lp < module * 7.0 || (lp - lq).abs() > lp.max(lq) * 0.2 || (px * qx + py * qy).abs() > lp * lq * 0.2 {
+            continue;
+        }
+        // With y pointing down, the top-right finder is clockwise from the bottom-left.
+        return Some(if px * qy - py * qx > 0.0 { [corner, p, q] } else { [corner, q, p] });
+    }
+    None
+}
+
+// Scores how well the grid mapped by `perspective` matches the parts of a QR code that
+// every module and finder shape keeps: the timing patterns, the centre, gap and middle of
+// each finder ring, the light border around the finders and the last alignment pattern.
+fn fitness(image: &Binarized, perspective: &Perspective, size: i32) -> i32 {
+    const OFFSETS: [f64; 3] = [0.3, 0.5, 0.7];
+    let cell = |x: i32, y: i32, dark: bool| {
+        let mut score = 0;
+        for v in OFFSETS {
+            for u in OFFSETS {
+                match image.is_dark(perspective.map(x as f64 + u, y as f64 + v)) {
+                    Some(found) if found == dark => score += 1,
+                    Some(_) => score -= 1,
+                    None => {}
+                }
+            }
+        }
+        score
+    };
+
+    let mut score = 0;
+    for i in 8..size - 8 {
+        score += cell(i, 6, i % 2 == 0) + cell(6, i, i % 2 == 0);
+    }
+    for (fx, fy) in [(0, 0), (size - 7, 0), (0, size - 7)] {
+        for (dx, dy, dark) in [
+            (3, 3, true), (2, 3, true), (4, 3, true), (3, 2, true), (3, 4, true),
+            (1, 3, false), (5, 3, false), (3, 1, false), (3, 5, false),
+            (0, 3, true), (6, 3, true), (3, 0, true), (3, 6, true),
+            (-1, 3, false), (7, 3, false), (3, -1, false), (3, 7, false),
+        ] {
+            score += cell(fx + dx, fy + dy, dark);
+        }
+    }
+    if size > 21 {
+        let center = size - 7;
+        for dy in -2..=2i32 {
+            for dx in -2..=2i32 {
+                score += cell(center + dx, center + dy, dx.abs().max(dy.abs()) != 1);
+            }
+        }
+    }
+    score
+}
+
+// Moves each corner in turn while the fitness improves, in ever smaller steps.
+fn refine(image: &Binarized, mut perspective: Perspective, module: f64) -> Perspective {
+    let size = perspective.size as i32;
+    let mut best = fitness(image, &perspective, size);
+    let mut step = module / 2.0;
+    while step > module / 16.0 {
+        let mut improved = true;
+        while improved {
+            improved = false;
+            for i in 0..8 {
+                for delta in [step, -step] {
+                    let mut corners = perspective.corners();
+                    let value = if i % 2 == 0 { &mut corners[i / 2].0 } else { &mut corners[i / 2].1 };
+                    *value += delta;
+                    let Some(candidate) = Perspective::new(corners, perspective.size) else {
+                        continue;
+                    };
+                    let score = fitness(image, &candidate, size);
+                    if score > best {
+                        best = score;
+                        perspective = candidate;
+                        improved = true;
+                    }
+                }
+            }
+        }
+        step /= 2.0;
+    }
+    perspective
+}
+
+struct Sampled<'a> {
+    image: &'a Binarized<'a>,
+    perspective: Perspective,
+}
+
+impl BitGrid for Sampled<'_> {
+    fn size(&self) -> usize {
+        self.perspective.size as usize
+    }
+
+    fn bit(&self, y: usize, x: usize) -> bool {
+        self.image.is_dark(self.perspective.map(x as f64 + 0.5, y as f64 + 0.5)).unwrap_or(false)
+    }
+}
+
+// Fits a `size`-module grid to three finders and reads it.
+fn read(image: &Binarized, [top_left, top_right, bottom_left]: [Finder; 3], size: usize) -> Option<(MetaData, Payload, [Point; 4])> {
+    let module = (top_left.module + top_right.module + bottom_left.module) / 3.0;
+    // The finder centres span size - 7 modules; the fourth corner assumes no perspective.
+    let bottom_right = (top_right.x + bottom_left.x - top_left.x, top_right.y + bottom_left.y - top_left.y);
+    let centers = [(top_left.x, top_left.y), (top_right.x, top_right.y), bottom_right, (bottom_left.x, bottom_left.y)];
+    let inner = Perspective::new(centers, size as f64 - 7.0)?;
+    let (near, far) = (-3.5, size as f64 - 3.5);
+    let corners = [inner.map(near, near), inner.map(far, near), inner.map(far, far), inner.map(near, far)];
+    let perspective = refine(image, Perspective::new(corners, size as f64)?, module);
+
+    let (metadata, payload) = bitstream::decode_grid(&Sampled { image, perspective }).ok()?;
+    let bounds = perspective.corners().map(|(x, y)| Point { x: x.round() as i32, y: y.round() as i32 });
+    Some((metadata, payload, bounds))
+}
+
+/// Locates and reads codes by their finder patterns, for images in which rqrr finds
+/// nothing it can read.
+///
+/// rqrr fits its grid to the corners of the finder patterns, so it misses or misreads codes
+/// drawn with rounded or circular finders. Here the finders are found by the 1:1:3:1:1
+/// ratio of their cross-sections instead, and the grid is fitted to the timing and
+/// alignment patterns before it is read with [`bitstream::decode_grid`]. Returns the
+/// corners of each symbol in image coordinates along with its payload.
+pub(super) fn decode(image: &GrayImage) -> Vec<(MetaData, Payload, [Point; 4])> {
+    let image = Binarized::new(image);
+    let finders = find_finders(&image);
+    let mut used = vec![false; finders.len()];
+    let mut found = Vec::new();
+
+    for a in 0..finders.len() {
+        for b in a + 1..finders.len() {
+            for c in b + 1..finders.len() {
+                if used[a] || used[b] || used[c] {
+                    continue;
+                }
+                let Some(arranged) = arrange(finders[a], finders[b], finders[c]) else {
+                    continue;
+                };
+                let [top_left, top_right, bottom_left] = arranged;
+                let span = ((top_right.x - top_left.x).hypot(top_right.y - top_left.y)
+                    + (bottom_left.x - top_left.x).hypot(bottom_left.y - top_left.y)) / 2.0;
+                let module = (top_left.module + top_right.module + bottom_left.module) / 3.0;
+                // The nearest version first, then its neighbours in case the estimate is off.
+                let version = ((span / module + 7.0 - 17.0) / 4.0).round().clamp(1.0, 40.0) as usize;
+                let versions = [version, version + 1, version - 1].into_iter().filter(|v| (1..=40).contains(v));
+                if let Some(symbol) = versions.into_iter().find_map(|v| read(&image, arranged, 17 + 4 * v)) {
+                    used[a] = true;
+                    used[b] = true;
+                    used[c] = true;
+                    found.push(symbol);
+                }
+            }
+        }
+    }
+    found
+}
@@ -0,0 +1,539 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use anyhow::Context;
+use image::{Rgba, RgbaImage};
+use qrcode::{Color, EcLevel, QrCode, Version};
+use qrcode::bits::{Bits, encode_auto};
+use qrcode::canvas::{Canvas, MaskPattern};
+use qrcode::ec::construct_codewords;
+use qrcode::render::Renderer;
+use serde::{Deserialize, Serialize};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+
+use crate::AppResult;
+use crate::caption::{self, Caption};
+use crate::style::{self, Style};
+
+mod vector;
+
+/// File format of a saved QR code.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RenderFormat {
+    #[default]
+    Png,
+    Svg,
+    Eps,
+    Pdf,
+}
+
+impl RenderFormat {
+    pub const ALL: [RenderFormat; 4] = [RenderFormat::Png, RenderFormat::Svg, RenderFormat::Eps, RenderFormat::Pdf];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            RenderFormat::Png => "png",
+            RenderFormat::Svg => "svg",
+            RenderFormat::Eps => "eps",
+            RenderFormat::Pdf => "pdf",
+        }
+    }
+
+    /// The format matching the extension of `path`, if it is one of the supported ones.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        RenderFormat::ALL.into_iter().find(|format| extension.eq_ignore_ascii_case(format.extension()))
+    }
+
+    pub fn is_vector(self) -> bool {
+        self != RenderFormat::Png
+    }
+}
+
+impl fmt::Display for RenderFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+impl FromStr for RenderFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        RenderFormat::ALL.into_iter()
+            .find(|format| s.eq_ignore_ascii_case(format.extension()))
+            .ok_or_else(|| format!("Expected 'png', 'svg', 'eps' or 'pdf', got: {}", s))
+    }
+}
+
+/// Settings used when building and rendering a QR code.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncodeOptions {
+    #[serde(with = "ec_level_serde")]
+    pub ec_level: EcLevel,
+    /// Smallest version to use, or the only one with `fixed_version`; the smallest that
+    /// fits when `None`.
+    pub version: Option<i16>,
+    pub fixed_version: bool,
+    /// Build Micro QR codes (versions M1 to M4) instead of regular ones.
+    pub micro: bool,
+    /// Mask pattern, 0-7 (0-3 for Micro QR); the one with the lowest penalty when `None`.
+    pub mask: Option<u8>,
+    /// Width of the light border, in modules; 4 (2 for Micro QR) when `None`.
+    pub quiet_zone: Option<u32>,
+    /// Size of one module, in pixels; when `None` modules grow to reach `min_dimensions`.
+    pub module_size: Option<u32>,
+    /// Minimum width and height of rendered images, in pixels.
+    pub min_dimensions: u32,
+    #[serde(with = "color_serde")]
+    pub dark_color: Rgba<u8>,
+    /// Color of light modules and the quiet zone; may be transparent.
+    #[serde(with = "color_serde")]
+    pub light_color: Rgba<u8>,
+    /// Module shapes and logo; plain square modules by default.
+    pub style: Style,
+    /// Text drawn under or over the code; none by default.
+    pub caption: Caption,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        EncodeOptions {
+            ec_level: EcLevel::M,
+            version: None,
+            fixed_version: false,
+            micro: false,
+            mask: None,
+            quiet_zone: None,
+            module_size: None,
+            min_dimensions: 200,
+            dark_color: Rgba([0, 0, 0, 255]),
+            light_color: Rgba([255, 255, 255, 255]),
+            style: Style::default(),
+            caption: Caption::default(),
+        }
+    }
+}
+
+impl EncodeOptions {
+    pub fn validate(&self) -> AppResult<()> {
+        let max_version = if self.micro { 4 } else { 40 };
+        if let Some(version) = self.version
+            && !(1..=max_version).contains(&version) {
+            anyhow::bail!("Version must be between 1 and {}, got: {}", max_version, version);
+        }
+        if self.fixed_version && self.version.is_none() {
+            anyhow::bail!("A fixed version needs a version number.");
+        }
+        if self.micro && self.ec_level == EcLevel::H {
+            anyhow::bail!("Micro QR codes do not support ECC level H.");
+        }
+        let mask_count = if self.micro { 4 } else { 8 };
+        if let Some(mask) = self.mask
+            && mask >= mask_count {
+            anyhow::bail!("Mask must be between 0 and {}, got: {}", mask_count - 1, mask);
+        }
+        if self.module_size == Some(0) {
+            anyhow::bail!("Module size must be at least 1 pixel.");
+        }
+        if self.micro && self.style.logo.is_some() {
+            anyhow::bail!("Logos need ECC level H, which Micro QR codes do not support.");
+        }
+        self.style.validate()?;
+        self.caption.validate()
+    }
+
+    /// The error correction level codes are built with: always H when a logo covers
+    /// part of the symbol.
+    pub fn effective_ec_level(&self) -> EcLevel {
+        if self.style.logo.is_some() { EcLevel::H } else { self.ec_level }
+    }
+
+    // Versions to try, smallest first.
+    pub(crate) fn versions(&self) -> Vec<Version> {
+        let max = if self.micro { 4 } else { 40 };
+        let first = self.version.unwrap_or(1);
+        let last = if self.fixed_version { first } else { max };
+        (first..=last)
+            .map(|n| if self.micro { Version::Micro(n) } else { Version::Normal(n) })
+            .collect()
+    }
+
+    pub(crate) fn quiet_zone_for(&self, version: Version) -> u32 {
+        self.quiet_zone.unwrap_or(if version.is_micro() { 2 } else { 4 })
+    }
+}
+
+/// Parses an error correction level: `L`, `M`, `Q` or `H`.
+pub fn parse_ec_level(value: &str) -> AppResult<EcLevel> {
+    match value.trim().to_uppercase().as_str() {
+        "L" => Ok(EcLevel::L),
+        "M" => Ok(EcLevel::M),
+        "Q" => Ok(EcLevel::Q),
+        "H" => Ok(EcLevel::H),
+        _ => anyhow::bail!("Expected an ECC level (L, M, Q or H), got: {}", value),
+    }
+}
+
+pub fn ec_level_name(ec_level: EcLevel) -> &'static str {
+    match ec_level {
+        EcLevel::L => "L",
+        EcLevel::M => "M",
+        EcLevel::Q => "Q",
+        EcLevel::H => "H",
+    }
+}
+
+/// Parses a color as `#rrggbb` or `#rgb`, with an optional alpha digit pair or digit
+/// (the `#` is optional), or as `black`, `white` or `transparent`.
+pub fn parse_color(value: &str) -> AppResult<Rgba<u8>> {
+    let value = value.trim();
+    match value.to_lowercase().as_str() {
+        "black" => return Ok(Rgba([0, 0, 0, 255])),
+        "white" => return Ok(Rgba([255, 255, 255, 255])),
+        "transparent" => return Ok(Rgba([255, 255, 255, 0])),
+        _ => {}
+    }
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    let digits: Vec<u8> = hex.chars()
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<_>>()
+        .context(format!("Expected a color like #1a2b3c, got: {}", value))?;
+    let channels: Vec<u8> = match digits.len() {
+        3 | 4 => digits.iter().map(|d| d * 17).collect(),
+        6 | 8 => digits.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect(),
+        _ => anyhow::bail!("Expected a color like #1a2b3c, got: {}", value),
+    };
+    Ok(Rgba([channels[0], channels[1], channels[2], channels.get(3).copied().unwrap_or(255)]))
+}
+
+/// Formats a color as `#rrggbb`, or `#rrggbbaa` when it is not opaque.
+pub fn format_color(color: Rgba<u8>) -> String {
+    let [r, g, b, a] = color.0;
+    if a == 255 {
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    } else {
+        format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+    }
+}
+
+mod ec_level_serde {
+    use qrcode::EcLevel;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(ec_level: &EcLevel, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(super::ec_level_name(*ec_level))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<EcLevel, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        super::parse_ec_level(&value).map_err(serde::de::Error::custom)
+    }
+}
+
+mod color_serde {
+    use image::Rgba;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Rgba<u8>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::format_color(*color))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Rgba<u8>, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        super::parse_color(&value).map_err(serde::de::Error::custom)
+    }
+}
+
+/// A finished QR symbol, ready to be rendered.
+///
+/// Symbols built by `qrcode` convert from [`QrCode`]; Structured Append symbols, which
+/// `qrcode` cannot produce, are assembled directly from their modules.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    version: Version,
+    ec_level: EcLevel,
+    width: usize,
+    modules: Vec<Color>,
+}
+
+impl Symbol {
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn ec_level(&self) -> EcLevel {
+        self.ec_level
+    }
+
+    /// Number of modules per side, without the quiet zone.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Module colors row by row, `width` modules per row.
+    pub fn modules(&self) -> &[Color] {
+        &self.modules
+    }
+
+    /// Whether the module at column `x`, row `y` is dark.
+    pub fn is_dark(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.width + x] == Color::Dark
+    }
+
+    fn renderer<P: qrcode::render::Pixel>(&self, quiet_zone: u32) -> Renderer<'_, P> {
+        Renderer::new(&self.modules, self.width, quiet_zone)
+    }
+}
+
+impl From<QrCode> for Symbol {
+    fn from(code: QrCode) -> Self {
+        Symbol {
+            version: code.version(),
+            ec_level: code.error_correction_level(),
+            width: code.width(),
+            modules: code.into_colors(),
+        }
+    }
+}
+
+/// Builds a QR code for `text`.
+pub fn encode(text: &str, options: &EncodeOptions) -> AppResult<Symbol> {
+    encode_bytes(text.as_bytes(), options)
+}
+
+/// Builds a QR code for arbitrary bytes.
+pub fn encode_bytes(data: &[u8], options: &EncodeOptions) -> AppResult<Symbol> {
+    options.validate()?;
+    let bits = if options.version.is_none() && !options.micro {
+        encode_auto(data, options.effective_ec_level()).ok()
+    } else {
+        options.versions().into_iter().find_map(|version| {
+            let mut bits = Bits::new(version);
+            bits.push_optimal_data(data).ok()?;
+            bits.push_terminator(options.effective_ec_level()).ok()?;
+            Some(bits)
+        })
+    };
+    let bits = bits.context("Could not create QR code. Text may be too long.")?;
+    draw_symbol(bits.version(), &bits.into_bytes(), options)
+}
+
+// Adds error correction to the data codewords and lays out the symbol with the chosen
+// mask, or the best one.
+pub(crate) fn draw_symbol(version: Version, codewords: &[u8], options: &EncodeOptions) -> AppResult<Symbol> {
+    let ec_level = options.effective_ec_level();
+    let (data, ec) = construct_codewords(codewords, version, ec_level)?;
+    let mut canvas = Canvas::new(version, ec_level);
+    canvas.draw_all_functional_patterns();
+    canvas.draw_data(&data, &ec);
+    let canvas = match options.mask {
+        Some(mask) => {
+            let patterns = if version.is_micro() { &MICRO_MASKS[..] } else { &MASKS[..] };
+            let pattern = *patterns.get(mask as usize).context(format!("Invalid mask: {}", mask))?;
+            canvas.apply_mask(pattern);
+            canvas
+        }
+        None => canvas.apply_best_mask(),
+    };
+    Ok(Symbol {
+        version,
+        ec_level,
+        width: version.width() as usize,
+        modules: canvas.into_colors(),
+    })
+}
+
+const MASKS: [MaskPattern; 8] = [
+    MaskPattern::Checkerboard,
+    MaskPattern::HorizontalLines,
+    MaskPattern::VerticalLines,
+    MaskPattern::DiagonalLines,
+    MaskPattern::LargeCheckerboard,
+    MaskPattern::Fields,
+    MaskPattern::Diamonds,
+    MaskPattern::Meadow,
+];
+
+// Micro QR only has four masks, numbered separately.
+const MICRO_MASKS: [MaskPattern; 4] = [
+    MaskPattern::HorizontalLines,
+    MaskPattern::LargeCheckerboard,
+    MaskPattern::Diamonds,
+    MaskPattern::Meadow,
+];
+
+/// Renders `symbol` with the colors, quiet zone, module size and style from `options`.
+///
+/// A logo that cannot be loaded is reported as an error. A caption set in `options.caption`
+/// is drawn outside the quiet zone, making the image taller.
+pub fn render_image(symbol: &Symbol, options: &EncodeOptions) -> AppResult<RgbaImage> {
+    let image = if options.style.is_plain() {
+        let mut renderer = symbol.renderer::<Rgba<u8>>(options.quiet_zone_for(symbol.version));
+        renderer.dark_color(options.dark_color).light_color(options.light_color);
+        match options.module_size {
+            Some(size) => renderer.module_dimensions(size, size),
+            None => renderer.min_dimensions(options.min_dimensions, options.min_dimensions),
+        };
+        renderer.build()
+    } else {
+        style::render(symbol, options)?
+    };
+    Ok(caption::draw(image, module_pixels(symbol, options), options))
+}
+
+// Pixels per module, as the plain renderer chooses it.
+pub(crate) fn module_pixels(symbol: &Symbol, options: &EncodeOptions) -> u32 {
+    let modules = symbol.width as u32 + 2 * options.quiet_zone_for(symbol.version);
+    options.module_size.unwrap_or_else(|| options.min_dimensions.div_ceil(modules).max(1))
+}
+
+/// Renders `symbol` with Unicode half blocks for display in a terminal.
+pub fn render_terminal(symbol: &Symbol) -> String {
+    use qrcode::render::unicode;
+
+    let quiet_zone = if symbol.version.is_micro() { 2 } else { 4 };
+    symbol.renderer::<unicode::Dense1x2>(quiet_zone)
+        .dark_color(unicode::Dense1x2::Light)
+        .light_color(unicode::Dense1x2::Dark)
+        .build()
+}
+
+/// Renders several symbols side by side on one image, in rows of up to `columns`.
+///
+/// Every cell is as large as the biggest symbol, and symbols are placed left to right,
+/// top to bottom. Captions are numbered with each symbol's position.
+pub fn render_contact_sheet(symbols: &[Symbol], columns: usize, options: &EncodeOptions) -> AppResult<RgbaImage> {
+    let images: Vec<RgbaImage> = symbols.iter()
+        .enumerate()
+        .map(|(i, symbol)| {
+            let caption = options.caption.for_part(i, symbols.len());
+            render_image(symbol, &EncodeOptions { caption, ..options.clone() })
+        })
+        .collect::<AppResult<_>>()?;
+    let columns = columns.clamp(1, images.len().max(1));
+    let rows = images.len().div_ceil(columns);
+    let cell_width = images.iter().map(|image| image.width()).max().unwrap_or(0);
+    let cell_height = images.iter().map(|image| image.height()).max().unwrap_or(0);
+
+    // Rendered symbols already carry their own quiet zone, so the cells can touch.
+    let mut sheet = RgbaImage::from_pixel(cell_width * columns as u32, cell_height * rows as u32, options.light_color);
+    for (i, image) in images.iter().enumerate() {
+        let x = (i % columns) as u32 * cell_width + (cell_width - image.width()) / 2;
+        let y = (i / columns) as u32 * cell_height + (cell_height - image.height()) / 2;
+        image::imageops::replace(&mut sheet, image, x as i64, y as i64);
+    }
+    Ok(sheet)
+}
+
+/// Renders `symbol` as an SVG document; modules are sized in pixels.
+///
+/// Vector output only draws plain square modules; see [`Style::is_plain`].
+pub fn render_svg(symbol: &Symbol, options: &EncodeOptions) -> String {
+    vector::render_svg(symbol, options)
+}
+
+/// Renders `symbol` as Encapsulated PostScript; modules are sized in points.
+pub fn render_eps(symbol: &Symbol, options: &EncodeOptions) -> String {
+    vector::render_eps(symbol, options)
+}
+
+/// Renders `symbol` as a one-page PDF the size of the code; modules are sized in points.
+pub fn render_pdf(symbol: &Symbol, options: &EncodeOptions) -> Vec<u8> {
+    vector::render_pdf(symbol, options)
+}
+
+/// Renders `symbol` and saves it as a PNG at `path`.
+pub fn save_png(symbol: &Symbol, path: &Path, options: &EncodeOptions) -> AppResult<()> {
+    save_image(&render_image(symbol, options)?, path)
+}
+
+/// Renders `symbol` in `format` and saves it at `path`.
+pub fn save_symbol(symbol: &Symbol, path: &Path, format: RenderFormat, options: &EncodeOptions) -> AppResult<()> {
+    if format.is_vector() && !options.style.is_plain() {
+        anyhow::bail!("Module shapes, finder shapes and logos are only available for PNG output.");
+    }
+    if format.is_vector() && options.caption.text.is_some() {
+        anyhow::bail!("Captions are only available for PNG output.");
+    }
+    let data = match format {
+        RenderFormat::Png => return save_png(symbol, path, options),
+        RenderFormat::Svg => render_svg(symbol, options).into_bytes(),
+        RenderFormat::Eps => render_eps(symbol, options).into_bytes(),
+        RenderFormat::Pdf => render_pdf(symbol, options),
+    };
+    std::fs::write(path, data)
+        .context(format!("Could not save QR code file: {}", path.display()))?;
+    set_readable(path)
+}
+
+/// Saves a rendered image as a PNG at `path`, readable by everyone.
+pub fn save_image(image: &RgbaImage, path: &Path) -> AppResult<()> {
+    image.save_with_format(path, image::ImageFormat::Png)
+        .context(format!("Could not save QR code file: {}", path.display()))?;
+    set_readable(path)
+}
+
+fn set_readable(path: &Path) -> AppResult<()> {
+    #[cfg(unix)]
+    {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o644))
+            .context(format!("Could not set file permissions: {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejected(options: EncodeOptions) -> String {
+        options.validate().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        let micro = || EncodeOptions { micro: true, ec_level: EcLevel::L, ..EncodeOptions::default() };
+        assert!(rejected(EncodeOptions { mask: Some(4), ..micro() }).contains("between 0 and 3"));
+        assert!(rejected(EncodeOptions { mask: Some(8), ..EncodeOptions::default() }).contains("between 0 and 7"));
+        assert!(rejected(EncodeOptions { version: Some(0), ..EncodeOptions::default() }).contains("between 1 and 40"));
+        assert!(rejected(EncodeOptions { version: Some(41), ..EncodeOptions::default() }).contains("between 1 and 40"));
+        assert!(rejected(EncodeOptions { version: Some(5), ..micro() }).contains("between 1 and 4"));
+        assert!(rejected(EncodeOptions { ec_level: EcLevel::H, ..micro() }).contains("level H"));
+        assert!(rejected(EncodeOptions { fixed_version: true, ..EncodeOptions::default() }).contains("needs a version"));
+        assert!(rejected(EncodeOptions { module_size: Some(0), ..EncodeOptions::default() }).contains("Module size"));
+        let logo = Style { logo: Some("logo.png".into()), ..Style::default() };
+        assert!(rejected(EncodeOptions { style: logo, ..micro() }).contains("Logos"));
+
+        assert!(EncodeOptions::default().validate().is_ok());
+        assert!(EncodeOptions { mask: Some(3), version: Some(4), ..micro() }.validate().is_ok());
+    }
+
+    #[test]
+    fn fixed_version_must_hold_the_data() {
+        let options = EncodeOptions { version: Some(1), fixed_version: true, ..EncodeOptions::default() };
+        assert!(encode("fits", &options).is_ok());
+        assert!(encode("far too long for a version one symbol at level M", &options).is_err());
+
+        // Without fixed_version the version is only a minimum.
+        let symbol = encode("far too long for a version one symbol at level M", &EncodeOptions { fixed_version: false, ..options }).unwrap();
+        assert!(symbol.version().width() > 21);
+    }
+
+    #[test]
+    fn captions_are_png_only() {
+        let caption = Caption { text: Some("label".to_string()), ..Caption::default() };
+        let options = EncodeOptions { caption, ..EncodeOptions::default() };
+        let symbol = encode("caption", &options).unwrap();
+        let path = std::env::temp_dir().join(format!("{}-caption-{}.svg", env!("CARGO_PKG_NAME"), std::process::id()));
+        for format in [RenderFormat::Svg, RenderFormat::Eps, RenderFormat::Pdf] {
+            let error = save_symbol(&symbol, &path, format, &options).unwrap_err();
+            assert_eq!(error.to_string(), "Captions are only available for PNG output.");
+        }
+        assert!(!path.exists());
+    }
+}
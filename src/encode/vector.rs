@@ -0,0 +1,202 @@
+use qrcode::render::svg;
+
+use super::{EncodeOptions, Symbol, format_color};
+
+// Size of one module and of the whole drawing, in output units (pixels or points).
+struct Layout {
+    unit: u32,
+    quiet_zone: u32,
+    size: u32,
+}
+
+impl Layout {
+    fn new(symbol: &Symbol, options: &EncodeOptions) -> Self {
+        let quiet_zone = options.quiet_zone_for(symbol.version());
+        let modules = symbol.width() as u32 + 2 * quiet_zone;
+        let unit = super::module_pixels(symbol, options);
+        Layout { unit, quiet_zone, size: modules * unit }
+    }
+
+    // Horizontal runs of dark modules as (column, row, length), rows from the top.
+    fn dark_runs(symbol: &Symbol) -> Vec<(u32, u32, u32)> {
+        let mut runs = Vec::new();
+        for y in 0..symbol.width() {
+            let mut x = 0;
+            while x < symbol.width() {
+                if symbol.is_dark(x, y) {
+                    let start = x;
+                    while x < symbol.width() && symbol.is_dark(x, y) {
+                        x += 1;
+                    }
+                    runs.push((start as u32, y as u32, (x - start) as u32));
+                } else {
+                    x += 1;
+                }
+            }
+        }
+        runs
+    }
+}
+
+pub(super) fn render_svg(symbol: &Symbol, options: &EncodeOptions) -> String {
+    let layout = Layout::new(symbol, options);
+    let dark = format_color(options.dark_color);
+    let light = format_color(options.light_color);
+    symbol.renderer::<svg::Color>(layout.quiet_zone)
+        .module_dimensions(layout.unit, layout.unit)
+        .dark_color(svg::Color(&dark))
+        .light_color(svg::Color(&light))
+        .build()
+}
+
+// PostScript and PDF have no alpha channel here, so only a fully transparent background
+// is honoured, by leaving it out.
+fn postscript_color(color: image::Rgba<u8>) -> String {
+    let [r, g, b, _] = color.0.map(|c| format!("{:.3}", c as f32 / 255.0));
+    format!("{} {} {}", r, g, b)
+}
+
+// Rectangles of the light background and every dark run, in PostScript coordinates
+// (origin at the bottom left), as "x y width height" strings.
+fn rectangles(symbol: &Symbol, layout: &Layout) -> Vec<String> {
+    Layout::dark_runs(symbol).into_iter()
+        .map(|(x, y, len)| {
+            let left = (x + layout.quiet_zone) * layout.unit;
+            let bottom = layout.size - (y + layout.quiet_zone + 1) * layout.unit;
+            format!("{} {} {} {}", left, bottom, len * layout.unit, layout.unit)
+        })
+        .collect()
+}
+
+pub(super) fn render_eps(symbol: &Symbol, options: &EncodeOptions) -> String {
+    let layout = Layout::new(symbol, options);
+    let mut eps = format!(
+        "%!PS-Adobe-3.0 EPSF-3.0\n%%BoundingBox: 0 0 {0} {0}\n%%Creator: {1}\n%%EndComments\n",
+        layout.size, env!("CARGO_PKG_NAME"),
+    );
+    if options.light_color[3] > 0 {
+        eps += &format!("{} setrgbcolor 0 0 {1} {1} rectfill\n", postscript_color(options.light_color), layout.size);
+    }
+    eps += &format!("{} setrgbcolor\n", postscript_color(options.dark_color));
+    for rectangle in rectangles(symbol, &layout) {
+        eps += &format!("{} rectfill\n", rectangle);
+    }
+    eps += "showpage\n%%EOF\n";
+    eps
+}
+
+// A single-page PDF whose page is exactly the size of the symbol.
+pub(super) fn render_pdf(symbol: &Symbol, options: &EncodeOptions) -> Vec<u8> {
+    let layout = Layout::new(symbol, options);
+    let mut content = String::new();
+    if options.light_color[3] > 0 {
+        content += &format!("{} rg 0 0 {1} {1} re f\n", postscript_color(options.light_color), layout.size);
+    }
+    content += &format!("{} rg\n", postscript_color(options.dark_color));
+    for rectangle in rectangles(symbol, &layout) {
+        content += &format!("{} re\n", rectangle);
+    }
+    content += "f\n";
+
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+        format!("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {0} {0}] /Contents 4 0 R /Resources << >> >>", layout.size),
+        format!("<< /Length {} >>\nstream\n{}endstream", content.len(), content),
+    ];
+
+    // Cross-reference entries must be exactly 20 bytes, hence the trailing spaces.
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf += &format!("{} 0 obj\n{}\nendobj\n", i + 1, object);
+    }
+    let xref = pdf.len();
+    pdf += &format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        pdf += &format!("{:010} 00000 n \n", offset);
+    }
+    pdf += &format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref);
+    pdf.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::encode;
+
+    fn sample() -> (Symbol, EncodeOptions, usize) {
+        let options = EncodeOptions { module_size: Some(3), ..EncodeOptions::default() };
+        let symbol = encode("vector output", &options).unwrap();
+        let dark = symbol.modules().iter().filter(|&&color| color == qrcode::Color::Dark).count();
+        (symbol, options, dark)
+    }
+
+    // The "x y width height" operands of every rectangle drawn, background included.
+    fn drawn_rectangles(text: &str, operator: &str) -> Vec<[u32; 4]> {
+        text.lines()
+            .filter_map(|line| line.strip_suffix(operator))
+            .map(|operands| {
+                let values: Vec<u32> = operands.split_whitespace().rev().take(4).map(|v| v.parse().unwrap()).collect();
+                [values[3], values[2], values[1], values[0]]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn svg_draws_every_dark_module_inside_the_quiet_zone() {
+        let (symbol, options, dark) = sample();
+        let svg = render_svg(&symbol, &options);
+        let size = (symbol.width() as u32 + 8) * 3;
+        assert!(svg.contains(&format!("width=\"{0}\" height=\"{0}\" viewBox=\"0 0 {0} {0}\"", size)), "{}", svg);
+        assert!(svg.contains(&format!("<rect x=\"0\" y=\"0\" width=\"{0}\" height=\"{0}\" fill=\"#ffffff\"/>", size)));
+        let path = svg.split(" d=\"").nth(1).unwrap();
+        assert_eq!(path.matches('M').count(), dark);
+        // The first module drawn is the corner of the top-left finder, past the quiet zone.
+        assert!(path.starts_with("M12 12h3v3H12V12"));
+    }
+
+    #[test]
+    fn pdf_and_eps_draw_every_dark_module() {
+        let (symbol, options, dark) = sample();
+        let size = (symbol.width() as u32 + 8) * 3;
+        let pdf = String::from_utf8(render_pdf(&symbol, &options)).unwrap();
+        let eps = render_eps(&symbol, &options);
+
+        for (rectangles, name) in [(drawn_rectangles(&pdf, " re f"), "pdf background"), (drawn_rectangles(&eps, " rectfill"), "eps")] {
+            assert_eq!(rectangles[0], [0, 0, size, size], "{}", name);
+        }
+        assert!(pdf.contains(&format!("/MediaBox [0 0 {0} {0}]", size)));
+        assert!(eps.contains(&format!("%%BoundingBox: 0 0 {0} {0}", size)));
+
+        let runs = Layout::dark_runs(&symbol).len();
+        for rectangles in [drawn_rectangles(&pdf, " re"), drawn_rectangles(&eps, " rectfill")[1..].to_vec()] {
+            assert_eq!(rectangles.len(), runs);
+            let area: u32 = rectangles.iter().map(|[_, _, width, height]| width * height).sum();
+            assert_eq!(area as usize, dark * 9);
+            assert!(rectangles.iter().all(|&[x, y, width, height]| x >= 12 && y >= 12 && x + width <= size - 12 && y + height <= size - 12));
+        }
+    }
+
+    #[test]
+    fn pdf_cross_references_point_at_objects() {
+        let (symbol, options, _) = sample();
+        let pdf = render_pdf(&symbol, &options);
+        let text = String::from_utf8_lossy(&pdf);
+        let xref: usize = text.rsplit("startxref\n").next().unwrap().lines().next().unwrap().parse().unwrap();
+        assert!(text[xref..].starts_with("xref\n0 5\n"));
+        for (i, entry) in text[xref..].lines().skip(3).take(4).enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(text[offset..].starts_with(&format!("{} 0 obj", i + 1)), "object {}", i + 1);
+        }
+    }
+
+    #[test]
+    fn transparent_background_is_left_out() {
+        let (symbol, options, _) = sample();
+        let options = EncodeOptions { light_color: image::Rgba([255, 255, 255, 0]), ..options };
+        let eps = render_eps(&symbol, &options);
+        assert_eq!(drawn_rectangles(&eps, " rectfill").len(), Layout::dark_runs(&symbol).len());
+    }
+}
@@ -0,0 +1,378 @@
+use std::path::{Path, PathBuf};
+use anyhow::Context;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use image::{DynamicImage, Frames, ImageFormat, ImageReader};
+use serde::{Serialize, Deserialize};
+use walkdir::{DirEntry, WalkDir};
+
+use crate::AppResult;
+
+#[cfg(feature = "pdf")]
+mod pdf_pages;
+#[cfg(feature = "pdf")]
+mod pdf_render;
+#[cfg(feature = "tiff")]
+mod tiff_pages;
+
+/// Pages of a multi-page document as `(page number, image)`, numbered from 1.
+pub type Pages = Box<dyn Iterator<Item = AppResult<(usize, DynamicImage)>>>;
+
+/// How [`collect_image_files`] walks a scan directory.
+///
+/// Glob patterns are matched against the path relative to the scan directory,
+/// using `/` as separator (e.g. `2024-*/**/*.png`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScanOptions {
+    #[serde(default)]
+    pub recursive: bool,
+    /// Deepest subdirectory level to enter when recursive; unlimited when `None`.
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    #[serde(default)]
+    pub follow_links: bool,
+    /// Only files matching at least one pattern are kept; all files when empty.
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Files and directories matching any pattern are skipped.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Also scan files and directories whose name starts with a dot.
+    #[serde(default)]
+    pub include_hidden: bool,
+}
+
+impl ScanOptions {
+    fn walk_depth(&self) -> usize {
+        if self.recursive {
+            // Depth 1 is the scan directory's own files, so subdirectory levels start at 2.
+            self.max_depth.map_or(usize::MAX, |depth| depth.saturating_add(1))
+        } else {
+            1
+        }
+    }
+}
+
+/// Whether this build can decode `format`, as selected by the crate's cargo features.
+pub fn can_decode(format: ImageFormat) -> bool {
+    match format {
+        // image reports AVIF as readable with its encoder-only feature; decoding needs dav1d.
+        ImageFormat::Avif => cfg!(feature = "avif"),
+        _ => format.reading_enabled(),
+    }
+}
+
+// The cargo feature that adds decoding of `format`; `None` when there is none.
+fn cargo_feature(format: ImageFormat) -> Option<&'static str> {
+    match format {
+        ImageFormat::Jpeg => Some("jpeg"),
+        ImageFormat::Gif => Some("gif"),
+        ImageFormat::WebP => Some("webp"),
+        ImageFormat::Bmp => Some("bmp"),
+        ImageFormat::Tiff => Some("tiff"),
+        ImageFormat::Ico => Some("ico"),
+        ImageFormat::Pnm => Some("pnm"),
+        ImageFormat::Tga => Some("tga"),
+        ImageFormat::Qoi => Some("qoi"),
+        ImageFormat::Hdr => Some("hdr"),
+        ImageFormat::OpenExr => Some("exr"),
+        ImageFormat::Farbfeld => Some("farbfeld"),
+        ImageFormat::Avif => Some("avif"),
+        _ => None,
+    }
+}
+
+/// Image formats this build can decode.
+pub fn supported_formats() -> Vec<ImageFormat> {
+    ImageFormat::all().filter(|format| can_decode(*format)).collect()
+}
+
+/// File extensions of every format this build can decode, lowercase.
+pub fn supported_extensions() -> Vec<&'static str> {
+    let mut extensions: Vec<&'static str> = supported_formats().into_iter()
+        .flat_map(|format| format.extensions_str().iter().copied())
+        .collect();
+    if cfg!(feature = "pdf") {
+        extensions.push("pdf");
+    }
+    extensions
+}
+
+pub fn is_supported_extension(ext: &str) -> bool {
+    (cfg!(feature = "pdf") && ext.eq_ignore_ascii_case("pdf"))
+        || ImageFormat::from_extension(ext).is_some_and(can_decode)
+}
+
+/// Identifies the image format of `path` from its leading bytes, ignoring the extension.
+pub fn detect_format(path: &Path) -> Option<ImageFormat> {
+    ImageReader::open(path).ok()?
+        .with_guessed_format().ok()?
+        .format()
+        .filter(|format| can_decode(*format))
+}
+
+/// Whether `path` looks like a decodable image, by extension or, failing that, by content.
+pub fn is_supported_image(path: &Path) -> bool {
+    let by_extension = path.extension()
+        .and_then(|s| s.to_str())
+        .is_some_and(is_supported_extension);
+    by_extension || detect_format(path).is_some() || is_pdf(path)
+}
+
+fn is_pdf(path: &Path) -> bool {
+    if !cfg!(feature = "pdf") {
+        return false;
+    }
+    let mut magic = [0u8; 5];
+    std::fs::File::open(path)
+        .and_then(|mut file| std::io::Read::read_exact(&mut file, &mut magic))
+        .is_ok_and(|()| &magic == b"%PDF-")
+}
+
+/// Opens an image, trusting its content over its extension.
+pub fn open_image(path: &Path) -> AppResult<DynamicImage> {
+    let reader = ImageReader::open(path)
+        .context(format!("Could not open image file: {}", path.display()))?
+        .with_guessed_format()
+        .context(format!("Could not read image file: {}", path.display()))?;
+    match reader.format() {
+        Some(format) if can_decode(format) => {}
+        Some(format) => match cargo_feature(format) {
+            Some(feature) => anyhow::bail!("{} is a {:?} image, which this build cannot decode; rebuild with the '{}' feature.", path.display(), format, feature),
+            None => anyhow::bail!("{} is a {:?} image, which is not supported.", path.display(), format),
+        },
+        None => anyhow::bail!("Unrecognized image format: {}", path.display()),
+    }
+    reader.decode().context(format!("Could not decode image file: {}", path.display()))
+}
+
+/// Opens the frames of a GIF or animated WebP; `None` for every other image.
+pub fn open_animation(path: &Path) -> AppResult<Option<Frames<'static>>> {
+    match detect_format(path) {
+        #[cfg(feature = "gif")]
+        Some(ImageFormat::Gif) => {
+            use image::AnimationDecoder;
+            let decoder = image::codecs::gif::GifDecoder::new(open_buffered(path)?)
+                .context(format!("Could not read GIF file: {}", path.display()))?;
+            Ok(Some(decoder.into_frames()))
+        }
+        #[cfg(feature = "webp")]
+        Some(ImageFormat::WebP) => {
+            use image::AnimationDecoder;
+            let decoder = image::codecs::webp::WebPDecoder::new(open_buffered(path)?)
+                .context(format!("Could not read WebP file: {}", path.display()))?;
+            Ok(decoder.has_animation().then(|| decoder.into_frames()))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Opens the pages of a PDF or multi-page TIFF; `None` for every other image.
+///
+/// PDF pages are rendered from their drawn shapes and images, or taken from the scanned
+/// image they hold; a page that cannot be rendered is yielded as an error.
+pub fn open_pages(path: &Path) -> AppResult<Option<Pages>> {
+    #[cfg(feature = "pdf")]
+    if is_pdf(path) {
+        return pdf_pages::open(path).map(Some);
+    }
+    #[cfg(feature = "tiff")]
+    if detect_format(path) == Some(ImageFormat::Tiff) {
+        return tiff_pages::open(path);
+    }
+    let _ = path;
+    Ok(None)
+}
+
+#[cfg(any(feature = "gif", feature = "webp", feature = "tiff"))]
+fn open_buffered(path: &Path) -> AppResult<std::io::BufReader<std::fs::File>> {
+    let file = std::fs::File::open(path)
+        .context(format!("Could not open image file: {}", path.display()))?;
+    Ok(std::io::BufReader::new(file))
+}
+
+fn build_globset(patterns: &[String]) -> AppResult<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).context(format!("Invalid glob pattern: {}", pattern))?);
+    }
+    Ok(Some(builder.build().context("Could not build glob patterns.")?))
+}
+
+fn relative_path(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn is_hidden(entry: &DirEntry) -> bool {
+    entry.depth() > 0 && entry.file_name().to_string_lossy().starts_with('.')
+}
+
+/// Image files found by [`collect_image_files`].
+#[derive(Debug, Default)]
+pub struct ScanReport {
+    /// Supported image files, sorted by path.
+    pub files: Vec<PathBuf>,
+    /// Entries that could not be read, such as directories without permission or symlink
+    /// loops; the rest of the scan goes on without them.
+    pub warnings: Vec<String>,
+}
+
+/// Lists the supported image files under `dir`, sorted by path.
+pub fn collect_image_files(dir: &Path, options: &ScanOptions) -> AppResult<ScanReport> {
+    let include = build_globset(&options.include)?;
+    let exclude = build_globset(&options.exclude)?;
+    let mut report = ScanReport::default();
+
+    let walker = WalkDir::new(dir)
+        .max_depth(options.walk_depth())
+        .follow_links(options.follow_links)
+        .into_iter()
+        .filter_entry(|entry| {
+            if entry.depth() == 0 {
+                return true;
+            }
+            if !options.include_hidden && is_hidden(entry) {
+                return false;
+            }
+            exclude.as_ref()
+                .is_none_or(|set| !set.is_match(relative_path(dir, entry.path())))
+        });
+
+    for entry in walker {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                report.warnings.push(format!("Skipped: {}", e));
+                continue;
+            }
+        };
+        let path = entry.path();
+        if path.is_file()
+            && include.as_ref().is_none_or(|set| set.is_match(relative_path(dir, path)))
+            && is_supported_image(path) {
+            report.files.push(path.to_path_buf());
+        }
+    }
+
+    report.files.sort();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}-input-{}-{}", env!("CARGO_PKG_NAME"), std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // A scan directory holding:
+    // a.png, notes.txt, .hidden.png, .cache/b.png, 2024-01/c.png, 2024-01/raw/d.png, skip/e.png
+    fn scan_tree(name: &str) -> PathBuf {
+        let dir = temp_dir(name);
+        for file in ["a.png", "notes.txt", ".hidden.png", ".cache/b.png", "2024-01/c.png", "2024-01/raw/d.png", "skip/e.png"] {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, b"not checked").unwrap();
+        }
+        dir
+    }
+
+    fn scan(dir: &Path, options: ScanOptions) -> Vec<String> {
+        let report = collect_image_files(dir, &options).unwrap();
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        report.files.iter().map(|path| relative_path(dir, path)).collect()
+    }
+
+    #[test]
+    fn scans_the_top_level_unless_recursive() {
+        let dir = scan_tree("top-level");
+        assert_eq!(scan(&dir, ScanOptions::default()), ["a.png"]);
+        assert_eq!(scan(&dir, ScanOptions { recursive: true, ..ScanOptions::default() }),
+            ["2024-01/c.png", "2024-01/raw/d.png", "a.png", "skip/e.png"]);
+        assert_eq!(scan(&dir, ScanOptions { recursive: true, max_depth: Some(1), ..ScanOptions::default() }),
+            ["2024-01/c.png", "a.png", "skip/e.png"]);
+        assert_eq!(scan(&dir, ScanOptions { recursive: true, max_depth: Some(0), ..ScanOptions::default() }), ["a.png"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn scans_hidden_files_when_asked() {
+        let dir = scan_tree("hidden");
+        let options = ScanOptions { recursive: true, include_hidden: true, ..ScanOptions::default() };
+        assert_eq!(scan(&dir, options), [".cache/b.png", ".hidden.png", "2024-01/c.png", "2024-01/raw/d.png", "a.png", "skip/e.png"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn filters_with_globs() {
+        let dir = scan_tree("globs");
+        let include = ScanOptions { recursive: true, include: vec!["2024-*/**/*.png".to_string()], ..ScanOptions::default() };
+        assert_eq!(scan(&dir, include), ["2024-01/c.png", "2024-01/raw/d.png"]);
+        let exclude = ScanOptions { recursive: true, exclude: vec!["skip".to_string(), "**/raw".to_string()], ..ScanOptions::default() };
+        assert_eq!(scan(&dir, exclude), ["2024-01/c.png", "a.png"]);
+        let invalid = ScanOptions { include: vec!["[".to_string()], ..ScanOptions::default() };
+        assert!(collect_image_files(&dir, &invalid).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn png_bytes() -> Vec<u8> {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        DynamicImage::ImageLuma8(image::GrayImage::new(4, 3)).write_to(&mut bytes, ImageFormat::Png).unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn trusts_content_over_extension() {
+        let dir = temp_dir("sniff");
+        let misnamed = dir.join("photo.jpg");
+        let unnamed = dir.join("scan.dat");
+        std::fs::write(&misnamed, png_bytes()).unwrap();
+        std::fs::write(&unnamed, png_bytes()).unwrap();
+        std::fs::write(dir.join("notes.txt"), b"plain text").unwrap();
+
+        assert_eq!(detect_format(&misnamed), Some(ImageFormat::Png));
+        assert_eq!(open_image(&misnamed).unwrap().width(), 4);
+        assert!(is_supported_image(&unnamed));
+        assert_eq!(open_image(&unnamed).unwrap().height(), 3);
+        assert!(!is_supported_image(&dir.join("notes.txt")));
+        assert!(open_image(&dir.join("notes.txt")).unwrap_err().to_string().contains("Unrecognized"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(not(feature = "farbfeld"))]
+    #[test]
+    fn names_the_feature_a_format_needs() {
+        let dir = temp_dir("feature");
+        let path = dir.join("image.png");
+        let mut farbfeld = b"farbfeld".to_vec();
+        farbfeld.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+        std::fs::write(&path, farbfeld).unwrap();
+        let error = open_image(&path).unwrap_err().to_string();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(error.contains("'farbfeld' feature"), "{}", error);
+        assert_eq!(cargo_feature(ImageFormat::Hdr), Some("hdr"));
+        assert_eq!(cargo_feature(ImageFormat::Png), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reports_symlink_loops() {
+        let dir = scan_tree("loop");
+        std::os::unix::fs::symlink(&dir, dir.join("2024-01/back")).unwrap();
+        let report = collect_image_files(&dir, &ScanOptions { recursive: true, follow_links: true, ..ScanOptions::default() }).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(report.files.len(), 4);
+        assert_eq!(report.warnings.len(), 1);
+        assert!(report.warnings[0].contains("back"), "{}", report.warnings[0]);
+    }
+}
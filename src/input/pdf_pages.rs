@@ -0,0 +1,243 @@
+use std::path::Path;
+use anyhow::Context;
+use image::{DynamicImage, GrayImage, RgbImage};
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+use crate::AppResult;
+use super::Pages;
+use super::pdf_render::{has_paths, render};
+
+// Embedded images larger than this on either side are rejected before allocating.
+const MAX_IMAGE_SIDE: u32 = 20_000;
+
+/// Pages of a PDF, each rendered to an image.
+///
+/// A page that only holds one scanned image yields that image at its own resolution.
+/// Pages with drawn content, such as generated codes made of filled rectangles, are
+/// rasterized with their paths and images; text is not rendered. A page that cannot be
+/// rendered yields an error and the remaining pages still follow.
+pub fn open(path: &Path) -> AppResult<Pages> {
+    let document = Document::load(path)
+        .context(format!("Could not read PDF file: {}", path.display()))?;
+    let pages: Vec<(u32, ObjectId)> = document.get_pages().into_iter().collect();
+
+    Ok(Box::new(pages.into_iter().map(move |(number, page_id)| {
+        render_page(&document, page_id)
+            .context(format!("Could not render PDF page {}", number))
+            .map(|image| (number as usize, image))
+    })))
+}
+
+fn render_page(document: &Document, page_id: ObjectId) -> AppResult<DynamicImage> {
+    let content = document.get_page_content(page_id)
+        .and_then(|data| Content::decode(&data))
+        .ok();
+    if let Some(content) = &content
+        && has_paths(content)
+        && let Some(image) = render(document, page_id, content)? {
+        return Ok(DynamicImage::ImageLuma8(image));
+    }
+    if let Some(image) = largest_image(document, page_id)? {
+        return Ok(image);
+    }
+    match &content {
+        Some(content) => render(document, page_id, content)?
+            .map(DynamicImage::ImageLuma8)
+            .context("Page has no images or drawn shapes; text is not rendered"),
+        None => anyhow::bail!("Page content could not be read and the page has no images"),
+    }
+}
+
+fn largest_image(document: &Document, page_id: ObjectId) -> AppResult<Option<DynamicImage>> {
+    let mut images = document.get_page_images(page_id)?;
+    if images.is_empty() {
+        return Ok(None);
+    }
+    images.sort_by_key(|image| std::cmp::Reverse(image.width.saturating_mul(image.height)));
+
+    let mut last_error = None;
+    for image in images {
+        match decode_image_xobject(document, image.id) {
+            Ok(decoded) => return Ok(Some(decoded)),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No decodable image on page")))
+}
+
+fn dimension(dict: &Dictionary, key: &[u8]) -> AppResult<u32> {
+    let value = dict.get(key)?.as_i64()?;
+    match u32::try_from(value) {
+        Ok(value @ 1..=MAX_IMAGE_SIDE) => Ok(value),
+        _ => anyhow::bail!("Image {} is out of range: {}", String::from_utf8_lossy(key), value),
+    }
+}
+
+pub(super) fn decode_image_xobject(document: &Document, id: ObjectId) -> AppResult<DynamicImage> {
+    let stream = document.get_object(id)?.as_stream()?;
+    let dict = &stream.dict;
+    let width = dimension(dict, b"Width")?;
+    let height = dimension(dict, b"Height")?;
+    let filters: Vec<&[u8]> = stream.filters().unwrap_or_default();
+
+    match filters.last().copied() {
+        Some(b"DCTDecode") => {
+            let data = if filters.len() > 1 {
+                // Only the outer encodings are undone here; the JPEG itself goes to image.
+                let mut outer = stream.clone();
+                outer.dict.set("Filter", Object::Array(
+                    filters[..filters.len() - 1].iter().map(|f| Object::Name(f.to_vec())).collect(),
+                ));
+                outer.decompressed_content()?
+            } else {
+                stream.content.clone()
+            };
+            Ok(image::load_from_memory(&data).context("Could not decode embedded JPEG")?)
+        }
+        Some(b"FlateDecode") | Some(b"LZWDecode") | Some(b"ASCII85Decode") | None => {
+            let data = if filters.is_empty() { stream.content.clone() } else { stream.decompressed_content()? };
+            let bits = dict.get(b"BitsPerComponent").and_then(Object::as_i64).unwrap_or(1);
+            // A [1 0] decode array swaps black and white.
+            let inverted = dict.get(b"Decode").and_then(Object::as_array)
+                .is_ok_and(|decode| decode.first().and_then(|v| v.as_float().ok()) == Some(1.0));
+            raw_to_image(width, height, bits, &data, inverted)
+        }
+        Some(filter) => anyhow::bail!("Unsupported image filter: {}", String::from_utf8_lossy(filter)),
+    }
+}
+
+fn raw_to_image(width: u32, height: u32, bits: i64, data: &[u8], inverted: bool) -> AppResult<DynamicImage> {
+    let pixels = width as usize * height as usize;
+    let image = match bits {
+        1 => {
+            let stride = width.div_ceil(8) as usize;
+            if data.len() < stride * height as usize {
+                anyhow::bail!("Truncated image data");
+            }
+            DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, y| {
+                let byte = data[y as usize * stride + x as usize / 8];
+                let set = byte & (0x80 >> (x % 8)) != 0;
+                image::Luma([if set != inverted { 255 } else { 0 }])
+            }))
+        }
+        // The number of colour components is inferred from the data length, which also
+        // covers ICC-based and calibrated colour spaces.
+        8 if data.len() >= pixels * 4 => {
+            let rgb = data.chunks_exact(4)
+                .take(pixels)
+                .flat_map(|cmyk| {
+                    let k = 255 - cmyk[3] as u32;
+                    cmyk[..3].iter().map(move |&c| ((255 - c as u32) * k / 255) as u8)
+                })
+                .collect();
+            DynamicImage::ImageRgb8(RgbImage::from_raw(width, height, rgb).context("Truncated image data")?)
+        }
+        8 if data.len() >= pixels * 3 => {
+            DynamicImage::ImageRgb8(RgbImage::from_raw(width, height, data[..pixels * 3].to_vec())
+                .context("Truncated image data")?)
+        }
+        8 => {
+            let mut gray = GrayImage::from_raw(width, height, data.get(..pixels).context("Truncated image data")?.to_vec())
+                .context("Truncated image data")?;
+            if inverted {
+                image::imageops::invert(&mut gray);
+            }
+            DynamicImage::ImageLuma8(gray)
+        }
+        other => anyhow::bail!("Unsupported bits per component: {}", other),
+    };
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use lopdf::{Stream, dictionary};
+
+    use super::*;
+    use crate::decode::{DecodeOptions, decode_pages};
+    use crate::encode::{EncodeOptions, encode, render_pdf};
+
+    fn temp_pdf(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("{}-{}-{}.pdf", env!("CARGO_PKG_NAME"), name, std::process::id()))
+    }
+
+    // Dark modules as one-unit squares, scaled and placed by `cm`, the way many generators
+    // write them.
+    fn module_content(text: &str) -> String {
+        let symbol = encode(text, &EncodeOptions::default()).unwrap();
+        let width = symbol.width();
+        let mut content = String::from("q 0.2 0.2 0.6 rg 4 0 0 4 40 40 cm\n");
+        for y in 0..width {
+            for x in 0..width {
+                if symbol.is_dark(x, y) {
+                    content += &format!("{} {} 1 1 re\n", x, width - 1 - y);
+                }
+            }
+        }
+        content + "f Q\n"
+    }
+
+    fn save_document(path: &Path, pages: Vec<(String, Option<Stream>)>) {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let mut kids = Vec::new();
+        for (content, image) in pages {
+            let content_id = document.add_object(Stream::new(dictionary! {}, content.into_bytes()));
+            let mut page = dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "Contents" => content_id,
+                "MediaBox" => vec![0.into(), 0.into(), 200.into(), 200.into()],
+            };
+            if let Some(image) = image {
+                let image_id = document.add_object(image);
+                page.set("Resources", dictionary! { "XObject" => dictionary! { "Im1" => image_id } });
+            }
+            kids.push(document.add_object(page).into());
+        }
+        let count = kids.len() as i64;
+        document.objects.insert(pages_id, Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => kids, "Count" => count }));
+        let catalog_id = document.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        document.trailer.set("Root", catalog_id);
+        document.save(path).unwrap();
+    }
+
+    #[test]
+    fn decodes_generated_vector_pdf() {
+        let options = EncodeOptions::default();
+        let path = temp_pdf("generated");
+        std::fs::write(&path, render_pdf(&encode("vector page", &options).unwrap(), &options)).unwrap();
+        let report = decode_pages(open(&path).unwrap(), &DecodeOptions::default()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(report.results.len(), 1);
+        assert_eq!(report.results[0].content.as_str(), "vector page");
+        assert_eq!(report.results[0].page, Some(1));
+    }
+
+    #[test]
+    fn reports_unreadable_pages_and_continues() {
+        let bad_image = Stream::new(dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => -1,
+            "Height" => 10,
+            "BitsPerComponent" => 8,
+        }, vec![0; 10]);
+        let path = temp_pdf("mixed");
+        save_document(&path, vec![
+            ("BT /F1 12 Tf (only text) Tj ET".to_string(), None),
+            ("q 100 0 0 100 50 50 cm /Im1 Do Q".to_string(), Some(bad_image)),
+            (module_content("third page"), None),
+        ]);
+        let report = decode_pages(open(&path).unwrap(), &DecodeOptions::default()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(report.results.len(), 1);
+        assert_eq!(report.results[0].content.as_str(), "third page");
+        assert_eq!(report.results[0].page, Some(3));
+        assert_eq!(report.warnings.len(), 2);
+        assert!(report.warnings[0].contains("page 1"));
+        assert!(report.warnings[1].contains("Width is out of range"));
+    }
+}
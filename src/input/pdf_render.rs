@@ -0,0 +1,422 @@
+use image::{GrayImage, Luma};
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+use crate::AppResult;
+use super::pdf_pages::decode_image_xobject;
+
+// Pages are rendered at 200 dpi, but never so small that a code printed on a tiny page
+// loses its modules, nor so large that a poster-sized page exhausts memory.
+const DPI: f32 = 200.0;
+const MIN_SIDE: f32 = 600.0;
+const MAX_SIDE: f32 = 4000.0;
+// Form XObjects may nest; deeper nesting than this is treated as a cycle.
+const MAX_FORM_DEPTH: usize = 8;
+// Each Bézier curve is approximated by this many straight segments.
+const CURVE_STEPS: usize = 8;
+
+// Affine transform [a b c d e f], mapping (x, y) to (a x + c y + e, b x + d y + f).
+type Matrix = [f32; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+// `first`, then `second`.
+fn multiply(first: &Matrix, second: &Matrix) -> Matrix {
+    [
+        first[0] * second[0] + first[1] * second[2],
+        first[0] * second[1] + first[1] * second[3],
+        first[2] * second[0] + first[3] * second[2],
+        first[2] * second[1] + first[3] * second[3],
+        first[4] * second[0] + first[5] * second[2] + second[4],
+        first[4] * second[1] + first[5] * second[3] + second[5],
+    ]
+}
+
+fn apply(m: &Matrix, x: f32, y: f32) -> (f32, f32) {
+    (m[0] * x + m[2] * y + m[4], m[1] * x + m[3] * y + m[5])
+}
+
+fn invert(m: &Matrix) -> Option<Matrix> {
+    let det = m[0] * m[3] - m[1] * m[2];
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let (a, b, c, d) = (m[3] / det, -m[1] / det, -m[2] / det, m[0] / det);
+    Some([a, b, c, d, -(m[4] * a + m[5] * c), -(m[4] * b + m[5] * d)])
+}
+
+#[derive(Clone)]
+struct State {
+    ctm: Matrix,
+    fill: u8,
+    stroke: u8,
+    line_width: f32,
+    projecting_caps: bool,
+}
+
+#[derive(Default)]
+struct Path {
+    subpaths: Vec<Vec<(f32, f32)>>,
+    closed: Vec<bool>,
+}
+
+struct Renderer<'a> {
+    document: &'a Document,
+    canvas: GrayImage,
+    state: State,
+    saved: Vec<State>,
+    path: Path,
+    painted: bool,
+}
+
+/// Whether `content` paints anything besides images and text, such as a code drawn as
+/// filled rectangles.
+pub(super) fn has_paths(content: &Content) -> bool {
+    content.operations.iter().any(|op| matches!(
+        op.operator.as_str(),
+        "f" | "F" | "f*" | "B" | "B*" | "b" | "b*" | "S" | "s"
+    ))
+}
+
+/// Rasterizes the filled and stroked paths and the images of a page to grayscale.
+///
+/// Text, shadings, clipping and transparency are not rendered. Returns `None` when the
+/// page paints nothing this renderer understands.
+pub(super) fn render(document: &Document, page_id: ObjectId, content: &Content) -> AppResult<Option<GrayImage>> {
+    let page = document.get_dictionary(page_id)?;
+    let [x0, y0, x1, y1] = page_box(document, page_id)?;
+    let (width, height) = (x1 - x0, y1 - y0);
+    let scale = (DPI / 72.0)
+        .max(MIN_SIDE / width.min(height))
+        .min(MAX_SIDE / width.max(height));
+    let pixels = |points: f32| (points * scale).round().max(1.0) as u32;
+
+    let mut renderer = Renderer {
+        document,
+        canvas: GrayImage::from_pixel(pixels(width), pixels(height), Luma([255])),
+        state: State {
+            // PDF space has its origin at the bottom left; the image at the top left.
+            ctm: [scale, 0.0, 0.0, -scale, -x0 * scale, y1 * scale],
+            fill: 0,
+            stroke: 0,
+            line_width: 1.0,
+            projecting_caps: false,
+        },
+        saved: Vec::new(),
+        path: Path::default(),
+        painted: false,
+    };
+    let resources = inherited(document, page, b"Resources").and_then(|r| r.as_dict().ok());
+    renderer.run(content, resources, 0);
+    Ok(renderer.painted.then_some(renderer.canvas))
+}
+
+// CropBox, or else MediaBox, as [left, bottom, right, top]. Both may be inherited from
+// the page tree.
+fn page_box(document: &Document, page_id: ObjectId) -> AppResult<[f32; 4]> {
+    let page = document.get_dictionary(page_id)?;
+    let rect = inherited(document, page, b"CropBox")
+        .or_else(|| inherited(document, page, b"MediaBox"))
+        .and_then(|rect| rect.as_array().ok())
+        .map(|rect| rect.iter().filter_map(|v| document.dereference(v).ok()?.1.as_float().ok()).collect::<Vec<_>>());
+    let [a, b, c, d] = match rect.as_deref() {
+        Some(&[a, b, c, d]) => [a, b, c, d],
+        // US Letter, the default when a page does not say.
+        _ => [0.0, 0.0, 612.0, 792.0],
+    };
+    let rect = [a.min(c), b.min(d), a.max(c), b.max(d)];
+    if !(rect[2] - rect[0] >= 1.0 && rect[3] - rect[1] >= 1.0) {
+        anyhow::bail!("Page has an empty page box");
+    }
+    Ok(rect)
+}
+
+fn inherited<'a>(document: &'a Document, mut node: &'a Dictionary, key: &[u8]) -> Option<&'a Object> {
+    for _ in 0..32 {
+        if let Ok(value) = node.get_deref(key, document) {
+            return Some(value);
+        }
+        node = node.get_deref(b"Parent", document).ok()?.as_dict().ok()?;
+    }
+    None
+}
+
+fn luminance(operands: &[Object]) -> Option<u8> {
+    let values: Vec<f32> = operands.iter().filter_map(|v| v.as_float().ok()).collect();
+    let value = match values[..] {
+        [gray] => gray,
+        [r, g, b] => 0.299 * r + 0.587 * g + 0.114 * b,
+        [c, m, y, k] => {
+            let ink = |v: f32| (1.0 - v) * (1.0 - k);
+            0.299 * ink(c) + 0.587 * ink(m) + 0.114 * ink(y)
+        }
+        _ => return None,
+    };
+    Some((value.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+impl Renderer<'_> {
+    fn run(&mut self, content: &Content, resources: Option<&Dictionary>, depth: usize) {
+        let mut current = (0.0, 0.0);
+        for op in &content.operations {
+            let n: Vec<f32> = op.operands.iter().filter_map(|v| v.as_float().ok()).collect();
+            match (op.operator.as_str(), &n[..]) {
+                ("q", _) => self.saved.push(self.state.clone()),
+                ("Q", _) => {
+                    if let Some(state) = self.saved.pop() {
+                        self.state = state;
+                    }
+                }
+                ("cm", &[a, b, c, d, e, f]) => self.state.ctm = multiply(&[a, b, c, d, e, f], &self.state.ctm),
+                ("w", &[width]) => self.state.line_width = width,
+                ("J", &[cap]) => self.state.projecting_caps = cap != 0.0,
+                ("g" | "rg" | "k" | "sc" | "scn", _) => {
+                    if let Some(value) = luminance(&op.operands) {
+                        self.state.fill = value;
+                    }
+                }
+                ("G" | "RG" | "K" | "SC" | "SCN", _) => {
+                    if let Some(value) = luminance(&op.operands) {
+                        self.state.stroke = value;
+                    }
+                }
+                ("m", &[x, y]) => {
+                    current = (x, y);
+                    self.path.subpaths.push(vec![apply(&self.state.ctm, x, y)]);
+                    self.path.closed.push(false);
+                }
+                ("l", &[x, y]) => {
+                    current = (x, y);
+                    self.line_to(apply(&self.state.ctm, x, y));
+                }
+                ("c", &[x1, y1, x2, y2, x3, y3]) => {
+                    self.curve_to(current, (x1, y1), (x2, y2), (x3, y3));
+                    current = (x3, y3);
+                }
+                ("v", &[x2, y2, x3, y3]) => {
+                    self.curve_to(current, current, (x2, y2), (x3, y3));
+                    current = (x3, y3);
+                }
+                ("y", &[x1, y1, x3, y3]) => {
+                    self.curve_to(current, (x1, y1), (x3, y3), (x3, y3));
+                    current = (x3, y3);
+                }
+                ("h", _) => {
+                    if let Some(closed) = self.path.closed.last_mut() {
+                        *closed = true;
+                    }
+                }
+                ("re", &[x, y, w, h]) => {
+                    current = (x, y);
+                    let ctm = self.state.ctm;
+                    self.path.subpaths.push(vec![
+                        apply(&ctm, x, y), apply(&ctm, x + w, y), apply(&ctm, x + w, y + h), apply(&ctm, x, y + h),
+                    ]);
+                    self.path.closed.push(true);
+                }
+                ("f" | "F", _) => self.paint(true, false, false),
+                ("f*", _) => self.paint(true, true, false),
+                ("B", _) => self.paint(true, false, true),
+                ("B*", _) => self.paint(true, true, true),
+                ("b", _) => {
+                    self.path.closed.iter_mut().for_each(|c| *c = true);
+                    self.paint(true, false, true);
+                }
+                ("b*", _) => {
+                    self.path.closed.iter_mut().for_each(|c| *c = true);
+                    self.paint(true, true, true);
+                }
+                ("S", _) => self.paint(false, false, true),
+                ("s", _) => {
+                    self.path.closed.iter_mut().for_each(|c| *c = true);
+                    self.paint(false, false, true);
+                }
+                ("n", _) => self.path = Path::default(),
+                ("Do", _) => {
+                    if let Some(name) = op.operands.first().and_then(|v| v.as_name().ok()) {
+                        self.draw_xobject(name, resources, depth);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn line_to(&mut self, point: (f32, f32)) {
+        match self.path.subpaths.last_mut() {
+            Some(subpath) => subpath.push(point),
+            None => {
+                self.path.subpaths.push(vec![point]);
+                self.path.closed.push(false);
+            }
+        }
+    }
+
+    fn curve_to(&mut self, p0: (f32, f32), p1: (f32, f32), p2: (f32, f32), p3: (f32, f32)) {
+        for step in 1..=CURVE_STEPS {
+            let t = step as f32 / CURVE_STEPS as f32;
+            let u = 1.0 - t;
+            let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+            let x = a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0;
+            let y = a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1;
+            self.line_to(apply(&self.state.ctm, x, y));
+        }
+    }
+
+    fn paint(&mut self, fill: bool, even_odd: bool, stroke: bool) {
+        let path = std::mem::take(&mut self.path);
+        if fill {
+            fill_polygons(&mut self.canvas, &path.subpaths, even_odd, self.state.fill);
+        }
+        if stroke {
+            let outline = self.stroke_outline(&path);
+            fill_polygons(&mut self.canvas, &outline, false, self.state.stroke);
+        }
+        self.painted |= !path.subpaths.is_empty();
+    }
+
+    // Every segment becomes a rectangle as wide as the line; joins are not filled in.
+    fn stroke_outline(&self, path: &Path) -> Vec<Vec<(f32, f32)>> {
+        let ctm = &self.state.ctm;
+        let scale = (ctm[0] * ctm[3] - ctm[1] * ctm[2]).abs().sqrt();
+        // Zero-width lines are drawn one pixel wide.
+        let half = (self.state.line_width * scale).max(1.0) / 2.0;
+        let mut outline = Vec::new();
+        for (subpath, &closed) in path.subpaths.iter().zip(&path.closed) {
+            let closing = subpath.last().zip(subpath.first()).filter(|_| closed).map(|(&last, &first)| (last, first));
+            let segments = subpath.windows(2).map(|w| (w[0], w[1])).chain(closing);
+            for ((ax, ay), (bx, by)) in segments {
+                let length = ((bx - ax).powi(2) + (by - ay).powi(2)).sqrt();
+                if length == 0.0 {
+                    continue;
+                }
+                let (dx, dy) = ((bx - ax) / length * half, (by - ay) / length * half);
+                let (ax, ay, bx, by) = if self.state.projecting_caps {
+                    (ax - dx, ay - dy, bx + dx, by + dy)
+                } else {
+                    (ax, ay, bx, by)
+                };
+                outline.push(vec![(ax - dy, ay + dx), (bx - dy, by + dx), (bx + dy, by - dx), (ax + dy, ay - dx)]);
+            }
+        }
+        outline
+    }
+
+    fn draw_xobject(&mut self, name: &[u8], resources: Option<&Dictionary>, depth: usize) {
+        let Some((id, stream)) = resources
+            .and_then(|r| r.get_deref(b"XObject", self.document).ok()?.as_dict().ok()?.get(name).ok())
+            .and_then(|object| object.as_reference().ok())
+            .and_then(|id| Some((id, self.document.get_object(id).ok()?.as_stream().ok()?)))
+        else {
+            return;
+        };
+        match stream.dict.get(b"Subtype").and_then(Object::as_name) {
+            Ok(b"Image") => self.draw_image(id, &stream.dict),
+            Ok(b"Form") if depth < MAX_FORM_DEPTH => {
+                let Ok(content) = stream.decompressed_content().or_else(|_| Ok::<_, lopdf::Error>(stream.content.clone()))
+                    .and_then(|data| Content::decode(&data))
+                else {
+                    return;
+                };
+                let matrix = stream.dict.get(b"Matrix").and_then(Object::as_array).ok()
+                    .map(|m| m.iter().filter_map(|v| v.as_float().ok()).collect::<Vec<_>>())
+                    .and_then(|m| <[f32; 6]>::try_from(m).ok())
+                    .unwrap_or(IDENTITY);
+                let form_resources = stream.dict.get_deref(b"Resources", self.document)
+                    .and_then(Object::as_dict).ok()
+                    .or(resources);
+                let saved_path = std::mem::take(&mut self.path);
+                self.saved.push(self.state.clone());
+                self.state.ctm = multiply(&matrix, &self.state.ctm);
+                self.run(&content, form_resources, depth + 1);
+                if let Some(state) = self.saved.pop() {
+                    self.state = state;
+                }
+                self.path = saved_path;
+            }
+            _ => {}
+        }
+    }
+
+    // Images fill the unit square of the current transformation, top row first.
+    fn draw_image(&mut self, id: ObjectId, dict: &Dictionary) {
+        let Ok(image) = decode_image_xobject(self.document, id) else {
+            return;
+        };
+        let image = image.to_luma8();
+        let Some(inverse) = invert(&self.state.ctm) else {
+            return;
+        };
+        let stencil = dict.get(b"ImageMask").and_then(Object::as_bool).unwrap_or(false);
+        let corners = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)].map(|(x, y)| apply(&self.state.ctm, x, y));
+        let (width, height) = self.canvas.dimensions();
+        let clamp = |v: f32, max: u32| v.clamp(0.0, max as f32) as u32;
+        let left = clamp(corners.iter().map(|c| c.0).fold(f32::INFINITY, f32::min).floor(), width);
+        let right = clamp(corners.iter().map(|c| c.0).fold(f32::NEG_INFINITY, f32::max).ceil(), width);
+        let top = clamp(corners.iter().map(|c| c.1).fold(f32::INFINITY, f32::min).floor(), height);
+        let bottom = clamp(corners.iter().map(|c| c.1).fold(f32::NEG_INFINITY, f32::max).ceil(), height);
+
+        for y in top..bottom {
+            for x in left..right {
+                let (u, v) = apply(&inverse, x as f32 + 0.5, y as f32 + 0.5);
+                if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+                    continue;
+                }
+                let sx = ((u * image.width() as f32) as u32).min(image.width() - 1);
+                let sy = (((1.0 - v) * image.height() as f32) as u32).min(image.height() - 1);
+                let sample = image.get_pixel(sx, sy)[0];
+                // A stencil mask paints the fill colour where its bits are clear.
+                if !stencil {
+                    self.canvas.put_pixel(x, y, Luma([sample]));
+                } else if sample < 128 {
+                    self.canvas.put_pixel(x, y, Luma([self.state.fill]));
+                }
+            }
+        }
+        self.painted = true;
+    }
+}
+
+// Scanline fill sampled at pixel centres, with the non-zero winding or even-odd rule.
+fn fill_polygons(canvas: &mut GrayImage, polygons: &[Vec<(f32, f32)>], even_odd: bool, value: u8) {
+    let mut edges = Vec::new();
+    for polygon in polygons {
+        for (i, &(ax, ay)) in polygon.iter().enumerate() {
+            let (bx, by) = polygon[(i + 1) % polygon.len()];
+            if ay != by && ax.is_finite() && bx.is_finite() && ay.is_finite() && by.is_finite() {
+                edges.push(if ay < by { (ax, ay, bx, by, 1) } else { (bx, by, ax, ay, -1) });
+            }
+        }
+    }
+    let Some(top) = edges.iter().map(|e| e.1).reduce(f32::min) else {
+        return;
+    };
+    let bottom = edges.iter().map(|e| e.3).fold(top, f32::max);
+    let (width, height) = canvas.dimensions();
+    let first_row = (top - 0.5).ceil().max(0.0) as u32;
+    let last_row = ((bottom - 0.5).ceil().max(0.0) as u32).min(height);
+
+    let mut crossings: Vec<(f32, i32)> = Vec::new();
+    for row in first_row..last_row {
+        let y = row as f32 + 0.5;
+        crossings.clear();
+        crossings.extend(edges.iter()
+            .filter(|&&(_, y0, _, y1, _)| y0 <= y && y < y1)
+            .map(|&(x0, y0, x1, y1, direction)| (x0 + (y - y0) * (x1 - x0) / (y1 - y0), direction)));
+        crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut winding = 0;
+        for pair in crossings.windows(2) {
+            winding += pair[0].1;
+            let inside = if even_odd { winding % 2 != 0 } else { winding != 0 };
+            if !inside {
+                continue;
+            }
+            let start = (pair[0].0 - 0.5).ceil().clamp(0.0, width as f32) as u32;
+            let end = (pair[1].0 - 0.5).ceil().clamp(0.0, width as f32) as u32;
+            for x in start..end {
+                canvas.put_pixel(x, row, Luma([value]));
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use anyhow::Context;
+use image::{DynamicImage, GrayAlphaImage, GrayImage, ImageBuffer, RgbImage, RgbaImage};
+use tiff::ColorType;
+use tiff::decoder::{Decoder, DecodingResult};
+
+use crate::AppResult;
+use super::{Pages, open_buffered};
+
+/// Pages of a multi-page TIFF, or `None` when it holds a single image.
+pub fn open(path: &Path) -> AppResult<Option<Pages>> {
+    let decoder = Decoder::new(open_buffered(path)?)
+        .context(format!("Could not read TIFF file: {}", path.display()))?;
+    if !decoder.more_images() {
+        return Ok(None);
+    }
+    Ok(Some(Box::new(TiffPages { decoder, page: 1, done: false })))
+}
+
+struct TiffPages {
+    decoder: Decoder<BufReader<File>>,
+    page: usize,
+    done: bool,
+}
+
+impl Iterator for TiffPages {
+    type Item = AppResult<(usize, DynamicImage)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let page = self.page;
+        let image = read_page(&mut self.decoder)
+            .context(format!("Could not decode TIFF page {}", page));
+
+        self.page += 1;
+        self.done = image.is_err() || !self.decoder.more_images() || self.decoder.next_image().is_err();
+        Some(image.map(|image| (page, image)))
+    }
+}
+
+fn read_page(decoder: &mut Decoder<BufReader<File>>) -> AppResult<DynamicImage> {
+    let (width, height) = decoder.dimensions()?;
+    let color_type = decoder.colortype()?;
+    let image = match (color_type, decoder.read_image()?) {
+        (ColorType::Gray(1), DecodingResult::U8(data)) => {
+            // Bilevel rows are bit-packed and padded to whole bytes; 1 is white.
+            let stride = width.div_ceil(8) as usize;
+            DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, y| {
+                let byte = data.get(y as usize * stride + x as usize / 8).copied().unwrap_or(0xff);
+                image::Luma([if byte & (0x80 >> (x % 8)) != 0 { 255 } else { 0 }])
+            }))
+        }
+        (ColorType::Gray(8), DecodingResult::U8(data)) => {
+            GrayImage::from_raw(width, height, data).map(DynamicImage::ImageLuma8).context("Truncated image data")?
+        }
+        (ColorType::GrayA(8), DecodingResult::U8(data)) => {
+            GrayAlphaImage::from_raw(width, height, data).map(DynamicImage::ImageLumaA8).context("Truncated image data")?
+        }
+        (ColorType::RGB(8), DecodingResult::U8(data)) => {
+            RgbImage::from_raw(width, height, data).map(DynamicImage::ImageRgb8).context("Truncated image data")?
+        }
+        (ColorType::RGBA(8), DecodingResult::U8(data)) => {
+            RgbaImage::from_raw(width, height, data).map(DynamicImage::ImageRgba8).context("Truncated image data")?
+        }
+        (ColorType::CMYK(8), DecodingResult::U8(data)) => {
+            let rgb = data.chunks_exact(4)
+                .flat_map(|cmyk| {
+                    let k = 255 - cmyk[3] as u32;
+                    cmyk[..3].iter().map(move |&c| ((255 - c as u32) * k / 255) as u8)
+                })
+                .collect();
+            RgbImage::from_raw(width, height, rgb).map(DynamicImage::ImageRgb8).context("Truncated image data")?
+        }
+        (ColorType::Gray(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma16).context("Truncated image data")?
+        }
+        (ColorType::RGB(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb16).context("Truncated image data")?
+        }
+        (ColorType::RGBA(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba16).context("Truncated image data")?
+        }
+        (color_type, _) => anyhow::bail!("Unsupported TIFF color type: {:?}", color_type),
+    };
+    Ok(image)
+}
@@ -0,0 +1,25 @@
+pub mod batch;
+mod bitstream;
+pub mod caption;
+mod charset;
+pub mod content;
+pub mod decode;
+pub mod encode;
+pub mod input;
+pub mod otp;
+pub mod payload;
+pub mod preprocess;
+pub mod safety;
+pub mod structured_append;
+pub mod style;
+pub mod transfer;
+pub mod verify;
+
+pub use decode::{
+    DecodeMode, DecodeOptions, DecodeReport, DecodedQr, decode_frames, decode_image, decode_image_report,
+    decode_pages, decode_path, decode_path_report,
+};
+pub use encode::{EncodeOptions, Symbol, encode};
+pub use structured_append::{AssembledMessage, StructuredAppend, assemble_messages, encode_structured_append};
+
+pub type AppResult<T> = anyhow::Result<T>;
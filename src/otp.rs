@@ -0,0 +1,568 @@
+use std::fmt;
+use anyhow::Context;
+use data_encoding::{BASE32_NOPAD, BASE64};
+use hmac::{Hmac, Mac};
+use hmac::digest::KeyInit;
+use serde::Serialize;
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::AppResult;
+use crate::content::{percent_decode, query_pairs};
+use crate::payload::percent_encode;
+
+/// Whether codes advance with time (TOTP) or with a counter (HOTP).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OtpKind {
+    Totp,
+    Hotp,
+}
+
+/// Hash function of the HMAC the codes are derived from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum OtpAlgorithm {
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl fmt::Display for OtpKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OtpKind::Totp => write!(f, "TOTP"),
+            OtpKind::Hotp => write!(f, "HOTP"),
+        }
+    }
+}
+
+impl fmt::Display for OtpAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OtpAlgorithm::Sha1 => write!(f, "SHA1"),
+            OtpAlgorithm::Sha256 => write!(f, "SHA256"),
+            OtpAlgorithm::Sha512 => write!(f, "SHA512"),
+        }
+    }
+}
+
+/// One authenticator account and its shared secret.
+///
+/// `Debug` output leaves the secret out, so accounts can be logged safely.
+#[derive(Clone)]
+pub struct OtpAccount {
+    pub kind: OtpKind,
+    /// Service the account belongs to; may be empty.
+    pub issuer: String,
+    /// User name or email address of the account.
+    pub account: String,
+    pub secret: Zeroizing<Vec<u8>>,
+    pub algorithm: OtpAlgorithm,
+    pub digits: u32,
+    /// Seconds each TOTP code stays valid.
+    pub period: u64,
+    /// Counter of the next HOTP code.
+    pub counter: u64,
+}
+
+/// Position of one QR code in a multi-code authenticator export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MigrationBatch {
+    /// Shared by every code of one export.
+    pub id: i64,
+    /// Position of this code, from 0.
+    pub index: u32,
+    pub size: u32,
+}
+
+/// Accounts read from one `otpauth://` or `otpauth-migration://` link.
+#[derive(Debug, Clone)]
+pub struct OtpImport {
+    pub accounts: Vec<OtpAccount>,
+    /// Set for authenticator exports that span several QR codes.
+    pub batch: Option<MigrationBatch>,
+}
+
+impl fmt::Debug for OtpAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OtpAccount")
+            .field("kind", &self.kind)
+            .field("issuer", &self.issuer)
+            .field("account", &self.account)
+            .field("secret", &"<redacted>")
+            .field("algorithm", &self.algorithm)
+            .field("digits", &self.digits)
+            .field("period", &self.period)
+            .field("counter", &self.counter)
+            .finish()
+    }
+}
+
+impl OtpAccount {
+    /// "Issuer (account)", or just the account when there is no issuer.
+    pub fn name(&self) -> String {
+        if self.issuer.is_empty() {
+            self.account.clone()
+        } else {
+            format!("{} ({})", self.issuer, self.account)
+        }
+    }
+
+    /// The secret in unpadded base32, as authenticator apps expect it.
+    pub fn secret_base32(&self) -> Zeroizing<String> {
+        Zeroizing::new(BASE32_NOPAD.encode(&self.secret))
+    }
+
+    /// The code valid at `unix_time` for TOTP, or the code for the current counter for HOTP.
+    pub fn code(&self, unix_time: u64) -> Zeroizing<String> {
+        let counter = match self.kind {
+            OtpKind::Totp => unix_time / self.period,
+            OtpKind::Hotp => self.counter,
+        };
+        let digest = match self.algorithm {
+            OtpAlgorithm::Sha1 => hmac_digest::<Hmac<Sha1>>(&self.secret, counter),
+            OtpAlgorithm::Sha256 => hmac_digest::<Hmac<Sha256>>(&self.secret, counter),
+            OtpAlgorithm::Sha512 => hmac_digest::<Hmac<Sha512>>(&self.secret, counter),
+        };
+        // Dynamic truncation (RFC 4226, section 5.3).
+        let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([digest[offset], digest[offset + 1], digest[offset + 2], digest[offset + 3]]) & 0x7fff_ffff;
+        let code = binary as u64 % 10u64.pow(self.digits);
+        Zeroizing::new(format!("{:0width$}", code, width = self.digits as usize))
+    }
+
+    /// Seconds until the TOTP code valid at `unix_time` expires; `None` for HOTP.
+    pub fn seconds_left(&self, unix_time: u64) -> Option<u64> {
+        match self.kind {
+            OtpKind::Totp => Some(self.period - unix_time % self.period),
+            OtpKind::Hotp => None,
+        }
+    }
+
+    /// The account as an `otpauth://` link, the format authenticator apps scan and import.
+    pub fn to_uri(&self) -> Zeroizing<String> {
+        let label = if self.issuer.is_empty() {
+            percent_encode(&self.account)
+        } else {
+            format!("{}:{}", percent_encode(&self.issuer), percent_encode(&self.account))
+        };
+        let mut uri = Zeroizing::new(format!(
+            "otpauth://{}/{}?secret={}",
+            self.kind.to_string().to_lowercase(), label, self.secret_base32().as_str()
+        ));
+        if !self.issuer.is_empty() {
+            uri.push_str(&format!("&issuer={}", percent_encode(&self.issuer)));
+        }
+        uri.push_str(&format!("&algorithm={}&digits={}", self.algorithm, self.digits));
+        match self.kind {
+            OtpKind::Totp => uri.push_str(&format!("&period={}", self.period)),
+            OtpKind::Hotp => uri.push_str(&format!("&counter={}", self.counter)),
+        }
+        uri
+    }
+}
+
+fn hmac_digest<M: Mac + KeyInit>(secret: &[u8], counter: u64) -> Zeroizing<Vec<u8>> {
+    let mut mac = <M as KeyInit>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let mut bytes = mac.finalize().into_bytes();
+    let digest = Zeroizing::new(bytes.to_vec());
+    bytes.as_mut_slice().zeroize();
+    digest
+}
+
+/// Whether `content` is an `otpauth://` or `otpauth-migration://` link.
+pub fn is_otp_link(content: &str) -> bool {
+    let scheme = content.trim().split_once("://").map(|(scheme, _)| scheme.to_ascii_lowercase());
+    matches!(scheme.as_deref(), Some("otpauth" | "otpauth-migration"))
+}
+
+/// Reads the accounts of an `otpauth://totp/...` or `otpauth://hotp/...` link, or of a
+/// Google Authenticator `otpauth-migration://offline?data=...` export.
+pub fn parse_otp(content: &str) -> AppResult<OtpImport> {
+    let content = content.trim();
+    let Some((scheme, rest)) = content.split_once("://") else {
+        anyhow::bail!("Not an otpauth:// or otpauth-migration:// link.");
+    };
+    match scheme.to_ascii_lowercase().as_str() {
+        "otpauth" => Ok(OtpImport { accounts: vec![parse_otpauth(rest)?], batch: None }),
+        "otpauth-migration" => parse_migration(rest),
+        _ => anyhow::bail!("Not an otpauth:// or otpauth-migration:// link."),
+    }
+}
+
+fn parse_otpauth(rest: &str) -> AppResult<OtpAccount> {
+    let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let (kind, label) = path.split_once('/').unwrap_or((path, ""));
+    let kind = match kind.to_ascii_lowercase().as_str() {
+        "totp" => OtpKind::Totp,
+        "hotp" => OtpKind::Hotp,
+        other => anyhow::bail!("Unknown one-time password type: {}", other),
+    };
+    let label = percent_decode(label);
+    let (label_issuer, account) = match label.split_once(':') {
+        Some((issuer, account)) => (issuer.trim().to_string(), account.trim().to_string()),
+        None => (String::new(), label.trim().to_string()),
+    };
+
+    let pairs = Zeroizing::new(query_pairs(query));
+    let value = |name: &str| pairs.iter().find(|(n, _)| n == name).map(|(_, v)| v.trim());
+    let secret = value("secret").filter(|s| !s.is_empty()).context("The otpauth:// link has no secret.")?;
+    let number = |name: &str, default: u64| -> AppResult<u64> {
+        match value(name) {
+            Some(v) => v.parse().context(format!("Expected a number for {}, got: {}", name, v)),
+            None => Ok(default),
+        }
+    };
+
+    let account = OtpAccount {
+        kind,
+        issuer: value("issuer").map(str::to_string).filter(|i| !i.is_empty()).unwrap_or(label_issuer),
+        account,
+        secret: decode_base32(secret)?,
+        algorithm: match value("algorithm").map(str::to_ascii_uppercase).as_deref() {
+            None | Some("SHA1") => OtpAlgorithm::Sha1,
+            Some("SHA256") => OtpAlgorithm::Sha256,
+            Some("SHA512") => OtpAlgorithm::Sha512,
+            Some(other) => anyhow::bail!("Unsupported OTP algorithm: {}", other),
+        },
+        digits: number("digits", 6)? as u32,
+        period: number("period", 30)?,
+        counter: number("counter", 0)?,
+    };
+    check_account(account)
+}
+
+fn check_account(account: OtpAccount) -> AppResult<OtpAccount> {
+    if !(6..=10).contains(&account.digits) {
+        anyhow::bail!("Expected 6 to 10 digits for {}, got: {}", account.name(), account.digits);
+    }
+    if account.period == 0 {
+        anyhow::bail!("The code period of {} must be at least one second.", account.name());
+    }
+    if account.secret.is_empty() {
+        anyhow::bail!("The secret of {} is empty.", account.name());
+    }
+    Ok(account)
+}
+
+// Secrets are often written in lower case, grouped with spaces or padded.
+fn decode_base32(secret: &str) -> AppResult<Zeroizing<Vec<u8>>> {
+    let normalized: Zeroizing<String> = Zeroizing::new(secret.chars()
+        .filter(|c| !c.is_whitespace() && *c != '=' && *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect());
+    BASE32_NOPAD.decode(normalized.as_bytes())
+        .map(Zeroizing::new)
+        .map_err(|_| anyhow::anyhow!("The secret is not valid base32."))
+}
+
+// The `data` parameter is a base64 MigrationPayload protobuf message. Its `+` characters
+// are often left unencoded, so they are not read as spaces.
+fn parse_migration(rest: &str) -> AppResult<OtpImport> {
+    let query = rest.split_once('?').map(|(_, query)| query).unwrap_or("");
+    let data = query.split('&')
+        .find_map(|pair| pair.strip_prefix("data="))
+        .context("The otpauth-migration:// link has no data.")?;
+    let data: Zeroizing<String> = Zeroizing::new(percent_decode(data).chars()
+        .filter(|c| !c.is_whitespace())
+        .collect());
+    let payload = Zeroizing::new(BASE64.decode(data.as_bytes())
+        .map_err(|_| anyhow::anyhow!("The authenticator export data is not valid base64."))?);
+
+    let mut accounts = Vec::new();
+    let (mut batch_size, mut batch_index, mut batch_id) = (0u64, 0u64, 0u64);
+    for field in ProtoReader::new(&payload) {
+        match field? {
+            (1, ProtoValue::Bytes(parameters)) => accounts.push(parse_migration_account(parameters)?),
+            (3, ProtoValue::Varint(value)) => batch_size = value,
+            (4, ProtoValue::Varint(value)) => batch_index = value,
+            (5, ProtoValue::Varint(value)) => batch_id = value,
+            _ => {}
+        }
+    }
+    let batch = (batch_size > 1).then_some(MigrationBatch {
+        id: batch_id as i64,
+        index: batch_index as u32,
+        size: batch_size as u32,
+    });
+    Ok(OtpImport { accounts, batch })
+}
+
+fn parse_migration_account(data: &[u8]) -> AppResult<OtpAccount> {
+    let mut secret = Zeroizing::new(Vec::new());
+    let (mut name, mut issuer) = (String::new(), String::new());
+    let (mut algorithm, mut digits, mut kind, mut counter) = (0, 0, 0, 0);
+    for field in ProtoReader::new(data) {
+        match field? {
+            (1, ProtoValue::Bytes(value)) => secret = Zeroizing::new(value.to_vec()),
+            (2, ProtoValue::Bytes(value)) => name = String::from_utf8_lossy(value).into_owned(),
+            (3, ProtoValue::Bytes(value)) => issuer = String::from_utf8_lossy(value).into_owned(),
+            (4, ProtoValue::Varint(value)) => algorithm = value,
+            (5, ProtoValue::Varint(value)) => digits = value,
+            (6, ProtoValue::Varint(value)) => kind = value,
+            (7, ProtoValue::Varint(value)) => counter = value,
+            _ => {}
+        }
+    }
+    // Names usually repeat the issuer as an "Issuer:account" prefix.
+    let account = match name.split_once(':') {
+        Some((prefix, account)) if issuer.is_empty() || prefix.trim() == issuer => {
+            if issuer.is_empty() {
+                issuer = prefix.trim().to_string();
+            }
+            account.trim().to_string()
+        }
+        _ => name.trim().to_string(),
+    };
+
+    let account = OtpAccount {
+        kind: if kind == 1 { OtpKind::Hotp } else { OtpKind::Totp },
+        algorithm: match algorithm {
+            0 | 1 => OtpAlgorithm::Sha1,
+            2 => OtpAlgorithm::Sha256,
+            3 => OtpAlgorithm::Sha512,
+            4 => anyhow::bail!("{} uses MD5, which is not supported.", account),
+            other => anyhow::bail!("{} uses an unknown algorithm ({}).", account, other),
+        },
+        digits: if digits == 2 { 8 } else { 6 },
+        period: 30,
+        counter,
+        issuer,
+        account,
+        secret,
+    };
+    check_account(account)
+}
+
+enum ProtoValue<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+// Minimal protobuf wire-format reader yielding (field number, value) pairs.
+struct ProtoReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ProtoReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        ProtoReader { data }
+    }
+
+    fn varint(&mut self) -> AppResult<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self.data.split_first().context("The authenticator export data is truncated.")?;
+            self.data = rest;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        anyhow::bail!("The authenticator export data is malformed.")
+    }
+
+    fn take(&mut self, length: usize) -> AppResult<&'a [u8]> {
+        if length > self.data.len() {
+            anyhow::bail!("The authenticator export data is truncated.");
+        }
+        let (value, rest) = self.data.split_at(length);
+        self.data = rest;
+        Ok(value)
+    }
+
+    fn field(&mut self) -> AppResult<(u64, ProtoValue<'a>)> {
+        let key = self.varint()?;
+        let value = match key & 7 {
+            0 => ProtoValue::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                ProtoValue::Fixed
+            }
+            2 => {
+                let length = self.varint()? as usize;
+                ProtoValue::Bytes(self.take(length)?)
+            }
+            5 => {
+                self.take(4)?;
+                ProtoValue::Fixed
+            }
+            _ => anyhow::bail!("The authenticator export data is malformed."),
+        };
+        Ok((key >> 3, value))
+    }
+}
+
+impl<'a> Iterator for ProtoReader<'a> {
+    type Item = AppResult<(u64, ProtoValue<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let field = self.field();
+        if field.is_err() {
+            self.data = &[];
+        }
+        Some(field)
+    }
+}
+
+/// One `otpauth://` link per line, the plain-text import format of most authenticator apps.
+pub fn export_uris(accounts: &[OtpAccount]) -> Zeroizing<String> {
+    let mut output = Zeroizing::new(String::new());
+    for account in accounts {
+        output.push_str(&account.to_uri());
+        output.push('\n');
+    }
+    output
+}
+
+#[derive(Serialize)]
+struct AndOtpEntry<'a> {
+    secret: Zeroizing<String>,
+    issuer: &'a str,
+    label: &'a str,
+    digits: u32,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    algorithm: OtpAlgorithm,
+    thumbnail: &'static str,
+    last_used: u64,
+    used_frequency: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    period: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    counter: Option<u64>,
+    tags: [&'static str; 0],
+}
+
+/// The unencrypted andOTP JSON backup format, which Aegis, 2FAS and others import.
+pub fn export_andotp(accounts: &[OtpAccount]) -> AppResult<Zeroizing<String>> {
+    let entries: Vec<AndOtpEntry> = accounts.iter()
+        .map(|account| AndOtpEntry {
+            secret: account.secret_base32(),
+            issuer: &account.issuer,
+            label: &account.account,
+            digits: account.digits,
+            kind: match account.kind {
+                OtpKind::Totp => "TOTP",
+                OtpKind::Hotp => "HOTP",
+            },
+            algorithm: account.algorithm,
+            thumbnail: "Default",
+            last_used: 0,
+            used_frequency: 0,
+            period: (account.kind == OtpKind::Totp).then_some(account.period),
+            counter: (account.kind == OtpKind::Hotp).then_some(account.counter),
+            tags: [],
+        })
+        .collect();
+    Ok(Zeroizing::new(serde_json::to_string_pretty(&entries)
+        .context("Could not convert accounts to JSON format.")?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(kind: OtpKind, algorithm: OtpAlgorithm, secret: &[u8], digits: u32) -> OtpAccount {
+        OtpAccount {
+            kind,
+            issuer: String::new(),
+            account: "test".to_string(),
+            secret: Zeroizing::new(secret.to_vec()),
+            algorithm,
+            digits,
+            period: 30,
+            counter: 0,
+        }
+    }
+
+    #[test]
+    fn hotp_matches_rfc_4226() {
+        let expected = ["755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583", "399871", "520489"];
+        let mut hotp = account(OtpKind::Hotp, OtpAlgorithm::Sha1, b"12345678901234567890", 6);
+        for (counter, code) in expected.into_iter().enumerate() {
+            hotp.counter = counter as u64;
+            assert_eq!(hotp.code(0).as_str(), code);
+        }
+    }
+
+    #[test]
+    fn totp_matches_rfc_6238() {
+        let sha1 = account(OtpKind::Totp, OtpAlgorithm::Sha1, b"12345678901234567890", 8);
+        let sha256 = account(OtpKind::Totp, OtpAlgorithm::Sha256, b"12345678901234567890123456789012", 8);
+        let sha512 = account(
+            OtpKind::Totp, OtpAlgorithm::Sha512,
+            b"1234567890123456789012345678901234567890123456789012345678901234", 8,
+        );
+        let vectors = [
+            (59, "94287082", "46119246", "90693936"),
+            (1111111109, "07081804", "68084774", "25091201"),
+            (1111111111, "14050471", "67062674", "99943326"),
+            (1234567890, "89005924", "91819424", "93441116"),
+            (2000000000, "69279037", "90698825", "38618901"),
+            (20000000000, "65353130", "77737706", "47863826"),
+        ];
+        for (time, code_sha1, code_sha256, code_sha512) in vectors {
+            assert_eq!(sha1.code(time).as_str(), code_sha1, "SHA1 at {}", time);
+            assert_eq!(sha256.code(time).as_str(), code_sha256, "SHA256 at {}", time);
+            assert_eq!(sha512.code(time).as_str(), code_sha512, "SHA512 at {}", time);
+        }
+        assert_eq!(sha1.seconds_left(59), Some(1));
+    }
+
+    #[test]
+    fn parses_otpauth_link() {
+        let import = parse_otp("otpauth://totp/Example:alice%40google.com?secret=jbsw y3dp ehpk3pxp&issuer=Example&digits=8").unwrap();
+        let account = &import.accounts[0];
+        assert_eq!(account.kind, OtpKind::Totp);
+        assert_eq!(account.issuer, "Example");
+        assert_eq!(account.account, "alice@google.com");
+        assert_eq!(account.secret.as_slice(), b"Hello!\xde\xad\xbe\xef");
+        assert_eq!(account.digits, 8);
+        assert_eq!(
+            account.to_uri().as_str(),
+            "otpauth://totp/Example:alice%40google.com?secret=JBSWY3DPEHPK3PXP&issuer=Example&algorithm=SHA1&digits=8&period=30",
+        );
+    }
+
+    #[test]
+    fn parses_migration_export() {
+        // Two accounts: a SHA1 TOTP key named "Example:alice@google.com" and an 8-digit
+        // SHA256 HOTP key at counter 5; the second code of a two-code export, batch 12345.
+        let import = parse_otp(concat!(
+            "otpauth-migration://offline?data=CjUKCkhlbGxvId6tvu8SGEV4YW1wbGU6YWxpY2VAZ29vZ2xlLmNvbRoHRXhh",
+            "bXBsZSABKAEwAgojChQxMjM0NTY3ODkwMTIzNDU2Nzg5MBIDYm9iIAIoAjABOAUQARgCIAEouWA%3D",
+        )).unwrap();
+        assert_eq!(import.batch, Some(MigrationBatch { id: 12345, index: 1, size: 2 }));
+        let [totp, hotp] = &import.accounts[..] else {
+            panic!("expected two accounts, got {}", import.accounts.len());
+        };
+        assert_eq!((totp.kind, totp.algorithm, totp.digits), (OtpKind::Totp, OtpAlgorithm::Sha1, 6));
+        assert_eq!((totp.issuer.as_str(), totp.account.as_str()), ("Example", "alice@google.com"));
+        assert_eq!(totp.secret_base32().as_str(), "JBSWY3DPEHPK3PXP");
+        assert_eq!((hotp.kind, hotp.algorithm, hotp.digits, hotp.counter), (OtpKind::Hotp, OtpAlgorithm::Sha256, 8, 5));
+        assert_eq!(hotp.account, "bob");
+        assert_eq!(hotp.secret.as_slice(), b"12345678901234567890");
+    }
+
+    #[test]
+    fn rejects_truncated_migration_export() {
+        assert!(parse_otp("otpauth-migration://offline?data=CjUKCkhlbGxv").is_err());
+    }
+
+    #[test]
+    fn debug_redacts_secret() {
+        let account = account(OtpKind::Totp, OtpAlgorithm::Sha1, b"Hello!", 6);
+        let debug = format!("{:?}", account);
+        assert!(debug.contains("<redacted>"));
+        assert!(!debug.contains("72, 101"), "{}", debug);
+    }
+}
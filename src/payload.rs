@@ -0,0 +1,533 @@
+use std::fmt;
+use std::str::FromStr;
+use zeroize::Zeroizing;
+
+use crate::AppResult;
+
+/// Structured content that phone scanners turn into an action, such as joining a network.
+#[derive(Clone)]
+pub enum Payload {
+    Wifi(Wifi),
+    /// Contact card as a vCard 3.0.
+    VCard(Contact),
+    /// Contact card in the shorter MeCard format, for smaller codes.
+    MeCard(Contact),
+    Geo(Geo),
+    Sms(Sms),
+    Email(Email),
+    Event(Event),
+}
+
+/// Encryption of a WiFi network.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WifiSecurity {
+    /// WPA, WPA2 or WPA3 personal.
+    #[default]
+    Wpa,
+    Wep,
+    /// An open network without a password.
+    None,
+}
+
+#[derive(Clone, Default)]
+pub struct Wifi {
+    pub ssid: String,
+    pub password: Zeroizing<String>,
+    pub security: WifiSecurity,
+    /// Whether the network does not broadcast its name.
+    pub hidden: bool,
+}
+
+/// A person or business; empty optional fields are left out.
+#[derive(Debug, Clone, Default)]
+pub struct Contact {
+    pub first_name: String,
+    pub last_name: String,
+    pub organization: Option<String>,
+    pub title: Option<String>,
+    pub phone: Option<String>,
+    pub email: Option<String>,
+    pub address: Option<String>,
+    pub url: Option<String>,
+    pub note: Option<String>,
+}
+
+/// A location in WGS 84 coordinates.
+#[derive(Debug, Clone, Default)]
+pub struct Geo {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Place name shown by map apps that support it.
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Sms {
+    pub number: String,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Email {
+    pub to: String,
+    pub subject: Option<String>,
+    pub body: Option<String>,
+}
+
+/// A calendar entry, written as an iCalendar `VEVENT`.
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub summary: String,
+    pub start: EventTime,
+    pub end: Option<EventTime>,
+    pub location: Option<String>,
+    pub description: Option<String>,
+}
+
+/// A date, or a date and time of day, in local time unless marked as UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    /// Hour, minute and second; `None` for an all-day event.
+    pub time: Option<(u8, u8, u8)>,
+    pub utc: bool,
+}
+
+impl Payload {
+    /// Checks the required fields and returns the text to encode, with every value escaped
+    /// for its format.
+    pub fn build(&self) -> AppResult<Zeroizing<String>> {
+        let text = match self {
+            Payload::Wifi(wifi) => wifi.build()?,
+            Payload::VCard(contact) => Zeroizing::new(contact.build_vcard()?),
+            Payload::MeCard(contact) => Zeroizing::new(contact.build_mecard()?),
+            Payload::Geo(geo) => Zeroizing::new(geo.build()?),
+            Payload::Sms(sms) => Zeroizing::new(sms.build()?),
+            Payload::Email(email) => Zeroizing::new(email.build()?),
+            Payload::Event(event) => Zeroizing::new(event.build()?),
+        };
+        Ok(text)
+    }
+}
+
+impl Wifi {
+    fn build(&self) -> AppResult<Zeroizing<String>> {
+        if self.ssid.is_empty() {
+            anyhow::bail!("The network name (SSID) is empty.");
+        }
+        let mut text = Zeroizing::new(format!("WIFI:T:{};S:{};", self.security, escape(&self.ssid, "\\;,\":")));
+        match self.security {
+            WifiSecurity::None => {}
+            _ if self.password.is_empty() => anyhow::bail!("{} networks need a password.", self.security),
+            _ => {
+                text.push_str("P:");
+                text.push_str(&Zeroizing::new(escape(&self.password, "\\;,\":")));
+                text.push(';');
+            }
+        }
+        if self.hidden {
+            text.push_str("H:true;");
+        }
+        text.push(';');
+        Ok(text)
+    }
+}
+
+impl Contact {
+    fn check(&self) -> AppResult<()> {
+        if self.first_name.is_empty() && self.last_name.is_empty() && self.organization.is_none() {
+            anyhow::bail!("A contact needs a name or an organization.");
+        }
+        Ok(())
+    }
+
+    fn build_vcard(&self) -> AppResult<String> {
+        self.check()?;
+        let full_name = [&self.first_name, &self.last_name].iter()
+            .filter(|part| !part.is_empty())
+            .map(|part| part.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut lines = vec![
+            "BEGIN:VCARD".to_string(),
+            "VERSION:3.0".to_string(),
+            format!("N:{};{};;;", escape_text(&self.last_name), escape_text(&self.first_name)),
+            format!("FN:{}", escape_text(if full_name.is_empty() { self.organization.as_deref().unwrap_or_default() } else { &full_name })),
+        ];
+        let fields = [
+            ("ORG", &self.organization),
+            ("TITLE", &self.title),
+            ("TEL", &self.phone),
+            ("EMAIL", &self.email),
+            ("NOTE", &self.note),
+        ];
+        lines.extend(fields.iter().filter_map(|(name, value)| Some(format!("{}:{}", name, escape_text(value.as_deref()?)))));
+        if let Some(address) = &self.address {
+            // The whole address goes in the street component.
+            lines.push(format!("ADR:;;{};;;;", escape_text(address)));
+        }
+        if let Some(url) = &self.url {
+            lines.push(format!("URL:{}", url));
+        }
+        lines.push("END:VCARD".to_string());
+        Ok(lines.join("\r\n"))
+    }
+
+    fn build_mecard(&self) -> AppResult<String> {
+        self.check()?;
+        let mut text = String::from("MECARD:");
+        if !self.first_name.is_empty() || !self.last_name.is_empty() {
+            let name = match (self.last_name.is_empty(), self.first_name.is_empty()) {
+                (false, false) => format!("{},{}", escape_mecard(&self.last_name), escape_mecard(&self.first_name)),
+                (false, true) => escape_mecard(&self.last_name),
+                _ => escape_mecard(&self.first_name),
+            };
+            text.push_str(&format!("N:{};", name));
+        }
+        let fields = [
+            ("ORG", &self.organization),
+            ("TEL", &self.phone),
+            ("EMAIL", &self.email),
+            ("ADR", &self.address),
+            ("URL", &self.url),
+            ("NOTE", &self.note),
+        ];
+        for (name, value) in fields {
+            if let Some(value) = value {
+                text.push_str(&format!("{}:{};", name, escape_mecard(value)));
+            }
+        }
+        text.push(';');
+        Ok(text)
+    }
+}
+
+impl Geo {
+    fn build(&self) -> AppResult<String> {
+        if !(-90.0..=90.0).contains(&self.latitude) {
+            anyhow::bail!("Latitude must be between -90 and 90, got: {}", self.latitude);
+        }
+        if !(-180.0..=180.0).contains(&self.longitude) {
+            anyhow::bail!("Longitude must be between -180 and 180, got: {}", self.longitude);
+        }
+        let mut text = format!("geo:{},{}", self.latitude, self.longitude);
+        if let Some(label) = &self.label {
+            text.push_str(&format!("?q={}", percent_encode(label)));
+        }
+        Ok(text)
+    }
+}
+
+impl Sms {
+    fn build(&self) -> AppResult<String> {
+        let number = check_phone(&self.number)?;
+        // Everything after the second colon is the message, so it needs no escaping.
+        Ok(format!("SMSTO:{}:{}", number, self.message.as_deref().unwrap_or_default()))
+    }
+}
+
+impl Email {
+    fn build(&self) -> AppResult<String> {
+        let to = self.to.trim();
+        if !to.contains('@') || to.chars().any(char::is_whitespace) {
+            anyhow::bail!("Expected an email address, got: {}", self.to);
+        }
+        let mut text = format!("mailto:{}", percent_encode_with(to, "@"));
+        let query: Vec<String> = [("subject", &self.subject), ("body", &self.body)].iter()
+            .filter_map(|(name, value)| Some(format!("{}={}", name, percent_encode(value.as_deref()?))))
+            .collect();
+        if !query.is_empty() {
+            text.push('?');
+            text.push_str(&query.join("&"));
+        }
+        Ok(text)
+    }
+}
+
+impl Event {
+    fn build(&self) -> AppResult<String> {
+        if self.summary.is_empty() {
+            anyhow::bail!("An event needs a summary.");
+        }
+        if let Some(end) = self.end {
+            if end.time.is_some() != self.start.time.is_some() {
+                anyhow::bail!("Start and end must both be dates or both be times.");
+            }
+            if end.utc != self.start.utc {
+                anyhow::bail!("Start and end must both be in UTC or both in local time.");
+            }
+            if end.sort_key() < self.start.sort_key() {
+                anyhow::bail!("The event ends before it starts.");
+            }
+        }
+        let mut lines = vec![
+            "BEGIN:VEVENT".to_string(),
+            format!("SUMMARY:{}", escape_text(&self.summary)),
+            self.start.property("DTSTART"),
+        ];
+        lines.extend(self.end.map(|end| end.property("DTEND")));
+        lines.extend(self.location.as_deref().map(|location| format!("LOCATION:{}", escape_text(location))));
+        lines.extend(self.description.as_deref().map(|description| format!("DESCRIPTION:{}", escape_text(description))));
+        lines.push("END:VEVENT".to_string());
+        Ok(lines.join("\r\n"))
+    }
+}
+
+impl EventTime {
+    fn property(self, name: &str) -> String {
+        if self.time.is_some() {
+            format!("{}:{}", name, self)
+        } else {
+            format!("{};VALUE=DATE:{}", name, self)
+        }
+    }
+
+    fn sort_key(self) -> (u16, u8, u8, (u8, u8, u8)) {
+        (self.year, self.month, self.day, self.time.unwrap_or_default())
+    }
+}
+
+// The way iCalendar writes times, such as `20261016T143000Z`.
+impl fmt::Display for EventTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}{:02}{:02}", self.year, self.month, self.day)?;
+        if let Some((hour, minute, second)) = self.time {
+            write!(f, "T{:02}{:02}{:02}{}", hour, minute, second, if self.utc { "Z" } else { "" })?;
+        }
+        Ok(())
+    }
+}
+
+// Accepts `2026-10-16`, `2026-10-16 14:30`, `2026-10-16T14:30:00Z` or the compact
+// iCalendar form `20261016T143000Z`.
+impl FromStr for EventTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Expected a date like 2026-10-16 or 2026-10-16 14:30, got: {}", s);
+        let value = s.trim();
+        let (value, utc) = match value.strip_suffix(['Z', 'z']) {
+            Some(value) => (value, true),
+            None => (value, false),
+        };
+        let (date, time) = match value.split_once(['T', 't', ' ']) {
+            Some((date, time)) => (date, Some(time)),
+            None => (value, None),
+        };
+
+        let date = date.replace('-', "");
+        if date.len() != 8 || !date.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let year: u16 = date[..4].parse().map_err(|_| invalid())?;
+        let month: u8 = date[4..6].parse().map_err(|_| invalid())?;
+        let day: u8 = date[6..].parse().map_err(|_| invalid())?;
+        if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+            return Err(invalid());
+        }
+
+        let time = match time {
+            Some(time) => {
+                let digits = time.replace(':', "");
+                if !matches!(digits.len(), 4 | 6) || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(invalid());
+                }
+                let hour: u8 = digits[..2].parse().map_err(|_| invalid())?;
+                let minute: u8 = digits[2..4].parse().map_err(|_| invalid())?;
+                let second: u8 = digits.get(4..).filter(|s| !s.is_empty()).map_or(Ok(0), str::parse).map_err(|_| invalid())?;
+                if hour > 23 || minute > 59 || second > 60 {
+                    return Err(invalid());
+                }
+                Some((hour, minute, second))
+            }
+            None if utc => return Err(invalid()),
+            None => None,
+        };
+        Ok(EventTime { year, month, day, time, utc })
+    }
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// The names used in the `T:` field of WiFi payloads.
+impl fmt::Display for WifiSecurity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WifiSecurity::Wpa => write!(f, "WPA"),
+            WifiSecurity::Wep => write!(f, "WEP"),
+            WifiSecurity::None => write!(f, "nopass"),
+        }
+    }
+}
+
+impl FromStr for WifiSecurity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "wpa" | "wpa2" | "wpa3" => Ok(WifiSecurity::Wpa),
+            "wep" => Ok(WifiSecurity::Wep),
+            "none" | "nopass" | "open" => Ok(WifiSecurity::None),
+            _ => Err(format!("Expected 'wpa', 'wep' or 'none', got: {}", s)),
+        }
+    }
+}
+
+// Puts a backslash before every character in `special`.
+fn escape(value: &str, special: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if special.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn escape_mecard(value: &str) -> String {
+    escape(value, "\\;,:")
+}
+
+// Text values of vCard and iCalendar, where line breaks become `\n`.
+fn escape_text(value: &str) -> String {
+    escape(&value.replace("\r\n", "\n"), "\\;,").replace('\n', "\\n")
+}
+
+pub(crate) fn percent_encode(value: &str) -> String {
+    percent_encode_with(value, "")
+}
+
+// Percent-encodes everything but unreserved URI characters and those in `keep`.
+fn percent_encode_with(value: &str, keep: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) || keep.as_bytes().contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+fn check_phone(number: &str) -> AppResult<&str> {
+    let number = number.trim();
+    let digits = number.chars().filter(char::is_ascii_digit).count();
+    if digits == 0 || !number.chars().all(|c| c.is_ascii_digit() || "+-() ".contains(c)) {
+        anyhow::bail!("Expected a phone number, got: {}", number);
+    }
+    Ok(number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(payload: Payload) -> String {
+        payload.build().unwrap().to_string()
+    }
+
+    fn contact() -> Contact {
+        Contact {
+            first_name: "Ana".to_string(),
+            last_name: "Silva, Jr.".to_string(),
+            organization: Some("A;B".to_string()),
+            phone: Some("+1 555".to_string()),
+            note: Some("line one\nhttp://x".to_string()),
+            ..Contact::default()
+        }
+    }
+
+    #[test]
+    fn escapes_wifi_fields() {
+        let wifi = Wifi {
+            ssid: r#"My;Net,"5G":"#.to_string(),
+            password: Zeroizing::new(r"p\a;ss".to_string()),
+            security: WifiSecurity::Wpa,
+            hidden: true,
+        };
+        assert_eq!(build(Payload::Wifi(wifi)), r#"WIFI:T:WPA;S:My\;Net\,\"5G\"\:;P:p\\a\;ss;H:true;;"#);
+
+        let open = Wifi { ssid: "Cafe".to_string(), security: WifiSecurity::None, ..Wifi::default() };
+        assert_eq!(build(Payload::Wifi(open)), "WIFI:T:nopass;S:Cafe;;");
+        assert!(Payload::Wifi(Wifi { ssid: "Cafe".to_string(), ..Wifi::default() }).build().is_err());
+    }
+
+    #[test]
+    fn escapes_vcard_and_mecard() {
+        assert_eq!(build(Payload::VCard(contact())), [
+            "BEGIN:VCARD",
+            "VERSION:3.0",
+            r"N:Silva\, Jr.;Ana;;;",
+            r"FN:Ana Silva\, Jr.",
+            r"ORG:A\;B",
+            "TEL:+1 555",
+            r"NOTE:line one\nhttp://x",
+            "END:VCARD",
+        ].join("\r\n"));
+        assert_eq!(build(Payload::MeCard(contact())), concat!(r"MECARD:N:Silva\, Jr.,Ana;ORG:A\;B;TEL:+1 555;NOTE:line one", "\n", r"http\://x;;"));
+    }
+
+    #[test]
+    fn builds_geo_sms_and_mailto() {
+        let geo = Geo { latitude: 40.7128, longitude: -74.006, label: Some("City Hall & Park".to_string()) };
+        assert_eq!(build(Payload::Geo(geo)), "geo:40.7128,-74.006?q=City%20Hall%20%26%20Park");
+        assert!(Payload::Geo(Geo { latitude: 91.0, ..Geo::default() }).build().is_err());
+
+        let sms = Sms { number: " +1 (555) 010 ".to_string(), message: Some("Hi: see you".to_string()) };
+        assert_eq!(build(Payload::Sms(sms)), "SMSTO:+1 (555) 010:Hi: see you");
+
+        let email = Email { to: "me@example.com".to_string(), subject: Some("Q&A".to_string()), body: Some("a b".to_string()) };
+        assert_eq!(build(Payload::Email(email)), "mailto:me@example.com?subject=Q%26A&body=a%20b");
+    }
+
+    fn event_at(summary: &str) -> Event {
+        Event { summary: summary.to_string(), start: "20261016".parse().unwrap(), end: None, location: None, description: None }
+    }
+
+    #[test]
+    fn builds_vevent() {
+        let event = Event {
+            summary: "Launch; v2".to_string(),
+            start: "2026-10-16 14:30Z".parse().unwrap(),
+            end: Some("2026-10-16T16:00:00Z".parse().unwrap()),
+            location: Some("Room 1, HQ".to_string()),
+            description: None,
+        };
+        assert_eq!(build(Payload::Event(event)), [
+            "BEGIN:VEVENT",
+            r"SUMMARY:Launch\; v2",
+            "DTSTART:20261016T143000Z",
+            "DTEND:20261016T160000Z",
+            r"LOCATION:Room 1\, HQ",
+            "END:VEVENT",
+        ].join("\r\n"));
+
+        let mixed = Event { end: Some("2026-10-16 16:00".parse().unwrap()), start: "2026-10-16T17:00Z".parse().unwrap(), ..event_at("Mixed") };
+        assert!(mixed.build().unwrap_err().to_string().contains("UTC"));
+        let backwards = Event { end: Some("2026-10-16 16:00".parse().unwrap()), start: "2026-10-16 17:00".parse().unwrap(), ..event_at("Backwards") };
+        assert!(backwards.build().is_err());
+
+        assert!(build(Payload::Event(event_at("Day"))).contains("DTSTART;VALUE=DATE:20261016"));
+    }
+
+    #[test]
+    fn rejects_days_past_the_end_of_the_month() {
+        for date in ["2026-02-29", "2026-02-31", "2026-04-31", "2100-02-29", "2026-13-01", "2026-01-00"] {
+            assert!(date.parse::<EventTime>().is_err(), "{}", date);
+        }
+        for date in ["2028-02-29", "2000-02-29", "2026-01-31", "2026-04-30"] {
+            assert!(date.parse::<EventTime>().is_ok(), "{}", date);
+        }
+    }
+}
@@ -0,0 +1,368 @@
+use std::path::Path;
+use anyhow::Context;
+use image::{ImageBuffer, Luma, DynamicImage, GrayImage};
+use serde::{Serialize, Deserialize};
+
+use crate::AppResult;
+
+pub fn enhance_contrast(img: &ImageBuffer<Luma<u8>, Vec<u8>>) -> ImageBuffer<Luma<u8>, Vec<u8>> {
+    let (width, height) = img.dimensions();
+    let mut enhanced = ImageBuffer::new(width, height);
+    
+    let mut histogram = [0u32; 256];
+    for pixel in img.pixels() {
+        histogram[pixel[0] as usize] += 1;
+    }
+    
+    let total_pixels = (width * height) as f32;
+    let mut cdf = [0.0f32; 256];
+    let mut sum = 0.0;
+    
+    for i in 0..256 {
+        sum += histogram[i] as f32 / total_pixels;
+        cdf[i] = sum;
+    }
+    
+    for (x, y, pixel) in enhanced.enumerate_pixels_mut() {
+        let old_val = img.get_pixel(x, y)[0] as usize;
+        let new_val = (cdf[old_val] * 255.0) as u8;
+        *pixel = Luma([new_val]);
+    }
+    
+    enhanced
+}
+
+fn compute_integral(img: &ImageBuffer<Luma<u8>, Vec<u8>>) -> Vec<Vec<u64>> {
+    let (width, height) = img.dimensions();
+    let w = width as usize;
+    let h = height as usize;
+    let mut integral = vec![vec![0u64; w + 1]; h + 1];
+
+    for y in 1..=h {
+        for x in 1..=w {
+            let val = img.get_pixel((x - 1) as u32, (y - 1) as u32)[0] as u64;
+            integral[y][x] = val + integral[y - 1][x] + integral[y][x - 1] - integral[y - 1][x - 1];
+        }
+    }
+
+    integral
+}
+
+pub fn adaptive_threshold(img: &ImageBuffer<Luma<u8>, Vec<u8>>, block_size: u32, offset: u32) -> ImageBuffer<Luma<u8>, Vec<u8>> {
+    let (width, height) = img.dimensions();
+    if width == 0 || height == 0 {
+        return ImageBuffer::new(width, height);
+    }
+
+    let mut result = ImageBuffer::new(width, height);
+    let half_block = block_size / 2;
+    let integral = compute_integral(img);
+
+    for y in 0..height as usize {
+        for x in 0..width as usize {
+            let x_start = x.saturating_sub(half_block as usize);
+            let x_end = (x + half_block as usize).min(width as usize - 1);
+            let y_start = y.saturating_sub(half_block as usize);
+            let y_end = (y + half_block as usize).min(height as usize - 1);
+
+            let count = ((x_end - x_start + 1) * (y_end - y_start + 1)) as u64;
+            if count == 0 {
+                result.put_pixel(x as u32, y as u32, Luma([128]));
+                continue;
+            }
+
+            let sum = integral[y_end + 1][x_end + 1]
+                .saturating_sub(integral[y_end + 1][x_start])
+                .saturating_sub(integral[y_start][x_end + 1])
+                .saturating_add(integral[y_start][x_start]);
+
+            let mean = (sum / count) as u32;
+            let pixel_val = img.get_pixel(x as u32, y as u32)[0] as u32;
+
+            let new_val = if pixel_val < mean.saturating_sub(offset) { 0 } else { 255 };
+            result.put_pixel(x as u32, y as u32, Luma([new_val as u8]));
+        }
+    }
+
+    result
+}
+
+/// A single preprocessing variant: resize, then filters, then equalization, then thresholding.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PipelineStep {
+    pub name: String,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    /// Skip the step unless both image dimensions exceed this many pixels.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_source_size: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<Filter>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub equalize: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<Threshold>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Filter {
+    Blur { sigma: f32 },
+    Sharpen { sigma: f32, threshold: i32 },
+    Invert,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Threshold {
+    pub block_size: u32,
+    #[serde(default = "default_threshold_offset")]
+    pub offset: u32,
+}
+
+/// Ordered list of preprocessing variants tried by the decoder.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pipeline {
+    pub steps: Vec<PipelineStep>,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+fn default_threshold_offset() -> u32 {
+    5
+}
+
+impl PipelineStep {
+    fn new(name: &str, scale: f32) -> Self {
+        PipelineStep {
+            name: name.to_string(),
+            scale,
+            min_source_size: None,
+            filters: Vec::new(),
+            equalize: false,
+            threshold: None,
+        }
+    }
+
+    pub fn applies_to(&self, width: u32, height: u32) -> bool {
+        self.min_source_size.is_none_or(|min| width > min && height > min)
+    }
+
+    pub fn apply(&self, img: &DynamicImage) -> GrayImage {
+        self.finish(self.base(img))
+    }
+
+    fn base(&self, img: &DynamicImage) -> GrayImage {
+        if self.scale == 1.0 {
+            img.to_luma8()
+        } else {
+            img.resize_exact(
+                ((img.width() as f32 * self.scale) as u32).max(1),
+                ((img.height() as f32 * self.scale) as u32).max(1),
+                image::imageops::FilterType::Lanczos3
+            ).to_luma8()
+        }
+    }
+
+    fn finish(&self, mut gray: GrayImage) -> GrayImage {
+        for filter in &self.filters {
+            gray = match *filter {
+                Filter::Blur { sigma } => image::imageops::blur(&gray, sigma),
+                Filter::Sharpen { sigma, threshold } => image::imageops::unsharpen(&gray, sigma, threshold),
+                Filter::Invert => {
+                    image::imageops::invert(&mut gray);
+                    gray
+                }
+            };
+        }
+
+        if self.equalize {
+            gray = enhance_contrast(&gray);
+        }
+        if let Some(threshold) = self.threshold {
+            gray = adaptive_threshold(&gray, threshold.block_size, threshold.offset);
+        }
+
+        gray
+    }
+
+    fn validate(&self) -> AppResult<()> {
+        if !(self.scale > 0.0 && self.scale <= 8.0) {
+            anyhow::bail!("Step '{}': scale must be greater than 0 and at most 8, got {}", self.name, self.scale);
+        }
+        if let Some(threshold) = self.threshold
+            && threshold.block_size < 3 {
+            anyhow::bail!("Step '{}': threshold block_size must be at least 3", self.name);
+        }
+        for filter in &self.filters {
+            if let Filter::Blur { sigma } | Filter::Sharpen { sigma, .. } = *filter
+                && sigma <= 0.0 {
+                anyhow::bail!("Step '{}': filter sigma must be greater than 0", self.name);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        let threshold = Threshold { block_size: 15, offset: default_threshold_offset() };
+        Pipeline {
+            steps: vec![
+                PipelineStep::new("grayscale", 1.0),
+                PipelineStep { equalize: true, ..PipelineStep::new("equalized", 1.0) },
+                PipelineStep { threshold: Some(threshold), ..PipelineStep::new("adaptive_threshold", 1.0) },
+                PipelineStep::new("upscaled_1.5x", 1.5),
+                PipelineStep { equalize: true, ..PipelineStep::new("upscaled_1.5x_equalized", 1.5) },
+                PipelineStep { min_source_size: Some(400), ..PipelineStep::new("downscaled_0.8x", 0.8) },
+            ],
+        }
+    }
+}
+
+impl Pipeline {
+    /// Reads a pipeline profile from a JSON file.
+    pub fn load(path: &Path) -> AppResult<Pipeline> {
+        let content = std::fs::read_to_string(path)
+            .context(format!("Could not read pipeline profile: {}", path.display()))?;
+        let pipeline: Pipeline = serde_json::from_str(&content)
+            .context(format!("Pipeline profile format is invalid: {}", path.display()))?;
+        pipeline.validate()?;
+        Ok(pipeline)
+    }
+
+    pub fn validate(&self) -> AppResult<()> {
+        if self.steps.is_empty() {
+            anyhow::bail!("Pipeline must contain at least one step.");
+        }
+        for step in &self.steps {
+            step.validate()?;
+        }
+        Ok(())
+    }
+
+    /// Produces every variant that applies to `img`, in pipeline order.
+    pub fn apply(&self, img: &DynamicImage) -> Vec<(String, GrayImage)> {
+        self.variants(img)
+            .map(|(name, variant)| (name.to_string(), variant))
+            .collect()
+    }
+
+    /// Lazily produces the variants for `img`, so callers can stop early.
+    pub fn variants<'a>(&'a self, img: &'a DynamicImage) -> Variants<'a> {
+        Variants {
+            img,
+            steps: self.steps.iter(),
+            base: None,
+        }
+    }
+}
+
+/// Iterator returned by [`Pipeline::variants`].
+///
+/// The resized base image of the previous step is kept, so consecutive steps
+/// at the same scale only pay for one resize.
+pub struct Variants<'a> {
+    img: &'a DynamicImage,
+    steps: std::slice::Iter<'a, PipelineStep>,
+    base: Option<(f32, GrayImage)>,
+}
+
+impl<'a> Iterator for Variants<'a> {
+    type Item = (&'a str, GrayImage);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (width, height) = (self.img.width(), self.img.height());
+        let step = self.steps.by_ref().find(|step| step.applies_to(width, height))?;
+
+        let base = match &self.base {
+            Some((scale, base)) if *scale == step.scale => base.clone(),
+            _ => {
+                let base = step.base(self.img);
+                self.base = Some((step.scale, base.clone()));
+                base
+            }
+        };
+
+        Some((step.name.as_str(), step.finish(base)))
+    }
+}
+
+pub fn try_different_scales(img: &DynamicImage) -> Vec<(String, GrayImage)> {
+    Pipeline::default().apply(img)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Luma};
+
+    use super::*;
+
+    fn gradient(size: u32) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(size, size, |x, y| Luma([((x * 7 + y * 3) % 256) as u8])))
+    }
+
+    fn load_json(name: &str, json: &str) -> AppResult<Pipeline> {
+        let path = std::env::temp_dir().join(format!("{}-pipeline-{}-{}.json", env!("CARGO_PKG_NAME"), std::process::id(), name));
+        std::fs::write(&path, json).unwrap();
+        let pipeline = Pipeline::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        pipeline
+    }
+
+    #[test]
+    fn default_matches_the_fixed_variants() {
+        let img = gradient(500);
+        let resized = |scale: f32| img.resize_exact((500.0 * scale) as u32, (500.0 * scale) as u32, image::imageops::FilterType::Lanczos3).to_luma8();
+        let gray = img.to_luma8();
+        let upscaled = resized(1.5);
+        let expected = [
+            ("grayscale", gray.clone()),
+            ("equalized", enhance_contrast(&gray)),
+            ("adaptive_threshold", adaptive_threshold(&gray, 15, 5)),
+            ("upscaled_1.5x", upscaled.clone()),
+            ("upscaled_1.5x_equalized", enhance_contrast(&upscaled)),
+            ("downscaled_0.8x", resized(0.8)),
+        ];
+        let variants = Pipeline::default().apply(&img);
+        assert_eq!(variants.len(), expected.len());
+        for ((name, variant), (expected_name, expected)) in variants.iter().zip(expected) {
+            assert_eq!(name, expected_name);
+            assert!(*variant == expected, "{}", name);
+        }
+
+        // Small images skip the downscaled variant.
+        let names: Vec<String> = Pipeline::default().apply(&gradient(400)).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["grayscale", "equalized", "adaptive_threshold", "upscaled_1.5x", "upscaled_1.5x_equalized"]);
+    }
+
+    #[test]
+    fn loads_a_profile() {
+        let pipeline = load_json("valid", r#"{"steps": [
+            {"name": "plain"},
+            {"name": "inverted", "scale": 2.0, "filters": [{"type": "invert"}, {"type": "blur", "sigma": 0.5}], "threshold": {"block_size": 21}}
+        ]}"#).unwrap();
+        assert_eq!(pipeline.steps.len(), 2);
+        assert_eq!(pipeline.steps[0].scale, 1.0);
+        assert_eq!(pipeline.steps[1].threshold, Some(Threshold { block_size: 21, offset: 5 }));
+
+        let json = serde_json::to_string(&Pipeline::default()).unwrap();
+        assert_eq!(load_json("default", &json).unwrap(), Pipeline::default());
+    }
+
+    #[test]
+    fn rejects_invalid_profiles() {
+        for (name, json) in [
+            ("empty", r#"{"steps": []}"#),
+            ("scale", r#"{"steps": [{"name": "a", "scale": 0}]}"#),
+            ("large-scale", r#"{"steps": [{"name": "a", "scale": 9}]}"#),
+            ("block", r#"{"steps": [{"name": "a", "threshold": {"block_size": 2}}]}"#),
+            ("sigma", r#"{"steps": [{"name": "a", "filters": [{"type": "sharpen", "sigma": 0, "threshold": 1}]}]}"#),
+            ("filter", r#"{"steps": [{"name": "a", "filters": [{"type": "emboss"}]}]}"#),
+            ("syntax", r#"{"steps": [{"name": "a"}"#),
+        ] {
+            assert!(load_json(name, json).is_err(), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,335 @@
+use std::fmt;
+use serde::Serialize;
+use zeroize::Zeroizing;
+
+/// A reason a decoded link may lead somewhere other than it appears to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum LinkWarning {
+    /// A `javascript:`, `data:`, `vbscript:`, `file:` or `blob:` link, by scheme.
+    ScriptScheme(String),
+    /// A link opened without TLS, by scheme.
+    Insecure(String),
+    /// A user name or password before the host, as in `https://bank.com@evil.example`.
+    Credentials,
+    /// A host given as an IP address, in any of the forms browsers accept.
+    IpAddress(String),
+    /// A domain with non-ASCII characters, by its readable form.
+    InternationalDomain(String),
+    /// A domain mixing lookalike letters from other alphabets, by its readable form.
+    Homoglyph(String),
+    /// A host on the bundled list of link shorteners.
+    Shortener(String),
+    /// A top-level domain frequently used for phishing.
+    SuspiciousTld(String),
+}
+
+impl LinkWarning {
+    /// Whether the finding suggests the link disguises where it leads, as opposed to
+    /// merely being unencrypted, shortened or on an unusual domain.
+    pub fn is_deceptive(&self) -> bool {
+        matches!(
+            self,
+            LinkWarning::ScriptScheme(_)
+                | LinkWarning::Credentials
+                | LinkWarning::IpAddress(_)
+                | LinkWarning::InternationalDomain(_)
+                | LinkWarning::Homoglyph(_)
+        )
+    }
+}
+
+impl fmt::Display for LinkWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkWarning::ScriptScheme(scheme) => {
+                write!(f, "The {}: scheme can run code or embed content instead of opening a web page.", scheme)
+            }
+            LinkWarning::Insecure(scheme) => {
+                write!(f, "The link uses {}, not HTTPS, so the connection is not encrypted.", scheme.to_uppercase())
+            }
+            LinkWarning::Credentials => {
+                write!(f, "The link contains a user name or password; the real host is the part after '@'.")
+            }
+            LinkWarning::IpAddress(host) => write!(f, "The link points to the IP address {} instead of a domain name.", host),
+            LinkWarning::InternationalDomain(host) => {
+                write!(f, "The domain uses international characters and reads as {}.", host)
+            }
+            LinkWarning::Homoglyph(host) => {
+                write!(f, "The domain {} mixes lookalike letters from other alphabets and may imitate a familiar site.", host)
+            }
+            LinkWarning::Shortener(host) => {
+                write!(f, "The link goes through the shortener {}, which hides its real destination.", host)
+            }
+            LinkWarning::SuspiciousTld(tld) => {
+                write!(f, "The domain ends in .{}, a top-level domain often used for phishing.", tld)
+            }
+        }
+    }
+}
+
+const SCRIPT_SCHEMES: &[&str] = &["javascript", "data", "vbscript", "file", "blob"];
+const INSECURE_SCHEMES: &[&str] = &["http", "ftp", "ws"];
+const WEB_SCHEMES: &[&str] = &["https", "http", "ftp", "ftps", "ws", "wss"];
+
+const SHORTENERS: &str = include_str!("safety/shorteners.txt");
+
+const SUSPICIOUS_TLDS: &[&str] = &[
+    "accountant", "bid", "bond", "buzz", "cam", "cf", "cfd", "click", "country", "cricket", "cyou",
+    "date", "download", "faith", "ga", "gq", "icu", "kim", "link", "loan", "lol", "men", "ml",
+    "monster", "mov", "party", "quest", "racing", "rest", "review", "sbs", "science", "stream",
+    "support", "tk", "top", "trade", "win", "work", "xyz", "zip",
+];
+
+/// Checks `content` for signs of a malicious link, without any network access.
+///
+/// Content that is not a link gives no warnings. Hosts are read the way browsers read
+/// them, so tabs and line breaks inside the link and backslashes ending the host are
+/// handled too.
+pub fn check_link(content: &str) -> Vec<LinkWarning> {
+    // Browsers drop tabs and line breaks anywhere in a link.
+    let link: Zeroizing<String> = Zeroizing::new(content.trim().chars()
+        .filter(|c| !matches!(c, '\t' | '\r' | '\n'))
+        .collect());
+    let mut warnings = Vec::new();
+
+    let rest = if link.get(..4).is_some_and(|prefix| prefix.eq_ignore_ascii_case("www.")) {
+        link.as_str()
+    } else {
+        let Some((scheme, rest)) = link.split_once(':') else {
+            return warnings;
+        };
+        let scheme = scheme.to_ascii_lowercase();
+        if SCRIPT_SCHEMES.contains(&scheme.as_str()) {
+            warnings.push(LinkWarning::ScriptScheme(scheme));
+            return warnings;
+        }
+        if !WEB_SCHEMES.contains(&scheme.as_str()) {
+            return warnings;
+        }
+        if INSECURE_SCHEMES.contains(&scheme.as_str()) {
+            warnings.push(LinkWarning::Insecure(scheme));
+        }
+        rest.trim_start_matches(['/', '\\'])
+    };
+
+    let authority = &rest[..rest.find(['/', '\\', '?', '#']).unwrap_or(rest.len())];
+    let host = match authority.rsplit_once('@') {
+        Some((_, host)) => {
+            warnings.push(LinkWarning::Credentials);
+            host
+        }
+        None => authority,
+    };
+    let host = strip_port(host);
+    // IDNA treats the ideographic and fullwidth full stops as dots.
+    let host = host.replace(['\u{3002}', '\u{FF0E}', '\u{FF61}'], ".").to_lowercase();
+    let host = host.trim_end_matches('.');
+    if host.is_empty() {
+        return warnings;
+    }
+
+    if is_ip_address(host) {
+        warnings.push(LinkWarning::IpAddress(host.to_string()));
+        return warnings;
+    }
+
+    let labels: Vec<String> = host.split('.')
+        .map(|label| match label.strip_prefix("xn--").and_then(punycode_decode) {
+            Some(decoded) => decoded,
+            None => label.to_string(),
+        })
+        .collect();
+    let readable = labels.join(".");
+    if !readable.is_ascii() {
+        if labels.iter().any(|label| is_lookalike(label)) {
+            warnings.push(LinkWarning::Homoglyph(readable));
+        } else {
+            warnings.push(LinkWarning::InternationalDomain(readable));
+        }
+    }
+
+    if let Some(shortener) = shortener(host) {
+        warnings.push(LinkWarning::Shortener(shortener.to_string()));
+    }
+    if let Some(tld) = host.rsplit('.').next()
+        && SUSPICIOUS_TLDS.contains(&tld) {
+        warnings.push(LinkWarning::SuspiciousTld(tld.to_string()));
+    }
+    warnings
+}
+
+fn strip_port(host: &str) -> &str {
+    if host.starts_with('[') {
+        return host.find(']').map_or(host, |end| &host[..=end]);
+    }
+    match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    }
+}
+
+// Bracketed IPv6, or a host whose last label is a number: browsers read such hosts as
+// IPv4 addresses, including the decimal, octal and hex forms like `3232235777` and `0x7f.1`.
+fn is_ip_address(host: &str) -> bool {
+    if host.starts_with('[') {
+        return true;
+    }
+    let last = host.rsplit('.').next().unwrap_or(host);
+    let hex = last.strip_prefix("0x");
+    !last.is_empty() && match hex {
+        Some(digits) => digits.chars().all(|c| c.is_ascii_hexdigit()),
+        None => last.chars().all(|c| c.is_ascii_digit()),
+    }
+}
+
+fn shortener(host: &str) -> Option<&'static str> {
+    SHORTENERS.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .find(|entry| {
+            host == *entry || host.strip_suffix(entry).is_some_and(|prefix| prefix.ends_with('.'))
+        })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+}
+
+fn script(c: char) -> Option<Script> {
+    match c {
+        'a'..='z' | '\u{00E0}'..='\u{024F}' => Some(Script::Latin),
+        '\u{0370}'..='\u{03FF}' => Some(Script::Greek),
+        '\u{0400}'..='\u{052F}' => Some(Script::Cyrillic),
+        _ => None,
+    }
+}
+
+// Greek and Cyrillic letters that render like Latin ones.
+const LATIN_LOOKALIKES: &str = "αεικνορτυχаеһіјӏорсѕухԁԛԝ";
+
+// A label mixing Latin with Greek or Cyrillic, written only in lookalike letters, or using
+// fullwidth forms of ASCII characters.
+fn is_lookalike(label: &str) -> bool {
+    if label.chars().any(|c| ('\u{FF01}'..='\u{FF5E}').contains(&c)) {
+        return true;
+    }
+    let mut scripts: Vec<Script> = label.chars().filter_map(script).collect();
+    scripts.sort_by_key(|script| *script as u8);
+    scripts.dedup();
+    match scripts.as_slice() {
+        [] | [Script::Latin] => false,
+        [_] => label.chars().filter(|c| c.is_alphabetic()).all(|c| LATIN_LOOKALIKES.contains(c)),
+        _ => true,
+    }
+}
+
+// Punycode (RFC 3492) decoding of one label, without its `xn--` prefix.
+fn punycode_decode(input: &str) -> Option<String> {
+    const BASE: u32 = 36;
+    const T_MIN: u32 = 1;
+    const T_MAX: u32 = 26;
+
+    let (basic, encoded) = input.rsplit_once('-').unwrap_or(("", input));
+    if !basic.is_ascii() {
+        return None;
+    }
+    let mut output: Vec<char> = basic.chars().collect();
+    let (mut n, mut i, mut bias) = (128u32, 0u32, 72u32);
+    let mut digits = encoded.chars();
+    while !digits.as_str().is_empty() {
+        let old_i = i;
+        let mut weight = 1u32;
+        let mut k = BASE;
+        loop {
+            let digit = match digits.next()? {
+                c @ 'a'..='z' => c as u32 - 'a' as u32,
+                c @ '0'..='9' => c as u32 - '0' as u32 + 26,
+                _ => return None,
+            };
+            i = i.checked_add(digit.checked_mul(weight)?)?;
+            let threshold = if k <= bias { T_MIN } else if k >= bias + T_MAX { T_MAX } else { k - bias };
+            if digit < threshold {
+                break;
+            }
+            weight = weight.checked_mul(BASE - threshold)?;
+            k += BASE;
+        }
+        let length = output.len() as u32 + 1;
+        bias = punycode_adapt(i - old_i, length, old_i == 0);
+        n = n.checked_add(i / length)?;
+        i %= length;
+        output.insert(i as usize, char::from_u32(n)?);
+        i += 1;
+    }
+    Some(output.into_iter().collect())
+}
+
+fn punycode_adapt(delta: u32, points: u32, first: bool) -> u32 {
+    const BASE: u32 = 36;
+    let mut delta = delta / if first { 700 } else { 2 };
+    delta += delta / points;
+    let mut k = 0;
+    while delta > (BASE - 1) * 26 / 2 {
+        delta /= BASE - 1;
+        k += BASE;
+    }
+    k + BASE * delta / (delta + 38)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_punycode() {
+        assert_eq!(punycode_decode("mnchen-3ya").as_deref(), Some("münchen"));
+        assert_eq!(punycode_decode("bcher-kva").as_deref(), Some("bücher"));
+        assert_eq!(punycode_decode("-> $1.00 <--").as_deref(), Some("-> $1.00 <-"));
+        assert_eq!(punycode_decode("abc-9!"), None);
+        assert_eq!(
+            check_link("https://xn--mnchen-3ya.de/"),
+            [LinkWarning::InternationalDomain("münchen.de".to_string())],
+        );
+    }
+
+    #[test]
+    fn flags_mixed_script_domains() {
+        assert!(is_lookalike("аpple"));
+        assert!(is_lookalike("раураl"));
+        assert!(is_lookalike("ｇoogle"));
+        assert!(!is_lookalike("apple"));
+        assert!(!is_lookalike("пример"));
+        assert_eq!(
+            check_link("https://xn--pple-43d.com/login"),
+            [LinkWarning::Homoglyph("аpple.com".to_string())],
+        );
+    }
+
+    #[test]
+    fn flags_ip_hosts() {
+        for (link, host) in [
+            ("http://192.168.1.1/admin", "192.168.1.1"),
+            ("https://3232235777/", "3232235777"),
+            ("https://0x7f.1/", "0x7f.1"),
+            ("https://[::1]:8080/", "[::1]"),
+            ("https://user@10.0.0.1:443", "10.0.0.1"),
+        ] {
+            assert!(check_link(link).contains(&LinkWarning::IpAddress(host.to_string())), "{}", link);
+        }
+        assert!(!is_ip_address("example.com"));
+        assert!(!is_ip_address("1.example"));
+    }
+
+    #[test]
+    fn plain_http_is_not_deceptive() {
+        assert!(check_link("https://example.com/").is_empty());
+        let warnings = check_link("http://example.com/");
+        assert_eq!(warnings, [LinkWarning::Insecure("http".to_string())]);
+        assert!(!warnings.iter().any(LinkWarning::is_deceptive));
+        assert!(check_link("javascript:alert(1)").iter().all(LinkWarning::is_deceptive));
+    }
+}
@@ -0,0 +1,223 @@
+use qrcode::Version;
+use qrcode::bits::Bits;
+use serde::Serialize;
+use zeroize::Zeroizing;
+
+use crate::AppResult;
+use crate::bitstream::{self, Run, RunKind, is_alphanumeric, structured_append_bits};
+use crate::charset::{BINARY, Text, decode_text};
+use crate::content::{Classification, classify, classify_binary};
+use crate::decode::DecodedQr;
+use crate::encode::{EncodeOptions, Symbol, draw_symbol, encode_bytes};
+use crate::safety::{LinkWarning, check_link};
+
+/// Most symbols one Structured Append sequence can span.
+pub const MAX_PARTS: usize = 16;
+
+/// Structured Append header of a symbol that carries one part of a longer message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct StructuredAppend {
+    /// Position of this symbol in the sequence, from 1.
+    pub part: u8,
+    /// Number of symbols in the sequence, at most 16.
+    pub total: u8,
+    /// XOR of every byte of the complete message; identifies the sequence.
+    pub parity: u8,
+}
+
+/// A Structured Append sequence put back together from the symbols that were found.
+#[derive(Debug, Clone, Serialize)]
+pub struct AssembledMessage {
+    pub parity: u8,
+    pub total: u8,
+    /// Part numbers that were found, in order.
+    pub found: Vec<u8>,
+    /// Part numbers that are still missing.
+    pub missing: Vec<u8>,
+    /// Whether the reassembled bytes match the parity byte; `None` until complete.
+    pub parity_ok: Option<bool>,
+    /// The full message; `None` until every part was found.
+    pub content: Option<Zeroizing<String>>,
+    /// Character set of the full message, or `"binary"`; `None` until every part was found.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<&'static str>,
+    /// Raw bytes of the full message; `None` until every part was found.
+    #[serde(skip)]
+    pub bytes: Option<Zeroizing<Vec<u8>>>,
+    /// What the full message represents; `None` until every part was found.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub classification: Option<Classification>,
+    /// Reasons the full message may be an unsafe link.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub link_warnings: Vec<LinkWarning>,
+    /// Source of each found part, in part order.
+    pub sources: Vec<String>,
+}
+
+impl AssembledMessage {
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+
+    /// Whether the full message is binary data rather than text.
+    pub fn is_binary(&self) -> bool {
+        self.encoding == Some(BINARY)
+    }
+}
+
+/// Groups Structured Append symbols into their sequences and reassembles each one.
+///
+/// Sequences are told apart by parity byte and length, so symbols may come from one
+/// image or from a whole batch. Symbols without a valid header are ignored.
+pub fn assemble_messages(results: &[DecodedQr]) -> Vec<AssembledMessage> {
+    let mut sequences: Vec<(StructuredAppend, Vec<Option<&DecodedQr>>)> = Vec::new();
+
+    for result in results {
+        let Some(header) = result.structured_append.filter(|h| (1..=h.total).contains(&h.part)) else {
+            continue;
+        };
+        let index = match sequences.iter().position(|(h, _)| h.parity == header.parity && h.total == header.total) {
+            Some(index) => index,
+            None => {
+                sequences.push((header, vec![None; header.total as usize]));
+                sequences.len() - 1
+            }
+        };
+        if let Some(slot) = sequences[index].1.get_mut(header.part as usize - 1)
+            && slot.is_none()
+        {
+            *slot = Some(result);
+        }
+    }
+
+    sequences.into_iter().map(|(header, parts)| assemble(header, &parts)).collect()
+}
+
+fn assemble(header: StructuredAppend, parts: &[Option<&DecodedQr>]) -> AssembledMessage {
+    let numbered = || parts.iter().zip(1u8..);
+    let found: Vec<u8> = numbered().filter(|(p, _)| p.is_some()).map(|(_, n)| n).collect();
+    let missing: Vec<u8> = numbered().filter(|(p, _)| p.is_none()).map(|(_, n)| n).collect();
+    let sources = parts.iter().flatten().map(|p| p.source.clone()).collect();
+
+    // A character set declared in the first part applies to the whole message.
+    let (parity_ok, content, encoding, bytes) = if missing.is_empty() {
+        let bytes: Zeroizing<Vec<u8>> = Zeroizing::new(parts.iter().flatten()
+            .flat_map(|p| p.bytes.iter().copied())
+            .collect());
+        let parity = bytes.iter().fold(0u8, |acc, b| acc ^ b);
+        let eci = parts.iter().flatten().next().and_then(|p| p.eci);
+        let Text { content, encoding, .. } = decode_text(&bytes, &[Run { kind: RunKind::Bytes(eci), end: bytes.len() }]);
+        (Some(parity == header.parity), Some(content), Some(encoding), Some(bytes))
+    } else {
+        (None, None, None, None)
+    };
+
+    let (classification, link_warnings) = match (&content, &bytes) {
+        (Some(_), Some(bytes)) if encoding == Some(BINARY) => (Some(classify_binary(bytes)), Vec::new()),
+        (Some(content), _) => (Some(classify(content)), check_link(content)),
+        _ => (None, Vec::new()),
+    };
+    AssembledMessage {
+        parity: header.parity, total: header.total, found, missing, parity_ok, content, encoding, bytes, classification, link_warnings, sources,
+    }
+}
+
+/// Encodes `data` as a Structured Append sequence of up to [`MAX_PARTS`] symbols.
+///
+/// Data that fits in a single QR code gives one ordinary symbol. Otherwise it is cut into
+/// the fewest equal parts that fit, and every part uses the same, smallest possible version
+/// allowed by `options`.
+pub fn encode_structured_append(data: &[u8], options: &EncodeOptions) -> AppResult<Vec<Symbol>> {
+    options.validate()?;
+    if let Ok(symbol) = encode_bytes(data, options) {
+        return Ok(vec![symbol]);
+    }
+    if options.micro {
+        anyhow::bail!("Data is too long for a Micro QR code, which cannot be split.");
+    }
+
+    let parity = data.iter().fold(0u8, |acc, b| acc ^ b);
+    for parts in 2..=MAX_PARTS {
+        let chunk_len = data.len().div_ceil(parts);
+        let Some(version) = smallest_version(chunk_len, is_alphanumeric(data), options)? else {
+            continue;
+        };
+        let chunks: Vec<&[u8]> = data.chunks(chunk_len).collect();
+        let total = chunks.len() as u8;
+        return chunks.iter().zip(1..)
+            .map(|(chunk, part)| {
+                let header = StructuredAppend { part, total, parity };
+                let codewords = bitstream::structured_append_codewords(header, chunk, version, options.effective_ec_level())?;
+                draw_symbol(version, &codewords, options)
+            })
+            .collect();
+    }
+    anyhow::bail!("Data is too long for {} QR codes ({} bytes).", MAX_PARTS, data.len())
+}
+
+fn smallest_version(len: usize, alphanumeric: bool, options: &EncodeOptions) -> AppResult<Option<Version>> {
+    for version in options.versions() {
+        let Version::Normal(number) = version else {
+            continue;
+        };
+        if structured_append_bits(len, alphanumeric, number) <= Bits::new(version).max_len(options.effective_ec_level())? {
+            return Ok(Some(version));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::DynamicImage;
+
+    use super::*;
+    use crate::decode::{DecodeMode, DecodeOptions, decode_image};
+    use crate::encode::render_image;
+
+    fn decode_parts(data: &[u8]) -> Vec<DecodedQr> {
+        let options = EncodeOptions { version: Some(1), fixed_version: true, ..EncodeOptions::default() };
+        let symbols = encode_structured_append(data, &options).unwrap();
+        assert!(symbols.len() > 1);
+        let decode_options = DecodeOptions { mode: DecodeMode::Exhaustive, ..DecodeOptions::default() };
+        symbols.iter()
+            .flat_map(|symbol| {
+                let image = DynamicImage::ImageRgba8(render_image(symbol, &options).unwrap());
+                decode_image(&image, &decode_options)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn splits_and_reassembles() {
+        let data = b"split across several tiny symbols";
+        let mut parts = decode_parts(data);
+        parts.reverse();
+        let messages = assemble_messages(&parts);
+        assert_eq!(messages.len(), 1);
+        let message = &messages[0];
+        assert!(message.is_complete());
+        assert_eq!(message.parity_ok, Some(true));
+        assert_eq!(message.content.as_deref().map(String::as_str), Some("split across several tiny symbols"));
+    }
+
+    #[test]
+    fn reports_missing_parts() {
+        let parts = decode_parts(b"split across several tiny symbols");
+        let messages = assemble_messages(&parts[1..]);
+        assert_eq!(messages[0].missing, [1]);
+        assert!(messages[0].content.is_none());
+    }
+
+    #[test]
+    fn ignores_out_of_range_parts() {
+        let mut parts = decode_parts(b"split across several tiny symbols");
+        for (part, result) in [0, 9].into_iter().zip(&mut parts) {
+            let header = result.structured_append.as_mut().unwrap();
+            header.part = part;
+        }
+        let messages = assemble_messages(&parts);
+        assert_eq!(messages.len(), 1);
+        assert!(!messages[0].is_complete());
+    }
+}
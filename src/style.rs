@@ -0,0 +1,312 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use image::{Rgba, RgbaImage};
+use image::imageops::FilterType;
+use serde::{Serialize, Deserialize};
+
+use crate::AppResult;
+use crate::encode::{EncodeOptions, Symbol};
+use crate::input::open_image;
+
+/// Shape drawn for each dark data module.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModuleShape {
+    #[default]
+    Square,
+    /// Squares whose outer corners are rounded, so neighbouring modules flow together.
+    Rounded,
+    Dots,
+}
+
+/// Shape drawn for the three finder patterns in the corners.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FinderShape {
+    #[default]
+    Square,
+    Rounded,
+    Circle,
+}
+
+/// How a QR code is drawn beyond its colors.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Style {
+    pub modules: ModuleShape,
+    pub finders: FinderShape,
+    /// Image placed in the center of the code. Codes with a logo use ECC level H.
+    pub logo: Option<PathBuf>,
+    /// Logo width as a fraction of the symbol width.
+    pub logo_size: f32,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            modules: ModuleShape::Square,
+            finders: FinderShape::Square,
+            logo: None,
+            logo_size: 0.2,
+        }
+    }
+}
+
+impl Style {
+    /// Whether this is the standard look, which every output format can draw.
+    pub fn is_plain(&self) -> bool {
+        self.modules == ModuleShape::Square && self.finders == FinderShape::Square && self.logo.is_none()
+    }
+
+    pub fn validate(&self) -> AppResult<()> {
+        // ECC level H restores up to 30% of the codewords; a wider logo hides too many.
+        if !(0.05..=0.35).contains(&self.logo_size) {
+            anyhow::bail!("Logo size must be between 0.05 and 0.35 of the code width, got: {}", self.logo_size);
+        }
+        if let Some(logo) = &self.logo
+            && !logo.is_file() {
+            anyhow::bail!("Logo file not found: {}", logo.display());
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ModuleShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModuleShape::Square => write!(f, "square"),
+            ModuleShape::Rounded => write!(f, "rounded"),
+            ModuleShape::Dots => write!(f, "dots"),
+        }
+    }
+}
+
+impl FromStr for ModuleShape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "square" => Ok(ModuleShape::Square),
+            "rounded" => Ok(ModuleShape::Rounded),
+            "dots" | "dot" => Ok(ModuleShape::Dots),
+            _ => Err(format!("Expected 'square', 'rounded' or 'dots', got: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for FinderShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FinderShape::Square => write!(f, "square"),
+            FinderShape::Rounded => write!(f, "rounded"),
+            FinderShape::Circle => write!(f, "circle"),
+        }
+    }
+}
+
+impl FromStr for FinderShape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "square" => Ok(FinderShape::Square),
+            "rounded" => Ok(FinderShape::Rounded),
+            "circle" => Ok(FinderShape::Circle),
+            _ => Err(format!("Expected 'square', 'rounded' or 'circle', got: {}", s)),
+        }
+    }
+}
+
+// Geometry in module units, tested at pixel centers.
+#[derive(Clone, Copy)]
+struct Rect {
+    x: f32,
+    y: f32,
+    size: f32,
+    radius: f32,
+}
+
+impl Rect {
+    // `rounded` flags the top-left, top-right, bottom-left and bottom-right corners.
+    fn contains(&self, px: f32, py: f32, rounded: [bool; 4]) -> bool {
+        let (right, bottom) = (self.x + self.size, self.y + self.size);
+        if px < self.x || py < self.y || px >= right || py >= bottom {
+            return false;
+        }
+        let r = self.radius;
+        let corners = [
+            (self.x + r, self.y + r, px < self.x + r && py < self.y + r),
+            (right - r, self.y + r, px > right - r && py < self.y + r),
+            (self.x + r, bottom - r, px < self.x + r && py > bottom - r),
+            (right - r, bottom - r, px > right - r && py > bottom - r),
+        ];
+        corners.iter().zip(rounded).all(|(&(cx, cy, inside_corner), rounded)| {
+            !(rounded && inside_corner) || (px - cx).powi(2) + (py - cy).powi(2) <= r * r
+        })
+    }
+}
+
+fn circle_contains(cx: f32, cy: f32, diameter: f32, px: f32, py: f32) -> bool {
+    (px - cx).powi(2) + (py - cy).powi(2) <= (diameter / 2.0).powi(2)
+}
+
+// Top-left corners of the finder patterns, in modules.
+fn finder_origins(symbol: &Symbol) -> Vec<(usize, usize)> {
+    let far = symbol.width() - 7;
+    if symbol.version().is_micro() {
+        vec![(0, 0)]
+    } else {
+        vec![(0, 0), (far, 0), (0, far)]
+    }
+}
+
+// Whether the point (in modules, relative to the finder's corner) is dark.
+fn finder_contains(shape: FinderShape, px: f32, py: f32) -> bool {
+    match shape {
+        FinderShape::Square => {
+            let ring = px < 1.0 || py < 1.0 || px >= 6.0 || py >= 6.0;
+            let center = (2.0..5.0).contains(&px) && (2.0..5.0).contains(&py);
+            ring || center
+        }
+        FinderShape::Rounded => {
+            let outer = Rect { x: 0.0, y: 0.0, size: 7.0, radius: 1.0 };
+            let inner = Rect { x: 1.0, y: 1.0, size: 5.0, radius: 0.5 };
+            let center = Rect { x: 2.0, y: 2.0, size: 3.0, radius: 0.5 };
+            (outer.contains(px, py, [true; 4]) && !inner.contains(px, py, [true; 4]))
+                || center.contains(px, py, [true; 4])
+        }
+        FinderShape::Circle => {
+            (circle_contains(3.5, 3.5, 7.0, px, py) && !circle_contains(3.5, 3.5, 5.0, px, py))
+                || circle_contains(3.5, 3.5, 3.0, px, py)
+        }
+    }
+}
+
+/// Draws `symbol` with the shapes and logo of `options.style`.
+pub(crate) fn render(symbol: &Symbol, options: &EncodeOptions) -> AppResult<RgbaImage> {
+    let style = &options.style;
+    let unit = crate::encode::module_pixels(symbol, options);
+    let quiet_zone = options.quiet_zone_for(symbol.version());
+    let width = symbol.width();
+    let size = (width as u32 + 2 * quiet_zone) * unit;
+    let mut image = RgbaImage::from_pixel(size, size, options.light_color);
+
+    let finders = finder_origins(symbol);
+    let in_finder = |x: usize, y: usize| finders.iter().any(|&(fx, fy)| (fx..fx + 7).contains(&x) && (fy..fy + 7).contains(&y));
+    let is_dark = |x: isize, y: isize| {
+        x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < width && symbol.is_dark(x as usize, y as usize)
+    };
+
+    let offset = quiet_zone * unit;
+    let scale = unit as f32;
+    for y in 0..width {
+        for x in 0..width {
+            if in_finder(x, y) || !symbol.is_dark(x, y) {
+                continue;
+            }
+            let (xi, yi) = (x as isize, y as isize);
+            // A corner is rounded only where neither neighbour on that side is dark.
+            let rounded = [
+                !is_dark(xi - 1, yi) && !is_dark(xi, yi - 1),
+                !is_dark(xi + 1, yi) && !is_dark(xi, yi - 1),
+                !is_dark(xi - 1, yi) && !is_dark(xi, yi + 1),
+                !is_dark(xi + 1, yi) && !is_dark(xi, yi + 1),
+            ];
+            for dy in 0..unit {
+                for dx in 0..unit {
+                    let (px, py) = ((dx as f32 + 0.5) / scale, (dy as f32 + 0.5) / scale);
+                    let dark = match style.modules {
+                        ModuleShape::Square => true,
+                        ModuleShape::Rounded => Rect { x: 0.0, y: 0.0, size: 1.0, radius: 0.5 }.contains(px, py, rounded),
+                        ModuleShape::Dots => circle_contains(0.5, 0.5, 0.9, px, py),
+                    };
+                    if dark {
+                        image.put_pixel(offset + x as u32 * unit + dx, offset + y as u32 * unit + dy, options.dark_color);
+                    }
+                }
+            }
+        }
+    }
+
+    for &(fx, fy) in &finders {
+        for dy in 0..7 * unit {
+            for dx in 0..7 * unit {
+                let (px, py) = ((dx as f32 + 0.5) / scale, (dy as f32 + 0.5) / scale);
+                if finder_contains(style.finders, px, py) {
+                    image.put_pixel(offset + fx as u32 * unit + dx, offset + fy as u32 * unit + dy, options.dark_color);
+                }
+            }
+        }
+    }
+
+    if let Some(path) = &style.logo {
+        draw_logo(&mut image, path, (width as u32 * unit) as f32 * style.logo_size, unit, options)?;
+    }
+    Ok(image)
+}
+
+// Centers the logo on a plate of the light color, one module wider on every side.
+fn draw_logo(image: &mut RgbaImage, path: &std::path::Path, box_size: f32, unit: u32, options: &EncodeOptions) -> AppResult<()> {
+    let box_size = (box_size.round() as u32).max(1);
+    let logo = open_image(path)?.resize(box_size, box_size, FilterType::Lanczos3).to_rgba8();
+    let plate_width = logo.width() + 2 * unit;
+    let plate_height = logo.height() + 2 * unit;
+    let plate_color = Rgba([options.light_color[0], options.light_color[1], options.light_color[2], 255]);
+    let plate = RgbaImage::from_pixel(plate_width, plate_height, plate_color);
+
+    let x = (image.width() as i64 - plate_width as i64) / 2;
+    let y = (image.height() as i64 - plate_height as i64) / 2;
+    image::imageops::replace(image, &plate, x, y);
+    image::imageops::overlay(image, &logo, x + unit as i64, y + unit as i64);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use image::DynamicImage;
+
+    use super::*;
+    use crate::decode::{DecodeMode, DecodeOptions, decode_image};
+    use crate::encode::{encode, render_image};
+
+    const TEXT: &str = "https://example.com/styled?id=12345";
+
+    fn reads_back(style: Style) -> bool {
+        let options = EncodeOptions { style, ..EncodeOptions::default() };
+        let image = DynamicImage::ImageRgba8(render_image(&encode(TEXT, &options).unwrap(), &options).unwrap());
+        decode_image(&image, &DecodeOptions { mode: DecodeMode::FirstHit, ..DecodeOptions::default() }).iter().any(|result| result.content.as_str() == TEXT)
+    }
+
+    #[test]
+    fn every_shape_still_scans() {
+        for modules in [ModuleShape::Square, ModuleShape::Rounded, ModuleShape::Dots] {
+            for finders in [FinderShape::Square, FinderShape::Rounded, FinderShape::Circle] {
+                assert!(reads_back(Style { modules, finders, ..Style::default() }), "{} modules, {} finders", modules, finders);
+            }
+        }
+    }
+
+    #[test]
+    fn largest_logo_still_scans() {
+        let path = std::env::temp_dir().join(format!("{}-logo-{}.png", env!("CARGO_PKG_NAME"), std::process::id()));
+        // A busy logo with dark and light areas, the hardest case for the decoder.
+        RgbaImage::from_fn(64, 64, |x, y| if (x / 8 + y / 8) % 2 == 0 { Rgba([20, 20, 20, 255]) } else { Rgba([230, 60, 60, 255]) })
+            .save(&path)
+            .unwrap();
+        let styles = [
+            Style { logo: Some(path.clone()), logo_size: 0.35, ..Style::default() },
+            Style { logo: Some(path.clone()), logo_size: 0.35, modules: ModuleShape::Dots, finders: FinderShape::Circle },
+        ];
+        let results: Vec<bool> = styles.into_iter().map(reads_back).collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(results, [true, true]);
+    }
+
+    #[test]
+    fn rejects_oversized_logos() {
+        assert!(Style { logo_size: 0.36, ..Style::default() }.validate().is_err());
+        assert!(Style { logo_size: 0.04, ..Style::default() }.validate().is_err());
+        assert!(Style { logo: Some("missing-logo.png".into()), ..Style::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use std::fmt;
+use std::io::{Read, Write};
+use std::str::FromStr;
+use anyhow::Context;
+use data_encoding::BASE64;
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use qrcode::EcLevel;
+use zeroize::Zeroizing;
+
+use crate::AppResult;
+use crate::bitstream::ALPHANUMERIC_CHARSET;
+use crate::encode::{EncodeOptions, Symbol, encode_bytes};
+use crate::structured_append::{MAX_PARTS, encode_structured_append};
+
+/// How file bytes are written into QR codes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Transport {
+    /// The bytes as they are, in byte mode; the most compact, but some scanner apps mangle
+    /// bytes that are not valid text.
+    #[default]
+    Raw,
+    /// Base45 text (RFC 9285), which fits the alphanumeric mode with about 3% overhead.
+    Base45,
+    /// Base64 text, readable by any scanner, with a third more data.
+    Base64,
+}
+
+impl Transport {
+    pub const ALL: [Transport; 3] = [Transport::Raw, Transport::Base45, Transport::Base64];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Transport::Raw => "raw",
+            Transport::Base45 => "base45",
+            Transport::Base64 => "base64",
+        }
+    }
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Transport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        Transport::ALL.into_iter()
+            .find(|transport| s.eq_ignore_ascii_case(transport.name()))
+            .ok_or_else(|| format!("Expected 'raw', 'base45' or 'base64', got: {}", s))
+    }
+}
+
+/// Prepares `data` for transfer: compressed with zlib when `compress` is set, then
+/// written out for `transport`.
+pub fn pack(data: &[u8], transport: Transport, compress: bool) -> AppResult<Zeroizing<Vec<u8>>> {
+    let data = if compress {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(data).context("Could not compress the data.")?;
+        Zeroizing::new(encoder.finish().context("Could not compress the data.")?)
+    } else {
+        Zeroizing::new(data.to_vec())
+    };
+    Ok(match transport {
+        Transport::Raw => data,
+        Transport::Base45 => Zeroizing::new(encode_base45(&data).into_bytes()),
+        Transport::Base64 => Zeroizing::new(BASE64.encode(&data).into_bytes()),
+    })
+}
+
+/// Reverses [`pack`]: reads `data` as `transport` text, then inflates it when `decompress`
+/// is set.
+pub fn unpack(data: &[u8], transport: Transport, decompress: bool) -> AppResult<Zeroizing<Vec<u8>>> {
+    let text = || std::str::from_utf8(data).map(str::trim).context(format!("The payload is not {} text.", transport));
+    let data = match transport {
+        Transport::Raw => Zeroizing::new(data.to_vec()),
+        Transport::Base45 => decode_base45(text()?)?,
+        Transport::Base64 => Zeroizing::new(BASE64.decode(text()?.as_bytes())
+            .map_err(|_| anyhow::anyhow!("The payload is not valid base64."))?),
+    };
+    if !decompress {
+        return Ok(data);
+    }
+    let mut inflated = Zeroizing::new(Vec::new());
+    ZlibDecoder::new(data.as_slice()).read_to_end(&mut inflated)
+        .context("The payload is not zlib-compressed data.")?;
+    Ok(inflated)
+}
+
+// Every two bytes become three characters, least significant first; a last odd byte
+// becomes two.
+fn encode_base45(data: &[u8]) -> String {
+    let mut text = String::with_capacity(data.len().div_ceil(2) * 3);
+    for chunk in data.chunks(2) {
+        let (mut value, digits) = match chunk {
+            [a, b] => ((*a as usize) << 8 | *b as usize, 3),
+            [a] => (*a as usize, 2),
+            _ => unreachable!("chunks of two"),
+        };
+        for _ in 0..digits {
+            text.push(ALPHANUMERIC_CHARSET[value % 45] as char);
+            value /= 45;
+        }
+    }
+    text
+}
+
+fn decode_base45(text: &str) -> AppResult<Zeroizing<Vec<u8>>> {
+    let invalid = || anyhow::anyhow!("The payload is not valid base45.");
+    let values = Zeroizing::new(text.bytes()
+        .map(|c| ALPHANUMERIC_CHARSET.iter().position(|&a| a == c).ok_or_else(invalid))
+        .collect::<AppResult<Vec<usize>>>()?);
+    let mut data = Zeroizing::new(Vec::with_capacity(values.len() / 3 * 2 + 1));
+    for chunk in values.chunks(3) {
+        let value = chunk.iter().rev().fold(0, |acc, v| acc * 45 + v);
+        match chunk.len() {
+            3 if value <= 0xffff => data.extend_from_slice(&(value as u16).to_be_bytes()),
+            2 if value <= 0xff => data.push(value as u8),
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(data)
+}
+
+/// Encodes `data` in as few QR codes as possible, using the highest error correction level
+/// that needs no more codes than the lowest.
+///
+/// `options.ec_level` is ignored; the chosen level can be read from the symbols. Data too
+/// long for one code is split into a Structured Append sequence.
+pub fn encode_fitted(data: &[u8], options: &EncodeOptions) -> AppResult<Vec<Symbol>> {
+    let levels: &[EcLevel] = if options.micro {
+        &[EcLevel::Q, EcLevel::M, EcLevel::L]
+    } else {
+        &[EcLevel::H, EcLevel::Q, EcLevel::M, EcLevel::L]
+    };
+    let with_level = |ec_level| EncodeOptions { ec_level, ..options.clone() };
+    for &ec_level in levels {
+        if let Ok(symbol) = encode_bytes(data, &with_level(ec_level)) {
+            return Ok(vec![symbol]);
+        }
+    }
+
+    let mut best: Option<Vec<Symbol>> = None;
+    for &ec_level in levels {
+        if let Ok(symbols) = encode_structured_append(data, &with_level(ec_level))
+            && best.as_ref().is_none_or(|best| symbols.len() < best.len()) {
+            best = Some(symbols);
+        }
+    }
+    best.context(format!("Data is too long for {} QR codes ({} bytes).", MAX_PARTS, data.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_base45_like_rfc_9285() {
+        assert_eq!(encode_base45(b"AB"), "BB8");
+        assert_eq!(encode_base45(b"Hello!!"), "%69 VD92EX0");
+        assert_eq!(encode_base45(b"base-45"), "UJCLQE7W581");
+        assert_eq!(decode_base45("QED8WEX0").unwrap().as_slice(), b"ietf!");
+        assert!(decode_base45("GGW").is_err());
+        assert!(decode_base45("a").is_err());
+    }
+
+    #[test]
+    fn unpack_reverses_pack() {
+        let data: Vec<u8> = (0..=255).chain(b"repeated text, repeated text".iter().copied()).collect();
+        for transport in Transport::ALL {
+            for compress in [false, true] {
+                let packed = pack(&data, transport, compress).unwrap();
+                assert_eq!(unpack(&packed, transport, compress).unwrap().as_slice(), data, "{} {}", transport, compress);
+            }
+        }
+        assert!(unpack(b"not zlib", Transport::Raw, true).is_err());
+    }
+
+    #[test]
+    fn fits_the_highest_level_in_one_code() {
+        let symbols = encode_fitted(b"short", &EncodeOptions::default()).unwrap();
+        assert_eq!(symbols.len(), 1);
+        assert_eq!(symbols[0].ec_level(), EcLevel::H);
+
+        // Too long for level H in version 40, but not for Q.
+        let symbols = encode_fitted(&[b'x'; 1500], &EncodeOptions::default()).unwrap();
+        assert_eq!(symbols.len(), 1);
+        assert_eq!(symbols[0].ec_level(), EcLevel::Q);
+    }
+
+    #[test]
+    fn splits_with_the_fewest_codes() {
+        let options = EncodeOptions { version: Some(1), fixed_version: true, ..EncodeOptions::default() };
+        let data = b"split across several tiny symbols";
+        let symbols = encode_fitted(data, &options).unwrap();
+        let fewest = encode_structured_append(data, &EncodeOptions { ec_level: EcLevel::L, ..options.clone() }).unwrap().len();
+        assert_eq!(symbols.len(), fewest);
+        // No higher level manages the same number of codes.
+        let level = symbols[0].ec_level();
+        for higher in [EcLevel::H, EcLevel::Q, EcLevel::M].into_iter().filter(|&higher| higher > level) {
+            let count = encode_structured_append(data, &EncodeOptions { ec_level: higher, ..options.clone() }).map_or(usize::MAX, |s| s.len());
+            assert!(count > fewest);
+        }
+
+        assert!(encode_fitted(&[b'x'; 2000], &options).is_err());
+    }
+}
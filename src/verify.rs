@@ -0,0 +1,254 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "pdf")]
+use anyhow::Context;
+use image::{DynamicImage, Rgba, RgbaImage};
+use image::imageops::FilterType;
+use zeroize::Zeroizing;
+
+use crate::AppResult;
+use crate::decode::{DecodeMode, DecodeOptions, decode_image};
+use crate::encode::{EncodeOptions, RenderFormat, Symbol, module_pixels, render_image};
+
+/// Damage a printed or shared QR code commonly suffers, simulated on the rendered image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Degradation {
+    /// Shrunk to half its size, as in a thumbnail or a photo taken from afar.
+    Downscale,
+    /// Gaussian blur of a quarter module, as from an out-of-focus camera.
+    Blur,
+    /// Saved as a low-quality JPEG, as by messaging apps.
+    #[cfg(feature = "jpeg")]
+    Jpeg,
+}
+
+impl Degradation {
+    pub const ALL: &[Degradation] = &[
+        Degradation::Downscale,
+        Degradation::Blur,
+        #[cfg(feature = "jpeg")]
+        Degradation::Jpeg,
+    ];
+
+    fn apply(self, image: &RgbaImage, unit: u32) -> AppResult<DynamicImage> {
+        let degraded = match self {
+            Degradation::Downscale => {
+                let (width, height) = ((image.width() / 2).max(1), (image.height() / 2).max(1));
+                DynamicImage::ImageRgba8(image::imageops::resize(image, width, height, FilterType::Triangle))
+            }
+            Degradation::Blur => DynamicImage::ImageRgba8(image::imageops::blur(image, unit as f32 / 4.0)),
+            #[cfg(feature = "jpeg")]
+            Degradation::Jpeg => {
+                let mut data = Vec::new();
+                let rgb = DynamicImage::ImageRgba8(image.clone()).to_rgb8();
+                image::codecs::jpeg::JpegEncoder::new_with_quality(&mut data, 30).encode_image(&rgb)?;
+                image::load_from_memory(&data)?
+            }
+        };
+        Ok(degraded)
+    }
+}
+
+impl fmt::Display for Degradation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Degradation::Downscale => write!(f, "downscale"),
+            Degradation::Blur => write!(f, "blur"),
+            #[cfg(feature = "jpeg")]
+            Degradation::Jpeg => write!(f, "JPEG"),
+        }
+    }
+}
+
+/// What a verification decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifiedFrom {
+    /// The files as they were written.
+    SavedFiles,
+    /// Images rendered from the symbols' module matrix, for formats the decoder cannot
+    /// read; the saved files themselves were not checked.
+    SymbolMatrix,
+}
+
+/// Outcome of decoding freshly generated symbols.
+#[derive(Debug, Clone)]
+pub struct Verification {
+    /// Whether the symbols decode to the original data.
+    pub readable: bool,
+    /// Each simulated degradation and whether the symbols still decoded after it; empty
+    /// unless robustness was checked.
+    pub robustness: Vec<(Degradation, bool)>,
+    pub from: VerifiedFrom,
+}
+
+impl Verification {
+    /// Number of degradations the symbols survived.
+    pub fn survived(&self) -> usize {
+        self.robustness.iter().filter(|(_, ok)| *ok).count()
+    }
+
+    /// A one-word summary of the robustness check, if one was run.
+    pub fn grade(&self) -> Option<&'static str> {
+        if self.robustness.is_empty() {
+            return None;
+        }
+        Some(match self.survived() {
+            n if n == self.robustness.len() => "robust",
+            0 => "fragile",
+            _ => "fair",
+        })
+    }
+}
+
+/// Renders `symbols` as they would be saved and checks they decode back to `data`.
+///
+/// Each symbol must be readable on its own; Structured Append parts are joined in order.
+/// Transparent areas are judged against a white background. With `robustness`, the check
+/// is repeated after every [`Degradation`]. Micro QR codes cannot be verified, since the
+/// decoder does not read them.
+pub fn verify(symbols: &[Symbol], data: &[u8], options: &EncodeOptions, robustness: bool) -> AppResult<Verification> {
+    check_decodable(symbols)?;
+    let images: Vec<(RgbaImage, u32)> = symbols.iter()
+        .map(|symbol| Ok((flatten(&render_image(symbol, options)?), module_pixels(symbol, options))))
+        .collect::<AppResult<_>>()?;
+    verify_images(&images, data, robustness, VerifiedFrom::SymbolMatrix)
+}
+
+/// Decodes the files `symbols` were saved to in `format` and checks they decode back to
+/// `data`.
+///
+/// `paths` holds one file per symbol, or a single contact sheet holding all of them. PNG
+/// files, and PDFs with the `pdf` feature, are read back from disk; other formats are
+/// checked as [`verify`] does, from the same symbols, which the result reports as
+/// [`VerifiedFrom::SymbolMatrix`].
+pub fn verify_saved(
+    paths: &[PathBuf],
+    format: RenderFormat,
+    symbols: &[Symbol],
+    data: &[u8],
+    options: &EncodeOptions,
+    robustness: bool,
+) -> AppResult<Verification> {
+    check_decodable(symbols)?;
+    let Some(symbol) = symbols.first() else {
+        anyhow::bail!("There are no codes to verify.");
+    };
+    let mut images = Vec::new();
+    for path in paths {
+        match load_saved(path, format)? {
+            Some(pages) => images.extend(pages.iter().map(|page| {
+                // PNGs keep the rendered module size; rasterized PDF pages are rescaled.
+                let unit = match format {
+                    RenderFormat::Png => module_pixels(symbol, options),
+                    _ => page.width() / (symbol.width() as u32 + 2 * options.quiet_zone_for(symbol.version())),
+                };
+                (flatten(&page.to_rgba8()), unit.max(1))
+            })),
+            None => return verify(symbols, data, options, robustness),
+        }
+    }
+    verify_images(&images, data, robustness, VerifiedFrom::SavedFiles)
+}
+
+fn check_decodable(symbols: &[Symbol]) -> AppResult<()> {
+    if symbols.iter().any(|symbol| symbol.version().is_micro()) {
+        anyhow::bail!("Micro QR codes cannot be read back by the decoder.");
+    }
+    Ok(())
+}
+
+// Images of a saved file as the decoder sees them; `None` for formats it cannot read.
+fn load_saved(path: &Path, format: RenderFormat) -> AppResult<Option<Vec<DynamicImage>>> {
+    match format {
+        RenderFormat::Png => Ok(Some(vec![crate::input::open_image(path)?])),
+        #[cfg(feature = "pdf")]
+        RenderFormat::Pdf => {
+            let pages = crate::input::open_pages(path)?.context("Saved file is not a PDF")?;
+            Ok(Some(pages.map(|page| page.map(|(_, image)| image)).collect::<AppResult<_>>()?))
+        }
+        _ => Ok(None),
+    }
+}
+
+// Each image comes with its module size in pixels.
+fn verify_images(images: &[(RgbaImage, u32)], data: &[u8], robustness: bool, from: VerifiedFrom) -> AppResult<Verification> {
+    let readable = reads_back(images.iter().map(|(image, _)| Ok(DynamicImage::ImageRgba8(image.clone()))), data)?;
+    let mut checks = Vec::new();
+    if robustness {
+        for &degradation in Degradation::ALL {
+            let survived = reads_back(images.iter().map(|(image, unit)| degradation.apply(image, *unit)), data)?;
+            checks.push((degradation, survived));
+        }
+    }
+    Ok(Verification { readable, robustness: checks, from })
+}
+
+fn flatten(image: &RgbaImage) -> RgbaImage {
+    let mut flat = RgbaImage::from_pixel(image.width(), image.height(), Rgba([255, 255, 255, 255]));
+    image::imageops::overlay(&mut flat, image, 0, 0);
+    flat
+}
+
+// Every image must hold at least one code. A contact sheet holds several, which are
+// joined in Structured Append order.
+fn reads_back(images: impl Iterator<Item = AppResult<DynamicImage>>, data: &[u8]) -> AppResult<bool> {
+    let decode_options = DecodeOptions { mode: DecodeMode::FirstHit, ..DecodeOptions::default() };
+    let mut bytes = Zeroizing::new(Vec::with_capacity(data.len()));
+    for image in images {
+        let mut results = decode_image(&image?, &decode_options);
+        if results.is_empty() {
+            return Ok(false);
+        }
+        results.sort_by_key(|result| result.structured_append.map(|header| header.part));
+        for result in &results {
+            bytes.extend_from_slice(&result.bytes);
+        }
+    }
+    Ok(bytes.as_slice() == data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::{encode, save_symbol};
+
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("{}-verify-{}-{}", env!("CARGO_PKG_NAME"), std::process::id(), name))
+    }
+
+    #[test]
+    fn reads_the_saved_png() {
+        let options = EncodeOptions::default();
+        let symbol = encode("saved", &options).unwrap();
+        let path = temp_file("saved.png");
+        save_symbol(&symbol, &path, RenderFormat::Png, &options).unwrap();
+        let verification = verify_saved(std::slice::from_ref(&path), RenderFormat::Png, std::slice::from_ref(&symbol), b"saved", &options, false).unwrap();
+        assert!(verification.readable);
+        assert_eq!(verification.from, VerifiedFrom::SavedFiles);
+
+        // A file that does not hold the symbol fails, even though the symbol itself is fine.
+        save_symbol(&encode("other", &options).unwrap(), &path, RenderFormat::Png, &options).unwrap();
+        let verification = verify_saved(std::slice::from_ref(&path), RenderFormat::Png, std::slice::from_ref(&symbol), b"saved", &options, false).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(!verification.readable);
+    }
+
+    #[test]
+    fn vector_formats_are_checked_from_the_symbol() {
+        let options = EncodeOptions::default();
+        let symbol = encode("vector", &options).unwrap();
+        let path = temp_file("vector.svg");
+        save_symbol(&symbol, &path, RenderFormat::Svg, &options).unwrap();
+        let verification = verify_saved(std::slice::from_ref(&path), RenderFormat::Svg, std::slice::from_ref(&symbol), b"vector", &options, false).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(verification.readable);
+        assert_eq!(verification.from, VerifiedFrom::SymbolMatrix);
+    }
+
+    #[test]
+    fn micro_qr_is_not_verified() {
+        let options = EncodeOptions { micro: true, ..EncodeOptions::default() };
+        let symbol = encode("12345", &options).unwrap();
+        assert!(verify(&[symbol], b"12345", &options, false).is_err());
+    }
+}